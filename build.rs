@@ -1,4 +1,9 @@
-use std::{env, fs::File, path::PathBuf};
+use std::{
+    env,
+    fs::File,
+    io::{self, Write},
+    path::PathBuf,
+};
 
 use gl_generator::{Api, DebugStructGenerator, Fallbacks, Profile, Registry, StructGenerator};
 
@@ -8,14 +13,82 @@ fn main() {
     println!("cargo:rerun-if-changed=build.rs");
 
     let mut file = File::create(dest.join("gl_bindings.rs")).unwrap();
+    let registry = Registry::new(Api::Gl, (4, 6), Profile::Core, Fallbacks::None, []);
 
     if cfg!(feature = "debug_gl_structs") {
-        Registry::new(Api::Gl, (4, 6), Profile::Core, Fallbacks::None, [])
+        registry
             .write_bindings(DebugStructGenerator, &mut file)
             .unwrap();
     } else {
-        Registry::new(Api::Gl, (4, 6), Profile::Core, Fallbacks::None, [])
-            .write_bindings(StructGenerator, &mut file)
-            .unwrap();
+        registry.write_bindings(StructGenerator, &mut file).unwrap();
+    }
+
+    let mut file = File::create(dest.join("gl_trace.rs")).unwrap();
+    write_trace_impl(&registry, &mut file).unwrap();
+}
+
+/// Writes an `impl TracedGl` with one recording wrapper per GL command. The struct itself lives in
+/// `src/trace.rs`, this only generates the forwarding methods.
+fn write_trace_impl(registry: &Registry, dest: &mut File) -> io::Result<()> {
+    writeln!(
+        dest,
+        "#[allow(non_snake_case, clippy::too_many_arguments, clippy::missing_safety_doc)]
+        impl TracedGl {{"
+    )?;
+
+    for cmd in &registry.cmds {
+        let fix_path = |ty: &str| {
+            ty.replace("__gl_imports::raw::", "std::os::raw::")
+                .replace("types::", "gl::types::")
+        };
+        let params = cmd
+            .params
+            .iter()
+            .map(|p| format!("{}: {}", p.ident, fix_path(&p.ty)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let idents = cmd
+            .params
+            .iter()
+            .map(|p| p.ident.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let args = cmd
+            .params
+            .iter()
+            .map(|p| {
+                if p.ty.contains("GLDEBUGPROC") {
+                    format!("(\"{}\", \"<callback>\".to_owned())", p.ident)
+                } else {
+                    format!("(\"{0}\", format!(\"{{:?}}\", {0}))", p.ident)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let (return_suffix, call, ret, tail) = if cmd.proto.ty == "()" {
+            (String::new(), "", "None", "")
+        } else {
+            (
+                format!(" -> {}", fix_path(&cmd.proto.ty)),
+                "let r = ",
+                "Some(format!(\"{:?}\", r))",
+                "r",
+            )
+        };
+
+        writeln!(
+            dest,
+            "#[inline]
+            pub unsafe fn {name}(&self, {params}){return_suffix} {{
+                {call}self.gl.{name}({idents});
+                if self.is_tracing() {{
+                    self.record(\"gl{name}\", vec![{args}], {ret});
+                }}
+                {tail}
+            }}",
+            name = cmd.proto.ident,
+        )?;
     }
+
+    writeln!(dest, "}}")
 }
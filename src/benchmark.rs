@@ -0,0 +1,176 @@
+use std::{f32::consts::TAU, fs, path::Path, time::Duration};
+
+use glam::{Mat4, Vec3};
+use serde::Serialize;
+
+use crate::{
+    camera::OrbitCamera,
+    error::{ensure, ModelLoadingError},
+    mesh::Aabb,
+};
+
+/// How [`crate::renderer::Renderer::run_benchmark`] renders.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BenchConfig {
+    /// Frames timed, spread evenly over one pass of the flythrough.
+    pub frames: u32,
+    /// Frames drawn before the timed ones and left out of the report, which covers the first use
+    /// of shaders and render targets the driver only finishes setting up then.
+    pub warmup_frames: u32,
+    /// Size of the offscreen framebuffer, independent of the window's.
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Default for BenchConfig {
+    /// Five seconds at 60 frames per second of 1080p.
+    fn default() -> Self {
+        Self {
+            frames: 300,
+            warmup_frames: 30,
+            width: 1920,
+            height: 1080,
+        }
+    }
+}
+
+impl BenchConfig {
+    pub(crate) fn validate(&self) -> Result<(), ModelLoadingError> {
+        ensure!(
+            self.frames > 0,
+            InvalidArgument,
+            "a benchmark needs at least one frame"
+        );
+        ensure!(
+            self.width > 0 && self.height > 0,
+            InvalidArgument,
+            "can't benchmark at {}x{}",
+            self.width,
+            self.height
+        );
+        Ok(())
+    }
+}
+
+/// Timings and throughput of a benchmark run, see [`BenchReport::to_json`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BenchReport {
+    pub frames: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Time the CPU spent submitting each frame's draws.
+    pub cpu: FrameTimeStats,
+    /// Time the GPU spent drawing each frame, or `None` if the context lacks
+    /// `GL_ARB_timer_query`.
+    pub gpu: Option<FrameTimeStats>,
+    /// Triangles drawn over every timed frame, after frustum culling.
+    pub triangles: u64,
+    /// `triangles` over the time from submitting the first timed frame to the GPU finishing the
+    /// last one.
+    pub triangles_per_second: f64,
+}
+
+impl BenchReport {
+    /// The report as pretty-printed JSON, for tracking in CI.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("reports have no maps to fail on")
+    }
+
+    /// Writes [`BenchReport::to_json`] to `path`.
+    pub fn save(&self, path: &Path) -> Result<(), ModelLoadingError> {
+        fs::write(path, self.to_json()).map_err(ModelLoadingError::file_io(path))
+    }
+}
+
+/// Distribution of frame times, in milliseconds.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct FrameTimeStats {
+    pub min_ms: f64,
+    pub avg_ms: f64,
+    pub max_ms: f64,
+    /// The time 99% of frames took at most, with the slowest frame of every hundred over it.
+    pub p99_ms: f64,
+}
+
+impl FrameTimeStats {
+    /// Stats of `times`, or `None` if there are none.
+    pub fn from_times(times: &[Duration]) -> Option<Self> {
+        let mut sorted = times.to_vec();
+        sorted.sort_unstable();
+        let ms = |time: Duration| time.as_secs_f64() * 1000.0;
+        // Nearest rank, so a single frame is its own 99th percentile.
+        let p99 = (sorted.len() * 99).div_ceil(100).checked_sub(1)?;
+        Some(Self {
+            min_ms: ms(*sorted.first()?),
+            avg_ms: ms(sorted.iter().sum::<Duration>()) / sorted.len() as f64,
+            max_ms: ms(*sorted.last()?),
+            p99_ms: ms(sorted[p99]),
+        })
+    }
+}
+
+/// View matrix of the flythrough a fraction `progress` of the way through, circling `bounds` once
+/// at a distance that keeps all of it in a `fov` high view while dipping closer and back, and
+/// swaying from below it to above it.
+pub(crate) fn flythrough_view(bounds: Option<Aabb>, fov: f32, progress: f32) -> Mat4 {
+    let (target, radius) = bounds.map_or((Vec3::ZERO, 1.0), |bounds| {
+        (bounds.center(), (bounds.size().length() / 2.0).max(1e-3))
+    });
+    let fit = radius / (fov / 2.0).sin();
+    let angle = TAU * progress;
+    OrbitCamera {
+        target,
+        distance: fit * (1.0 - 0.4 * (angle / 2.0).sin().powi(2)),
+        yaw: angle,
+        pitch: 0.4 * angle.sin(),
+        fov,
+    }
+    .view_matrix()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn millis(values: impl IntoIterator<Item = u64>) -> Vec<Duration> {
+        values.into_iter().map(Duration::from_millis).collect()
+    }
+
+    #[test]
+    fn stats_cover_min_avg_max_and_p99() {
+        let stats = FrameTimeStats::from_times(&millis((1..=100).rev())).unwrap();
+        assert_eq!(stats.min_ms, 1.0);
+        assert_eq!(stats.avg_ms, 50.5);
+        assert_eq!(stats.max_ms, 100.0);
+        assert_eq!(stats.p99_ms, 99.0);
+    }
+
+    #[test]
+    fn p99_of_few_frames_is_the_slowest() {
+        let stats = FrameTimeStats::from_times(&millis([4, 2, 8])).unwrap();
+        assert_eq!(stats.p99_ms, 8.0);
+        assert_eq!(
+            FrameTimeStats::from_times(&millis([3])).unwrap().p99_ms,
+            3.0
+        );
+        assert_eq!(FrameTimeStats::from_times(&[]), None);
+    }
+
+    #[test]
+    fn report_serializes_missing_gpu_times_as_null() {
+        let cpu = FrameTimeStats::from_times(&millis([2])).unwrap();
+        let report = BenchReport {
+            frames: 1,
+            width: 4,
+            height: 3,
+            cpu,
+            gpu: None,
+            triangles: 12,
+            triangles_per_second: 6000.0,
+        };
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(json["gpu"], serde_json::Value::Null);
+        assert_eq!(json["cpu"]["p99_ms"], 2.0);
+        assert_eq!(json["triangles"], 12);
+    }
+}
@@ -0,0 +1,306 @@
+use std::{f32::consts::PI, fs, path::Path};
+
+use glam::{vec3, Mat4, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{ensure, Context, ModelLoadingError};
+
+/// Pitch is kept just short of straight up/down so the view never flips over.
+const MAX_PITCH: f32 = 89f32.to_radians();
+/// Closest an orbit camera can zoom in, keeping it outside the model it's inspecting.
+const MIN_ORBIT_DISTANCE: f32 = 0.5;
+/// Furthest an orbit camera can zoom out, well within the far plane.
+const MAX_ORBIT_DISTANCE: f32 = 50.0;
+
+/// A free-look camera. Angles are in radians; a yaw of -90° looks down -Z.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Camera {
+    pub position: Vec3,
+    pub yaw: f32,
+    pub pitch: f32,
+    /// Vertical field of view.
+    pub fov: f32,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            position: vec3(0.0, 0.0, 3.0),
+            yaw: -90f32.to_radians(),
+            pitch: 0.0,
+            fov: 45f32.to_radians(),
+        }
+    }
+}
+
+impl Camera {
+    /// Unit vector the camera is looking along.
+    pub fn forward(&self) -> Vec3 {
+        direction(self.yaw, self.pitch)
+    }
+
+    /// Unit vector pointing to the camera's right, parallel to the ground.
+    pub fn right(&self) -> Vec3 {
+        self.forward().cross(Vec3::Y).normalize()
+    }
+
+    /// Turns the camera by the given angles, clamping the pitch to ±89°.
+    pub fn rotate(&mut self, yaw: f32, pitch: f32) {
+        self.yaw += yaw;
+        self.pitch = (self.pitch + pitch).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    pub fn view_matrix(&self) -> Mat4 {
+        Mat4::look_to_rh(self.position, self.forward(), Vec3::Y)
+    }
+
+    /// Writes the camera to `path` as JSON, to be read back with [`Camera::load`].
+    pub fn save(&self, path: &Path) -> Result<(), ModelLoadingError> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json).map_err(ModelLoadingError::file_io(path))
+    }
+
+    /// Reads a camera written by [`Camera::save`], rejecting values it couldn't have saved such
+    /// as a non-finite position. The pitch is clamped to ±89° as if rotated there.
+    pub fn load(path: &Path) -> Result<Self, ModelLoadingError> {
+        let json = fs::read_to_string(path).map_err(ModelLoadingError::file_io(path))?;
+        let mut camera: Self = serde_json::from_str(&json)
+            .with_context(|| format!("failed to parse {}", path.display()))?;
+        ensure!(
+            camera.position.is_finite() && camera.yaw.is_finite() && camera.pitch.is_finite(),
+            "{} has a non-finite position or angle",
+            path.display()
+        );
+        ensure!(
+            camera.fov > 0.0 && camera.fov < PI,
+            "{} has a field of view of {} radians, outside 0 to π",
+            path.display(),
+            camera.fov
+        );
+        camera.pitch = camera.pitch.clamp(-MAX_PITCH, MAX_PITCH);
+        Ok(camera)
+    }
+}
+
+/// A camera circling `target` at `distance`, for inspecting a model. The angles give the direction
+/// it looks in, the same way as [`Camera`]'s.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrbitCamera {
+    pub target: Vec3,
+    pub distance: f32,
+    pub yaw: f32,
+    pub pitch: f32,
+    /// Vertical field of view.
+    pub fov: f32,
+}
+
+impl Default for OrbitCamera {
+    fn default() -> Self {
+        Self::looking_from(&Camera::default(), 3.0)
+    }
+}
+
+impl OrbitCamera {
+    /// An orbit camera with the same view as `camera`, circling the point `distance` ahead of it.
+    pub fn looking_from(camera: &Camera, distance: f32) -> Self {
+        let distance = distance.clamp(MIN_ORBIT_DISTANCE, MAX_ORBIT_DISTANCE);
+        Self {
+            target: camera.position + camera.forward() * distance,
+            distance,
+            yaw: camera.yaw,
+            pitch: camera.pitch,
+            fov: camera.fov,
+        }
+    }
+
+    pub fn position(&self) -> Vec3 {
+        self.target - direction(self.yaw, self.pitch) * self.distance
+    }
+
+    /// Orbits around the target by the given angles, clamping the pitch to ±89°.
+    pub fn rotate(&mut self, yaw: f32, pitch: f32) {
+        self.yaw += yaw;
+        self.pitch = (self.pitch + pitch).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    /// Scales the distance to the target by `factor`, within a fixed min/max.
+    pub fn zoom(&mut self, factor: f32) {
+        self.distance = (self.distance * factor).clamp(MIN_ORBIT_DISTANCE, MAX_ORBIT_DISTANCE);
+    }
+
+    pub fn view_matrix(&self) -> Mat4 {
+        Mat4::look_at_rh(self.position(), self.target, Vec3::Y)
+    }
+}
+
+/// Whichever camera the scene is currently viewed through.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CameraMode {
+    Fly(Camera),
+    Orbit(OrbitCamera),
+}
+
+impl Default for CameraMode {
+    fn default() -> Self {
+        CameraMode::Fly(Camera::default())
+    }
+}
+
+impl CameraMode {
+    /// Switches between flying and orbiting while keeping the current view.
+    pub fn toggled(&self) -> Self {
+        match self {
+            CameraMode::Fly(camera) => CameraMode::Orbit(OrbitCamera::looking_from(camera, 3.0)),
+            CameraMode::Orbit(_) => CameraMode::Fly(self.fly_camera()),
+        }
+    }
+
+    /// A fly camera with the same view, or the fly camera itself.
+    pub fn fly_camera(&self) -> Camera {
+        match self {
+            CameraMode::Fly(camera) => *camera,
+            CameraMode::Orbit(orbit) => Camera {
+                position: orbit.position(),
+                yaw: orbit.yaw,
+                pitch: orbit.pitch,
+                fov: orbit.fov,
+            },
+        }
+    }
+
+    pub fn view_matrix(&self) -> Mat4 {
+        match self {
+            CameraMode::Fly(camera) => camera.view_matrix(),
+            CameraMode::Orbit(orbit) => orbit.view_matrix(),
+        }
+    }
+
+    pub fn fov(&self) -> f32 {
+        match self {
+            CameraMode::Fly(camera) => camera.fov,
+            CameraMode::Orbit(orbit) => orbit.fov,
+        }
+    }
+}
+
+/// Unit vector for a yaw and pitch, with a yaw of -90° pointing down -Z.
+fn direction(yaw: f32, pitch: f32) -> Vec3 {
+    vec3(
+        yaw.cos() * pitch.cos(),
+        pitch.sin(),
+        yaw.sin() * pitch.cos(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    /// A path in the temp directory unique to the test process and `name`, removed on drop.
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> Self {
+            Self(std::env::temp_dir().join(format!("camera-{}-{name}.json", std::process::id())))
+        }
+
+        fn with_contents(name: &str, contents: &str) -> Self {
+            let file = Self::new(name);
+            fs::write(&file.0, contents).unwrap();
+            file
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn camera_json(position: &str, yaw: &str, pitch: &str, fov: &str) -> String {
+        format!(r#"{{"position": {position}, "yaw": {yaw}, "pitch": {pitch}, "fov": {fov}}}"#)
+    }
+
+    #[test]
+    fn saved_cameras_load_back_the_same() {
+        let file = TempFile::new("round-trip");
+        let camera = Camera {
+            position: vec3(1.5, -2.0, 30.25),
+            yaw: 2.5,
+            pitch: -0.75,
+            fov: 1.2,
+        };
+        camera.save(&file.0).unwrap();
+        assert_eq!(Camera::load(&file.0).unwrap(), camera);
+    }
+
+    #[test]
+    fn missing_and_corrupt_files_are_errors() {
+        let err = Camera::load(&TempFile::new("missing").0).unwrap_err();
+        assert!(matches!(err, ModelLoadingError::FileIo { .. }), "{err:?}");
+        for (name, contents) in [
+            ("truncated", r#"{"position": [0, 0, 3], "yaw": -1.5"#),
+            ("not-json", "camera at the origin"),
+            (
+                "missing-field",
+                r#"{"position": [0, 0, 3], "yaw": 0, "pitch": 0}"#,
+            ),
+        ] {
+            let file = TempFile::with_contents(name, contents);
+            let err = Camera::load(&file.0).unwrap_err();
+            assert!(
+                format!("{err:#}").contains("failed to parse"),
+                "{name}: {err:#}"
+            );
+        }
+    }
+
+    #[test]
+    fn non_finite_values_are_rejected() {
+        // JSON has no NaN, which is saved as null and can't be read back as a number.
+        let file = TempFile::new("nan");
+        Camera {
+            pitch: f32::NAN,
+            ..Default::default()
+        }
+        .save(&file.0)
+        .unwrap();
+        assert!(Camera::load(&file.0).is_err());
+
+        // Numbers too large for an f32 read as infinity.
+        for (name, json) in [
+            ("infinite-x", camera_json("[1e39, 0, 3]", "0", "0", "0.8")),
+            (
+                "infinite-yaw",
+                camera_json("[0, 0, 3]", "-1e39", "0", "0.8"),
+            ),
+        ] {
+            let file = TempFile::with_contents(name, &json);
+            let err = Camera::load(&file.0).unwrap_err();
+            assert!(format!("{err:#}").contains("non-finite"), "{name}: {err:#}");
+        }
+    }
+
+    #[test]
+    fn fov_must_be_between_0_and_pi() {
+        for fov in ["0", "-0.5", "3.2", "1e39"] {
+            let file = TempFile::with_contents("fov", &camera_json("[0, 0, 3]", "0", "0", fov));
+            let err = Camera::load(&file.0).unwrap_err();
+            assert!(
+                format!("{err:#}").contains("field of view"),
+                "{fov}: {err:#}"
+            );
+        }
+    }
+
+    #[test]
+    fn pitch_past_straight_up_or_down_is_clamped() {
+        for (pitch, expected) in [("2.5", MAX_PITCH), ("-100", -MAX_PITCH), ("0.5", 0.5)] {
+            let json = camera_json("[0, 0, 3]", "0", pitch, "0.8");
+            let file = TempFile::with_contents("pitch", &json);
+            assert_eq!(Camera::load(&file.0).unwrap().pitch, expected, "{pitch}");
+        }
+    }
+}
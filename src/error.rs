@@ -0,0 +1,248 @@
+use std::{
+    fmt, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{gl::error_name, shader::ShaderStage};
+
+/// Everything the library can fail with, so callers can tell failures apart instead of only
+/// printing them.
+#[derive(Debug)]
+pub enum ModelLoadingError {
+    /// The window couldn't be created, or the event loop running it failed.
+    WindowCreation(String),
+    /// No GL context could be created, given a surface or made current.
+    ContextCreation(String),
+    /// A shader stage failed to compile, with the driver's info log.
+    ShaderCompile { stage: ShaderStage, log: String },
+    /// The shader stages compiled but failed to link, with the driver's info log.
+    ShaderLink { log: String },
+    /// Reading or writing `path` failed.
+    FileIo { path: PathBuf, source: io::Error },
+    /// A file or image is of a kind that can't be loaded, e.g. an unknown extension.
+    UnsupportedFormat(String),
+    /// Data being loaded is malformed, with what's wrong with it.
+    InvalidData(String),
+    /// An argument was out of range, or the call isn't allowed in the current state.
+    InvalidArgument(String),
+    /// A request for a model or a file it refers to failed, or the server answered it with an
+    /// error status.
+    Network(String),
+    /// A GL call failed with this error code, or a framebuffer with this completeness status.
+    GlError(u32),
+}
+
+impl ModelLoadingError {
+    /// For `map_err`, turning an I/O error into a [`ModelLoadingError::FileIo`] for `path`.
+    pub(crate) fn file_io(path: &Path) -> impl FnOnce(io::Error) -> Self + '_ {
+        move |source| ModelLoadingError::FileIo {
+            path: path.to_owned(),
+            source,
+        }
+    }
+
+    /// For `map_err` on loading or saving the image at `path`, keeping I/O errors as
+    /// [`ModelLoadingError::FileIo`].
+    pub(crate) fn image_file(path: &Path) -> impl FnOnce(image::ImageError) -> Self + '_ {
+        move |err| match err {
+            image::ImageError::IoError(source) => ModelLoadingError::FileIo {
+                path: path.to_owned(),
+                source,
+            },
+            err => ModelLoadingError::from(err).context(path.display()),
+        }
+    }
+
+    /// Prefixes the message of the variants that have one with `context`, like
+    /// `failed to parse model.obj: line 3: ...`. The others already say what they're about.
+    pub(crate) fn context(self, context: impl fmt::Display) -> Self {
+        let prefix = |message: String| format!("{context}: {message}");
+        match self {
+            ModelLoadingError::WindowCreation(message) => {
+                ModelLoadingError::WindowCreation(prefix(message))
+            }
+            ModelLoadingError::ContextCreation(message) => {
+                ModelLoadingError::ContextCreation(prefix(message))
+            }
+            ModelLoadingError::UnsupportedFormat(message) => {
+                ModelLoadingError::UnsupportedFormat(prefix(message))
+            }
+            ModelLoadingError::InvalidData(message) => {
+                ModelLoadingError::InvalidData(prefix(message))
+            }
+            ModelLoadingError::InvalidArgument(message) => {
+                ModelLoadingError::InvalidArgument(prefix(message))
+            }
+            ModelLoadingError::Network(message) => ModelLoadingError::Network(prefix(message)),
+            other => other,
+        }
+    }
+}
+
+impl fmt::Display for ModelLoadingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModelLoadingError::WindowCreation(message) => {
+                write!(f, "failed to create the window: {message}")
+            }
+            ModelLoadingError::ContextCreation(message) => {
+                write!(f, "failed to set up the GL context: {message}")
+            }
+            ModelLoadingError::ShaderCompile { stage, log } => {
+                write!(f, "failed to compile {stage} shader: {log}")
+            }
+            ModelLoadingError::ShaderLink { log } => {
+                write!(f, "failed to link shader program: {log}")
+            }
+            ModelLoadingError::FileIo { path, source } => {
+                write!(f, "failed to access {}: {source}", path.display())
+            }
+            ModelLoadingError::UnsupportedFormat(message)
+            | ModelLoadingError::InvalidData(message)
+            | ModelLoadingError::InvalidArgument(message)
+            | ModelLoadingError::Network(message) => write!(f, "{message}"),
+            ModelLoadingError::GlError(code) => {
+                write!(f, "GL error {} ({code:#x})", error_name(*code))
+            }
+        }
+    }
+}
+
+impl std::error::Error for ModelLoadingError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ModelLoadingError::FileIo { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<glutin::error::Error> for ModelLoadingError {
+    fn from(err: glutin::error::Error) -> Self {
+        ModelLoadingError::ContextCreation(err.to_string())
+    }
+}
+
+impl From<winit::error::EventLoopError> for ModelLoadingError {
+    fn from(err: winit::error::EventLoopError) -> Self {
+        ModelLoadingError::WindowCreation(err.to_string())
+    }
+}
+
+impl From<winit::raw_window_handle::HandleError> for ModelLoadingError {
+    fn from(err: winit::raw_window_handle::HandleError) -> Self {
+        ModelLoadingError::WindowCreation(err.to_string())
+    }
+}
+
+#[cfg(feature = "net")]
+impl From<reqwest::Error> for ModelLoadingError {
+    fn from(err: reqwest::Error) -> Self {
+        // The error's own message leaves out the cause, like a refused connection or a timeout.
+        let mut message = err.to_string();
+        let mut source = std::error::Error::source(&err);
+        while let Some(err) = source {
+            message = format!("{message}: {err}");
+            source = err.source();
+        }
+        ModelLoadingError::Network(message)
+    }
+}
+
+impl From<image::ImageError> for ModelLoadingError {
+    fn from(err: image::ImageError) -> Self {
+        match err {
+            image::ImageError::Unsupported(err) => {
+                ModelLoadingError::UnsupportedFormat(err.to_string())
+            }
+            err => ModelLoadingError::InvalidData(err.to_string()),
+        }
+    }
+}
+
+/// Errors from parsing file contents, which mean the file is malformed.
+macro_rules! invalid_data_from {
+    ($($error:ty),+) => {
+        $(impl From<$error> for ModelLoadingError {
+            fn from(err: $error) -> Self {
+                ModelLoadingError::InvalidData(err.to_string())
+            }
+        })+
+    };
+}
+
+invalid_data_from!(
+    serde_json::Error,
+    std::num::ParseFloatError,
+    std::num::ParseIntError,
+    std::str::Utf8Error,
+    std::string::FromUtf8Error,
+    std::array::TryFromSliceError,
+    base64::DecodeError
+);
+
+/// Adds context to errors like `anyhow::Context`, keeping the kind of error.
+pub(crate) trait Context<T> {
+    fn context(self, context: impl fmt::Display) -> Result<T, ModelLoadingError>;
+
+    fn with_context<C: fmt::Display>(
+        self,
+        context: impl FnOnce() -> C,
+    ) -> Result<T, ModelLoadingError>;
+}
+
+impl<T, E: Into<ModelLoadingError>> Context<T> for Result<T, E> {
+    fn context(self, context: impl fmt::Display) -> Result<T, ModelLoadingError> {
+        self.map_err(|err| err.into().context(context))
+    }
+
+    fn with_context<C: fmt::Display>(
+        self,
+        context: impl FnOnce() -> C,
+    ) -> Result<T, ModelLoadingError> {
+        self.map_err(|err| err.into().context(context()))
+    }
+}
+
+/// A missing value is invalid data, described by the context.
+impl<T> Context<T> for Option<T> {
+    fn context(self, context: impl fmt::Display) -> Result<T, ModelLoadingError> {
+        self.ok_or_else(|| ModelLoadingError::InvalidData(context.to_string()))
+    }
+
+    fn with_context<C: fmt::Display>(
+        self,
+        context: impl FnOnce() -> C,
+    ) -> Result<T, ModelLoadingError> {
+        self.ok_or_else(|| ModelLoadingError::InvalidData(context().to_string()))
+    }
+}
+
+/// Returns an error with a formatted message, like `anyhow::bail!`. It's
+/// [`ModelLoadingError::InvalidData`] unless another variant taking a message is named first, as
+/// in `bail!(InvalidArgument, "...")`.
+macro_rules! bail {
+    ($kind:ident, $($message:tt)+) => {
+        return Err($crate::error::ModelLoadingError::$kind(format!($($message)+)))
+    };
+    ($($message:tt)+) => {
+        $crate::error::bail!(InvalidData, $($message)+)
+    };
+}
+
+/// Bails unless the condition holds, like `anyhow::ensure!`, with an optional variant as for
+/// [`bail!`].
+macro_rules! ensure {
+    ($condition:expr, $kind:ident, $($message:tt)+) => {
+        // Negating a float comparison trips a clippy lint, so the condition is left as it is.
+        if $condition {
+        } else {
+            $crate::error::bail!($kind, $($message)+);
+        }
+    };
+    ($condition:expr, $($message:tt)+) => {
+        $crate::error::ensure!($condition, InvalidData, $($message)+)
+    };
+}
+
+pub(crate) use {bail, ensure};
@@ -0,0 +1,205 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// Number of recent frames the average frame time is taken over.
+const AVERAGE_FRAMES: usize = 60;
+
+/// Measures the time between frames and keeps a rolling average over the last few.
+#[derive(Debug, Clone)]
+pub struct FrameTimer {
+    last_frame: Instant,
+    frame_times: VecDeque<Duration>,
+}
+
+impl Default for FrameTimer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameTimer {
+    pub fn new() -> Self {
+        Self {
+            last_frame: Instant::now(),
+            frame_times: VecDeque::with_capacity(AVERAGE_FRAMES),
+        }
+    }
+
+    /// Restarts timing from now without recording a frame, e.g. after the loop was idle.
+    pub fn reset(&mut self) {
+        self.last_frame = Instant::now();
+    }
+
+    /// Marks the start of a new frame and returns the time since the previous one.
+    pub fn tick(&mut self) -> Duration {
+        let now = Instant::now();
+        let delta = now.duration_since(self.last_frame);
+        self.last_frame = now;
+
+        if self.frame_times.len() == AVERAGE_FRAMES {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(delta);
+        delta
+    }
+
+    /// Mean time between the recently recorded frames.
+    pub fn average_frame_time(&self) -> Duration {
+        if self.frame_times.is_empty() {
+            return Duration::ZERO;
+        }
+        self.frame_times.iter().sum::<Duration>() / self.frame_times.len() as u32
+    }
+
+    /// Frames per second over the recent frames, or 0 before any were recorded.
+    pub fn fps(&self) -> f32 {
+        let frame_time = self.average_frame_time().as_secs_f32();
+        if frame_time > 0.0 {
+            1.0 / frame_time
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Number of recent frames a [`FrameHistory`] keeps.
+const HISTORY_FRAMES: usize = 240;
+
+/// How long one frame took to draw.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FrameStats {
+    /// Time the CPU spent submitting the frame's draws, up to but not including the swap.
+    pub cpu_time: Duration,
+    /// GPU time of the latest frame it has finished, usually a frame or two behind. `None` when
+    /// the context can't time GPU work or no result has arrived yet.
+    pub gpu_time: Option<Duration>,
+}
+
+/// The stats of the last few hundred frames, for spotting slow frames an average would hide.
+#[derive(Debug, Clone, Default)]
+pub struct FrameHistory {
+    frames: VecDeque<FrameStats>,
+}
+
+impl FrameHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, stats: FrameStats) {
+        if self.frames.len() == HISTORY_FRAMES {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(stats);
+    }
+
+    /// The recorded frames, oldest first.
+    pub fn frames(&self) -> impl Iterator<Item = &FrameStats> {
+        self.frames.iter()
+    }
+
+    pub fn latest(&self) -> Option<&FrameStats> {
+        self.frames.back()
+    }
+
+    /// Counts of frames by CPU time, in buckets `bucket` wide starting from zero. The last
+    /// non-empty bucket ends the list, which is empty for a zero `bucket`.
+    pub fn cpu_histogram(&self, bucket: Duration) -> Vec<usize> {
+        histogram(self.frames.iter().map(|frame| frame.cpu_time), bucket)
+    }
+
+    /// Like [`FrameHistory::cpu_histogram`], over the frames with a GPU time.
+    pub fn gpu_histogram(&self, bucket: Duration) -> Vec<usize> {
+        histogram(
+            self.frames.iter().filter_map(|frame| frame.gpu_time),
+            bucket,
+        )
+    }
+}
+
+fn histogram(times: impl Iterator<Item = Duration>, bucket: Duration) -> Vec<usize> {
+    // Nanosecond buckets instead would take millions of entries for a millisecond frame.
+    if bucket.is_zero() {
+        return Vec::new();
+    }
+    let bucket = bucket.as_nanos();
+    let mut counts = Vec::new();
+    for time in times {
+        let index = (time.as_nanos() / bucket) as usize;
+        if index >= counts.len() {
+            counts.resize(index + 1, 0);
+        }
+        counts[index] += 1;
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(cpu_ms: u64, gpu_ms: Option<u64>) -> FrameStats {
+        FrameStats {
+            cpu_time: Duration::from_millis(cpu_ms),
+            gpu_time: gpu_ms.map(Duration::from_millis),
+        }
+    }
+
+    #[test]
+    fn history_keeps_the_latest_frames_oldest_first() {
+        let mut history = FrameHistory::new();
+        assert_eq!(history.latest(), None);
+        for ms in 0..HISTORY_FRAMES as u64 + 10 {
+            history.record(frame(ms, None));
+        }
+        let cpu_times: Vec<u64> = history
+            .frames()
+            .map(|frame| frame.cpu_time.as_millis() as u64)
+            .collect();
+        assert_eq!(
+            cpu_times,
+            (10..HISTORY_FRAMES as u64 + 10).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            history.latest(),
+            Some(&frame(HISTORY_FRAMES as u64 + 9, None))
+        );
+    }
+
+    #[test]
+    fn histogram_counts_frames_per_bucket() {
+        let mut history = FrameHistory::new();
+        for ms in [1, 4, 5, 9, 16, 5] {
+            history.record(frame(ms, None));
+        }
+        // 0-5, 5-10 and 15-20 ms, with the empty 10-15 ms bucket kept in between.
+        let bucket = Duration::from_millis(5);
+        assert_eq!(history.cpu_histogram(bucket), [2, 3, 0, 1]);
+        assert!(FrameHistory::new().cpu_histogram(bucket).is_empty());
+    }
+
+    #[test]
+    fn gpu_histogram_skips_frames_without_a_gpu_time() {
+        let mut history = FrameHistory::new();
+        for (cpu, gpu) in [(1, None), (2, Some(12)), (3, None), (4, Some(3))] {
+            history.record(frame(cpu, gpu));
+        }
+        let bucket = Duration::from_millis(10);
+        assert_eq!(history.gpu_histogram(bucket), [1, 1]);
+        assert_eq!(history.cpu_histogram(bucket), [4]);
+
+        let mut history = FrameHistory::new();
+        history.record(frame(1, None));
+        assert!(history.gpu_histogram(bucket).is_empty());
+    }
+
+    #[test]
+    fn zero_width_buckets_give_an_empty_histogram() {
+        let mut history = FrameHistory::new();
+        history.record(frame(1000, Some(1000)));
+        assert!(history.cpu_histogram(Duration::ZERO).is_empty());
+        assert!(history.gpu_histogram(Duration::ZERO).is_empty());
+    }
+}
@@ -0,0 +1,99 @@
+use glam::{Mat4, Vec3, Vec4};
+
+use crate::mesh::Aabb;
+
+/// The six planes bounding what a camera can see, for skipping geometry that's off-screen.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    /// Left, right, bottom, top, near and far, each as `(normal, distance)` with the normal
+    /// pointing into the frustum.
+    planes: [Vec4; 6],
+}
+
+impl Frustum {
+    /// Extracts the planes from a combined `projection * view` matrix (Gribb/Hartmann), giving
+    /// them in world space.
+    pub fn from_view_projection(view_projection: &Mat4) -> Self {
+        let rows = [0, 1, 2, 3].map(|i| view_projection.row(i));
+        let planes = [
+            rows[3] + rows[0],
+            rows[3] - rows[0],
+            rows[3] + rows[1],
+            rows[3] - rows[1],
+            rows[3] + rows[2],
+            rows[3] - rows[2],
+        ];
+        Self { planes }
+    }
+
+    /// Whether any part of `aabb` may be inside. Boxes near a corner of the frustum can pass
+    /// without being visible, but a box that's inside is never rejected.
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            // The corner furthest along the plane normal is the last to leave the frustum.
+            let normal = plane.truncate();
+            let corner = Vec3::select(normal.cmpge(Vec3::ZERO), aabb.max, aabb.min);
+            plane.dot(corner.extend(1.0)) >= 0.0
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use glam::vec3;
+
+    use super::*;
+
+    /// A square 90° view from 5 units along +Z towards the origin, so the frustum reaches as far to
+    /// either side as it is in front of the camera, from 0.1 to 100 units.
+    fn frustum() -> Frustum {
+        let projection = Mat4::perspective_rh_gl(FRAC_PI_2, 1.0, 0.1, 100.0);
+        let view = Mat4::look_at_rh(vec3(0.0, 0.0, 5.0), Vec3::ZERO, Vec3::Y);
+        Frustum::from_view_projection(&(projection * view))
+    }
+
+    fn aabb(min: Vec3, max: Vec3) -> Aabb {
+        Aabb { min, max }
+    }
+
+    #[test]
+    fn boxes_inside_are_kept() {
+        let frustum = frustum();
+        assert!(frustum.intersects_aabb(&aabb(Vec3::splat(-1.0), Vec3::ONE)));
+        assert!(frustum.intersects_aabb(&aabb(vec3(3.0, 3.0, -1.0), vec3(4.0, 4.0, 0.0))));
+        assert!(frustum.intersects_aabb(&aabb(vec3(0.0, 0.0, -90.0), vec3(1.0, 1.0, -89.0))));
+    }
+
+    #[test]
+    fn boxes_outside_are_culled() {
+        let frustum = frustum();
+        for (min, max, side) in [
+            (vec3(7.0, -1.0, -1.0), vec3(8.0, 1.0, 1.0), "right"),
+            (vec3(-8.0, -1.0, -1.0), vec3(-7.0, 1.0, 1.0), "left"),
+            (vec3(-1.0, 7.0, -1.0), vec3(1.0, 8.0, 1.0), "top"),
+            (vec3(-1.0, -8.0, -1.0), vec3(1.0, -7.0, 1.0), "bottom"),
+            (vec3(-1.0, -1.0, 5.0), vec3(1.0, 1.0, 5.05), "near"),
+            (vec3(-1.0, -1.0, 6.0), vec3(1.0, 1.0, 7.0), "behind"),
+            (vec3(-1.0, -1.0, -200.0), vec3(1.0, 1.0, -96.0), "far"),
+        ] {
+            assert!(!frustum.intersects_aabb(&aabb(min, max)), "{side}");
+        }
+    }
+
+    #[test]
+    fn boxes_straddling_a_plane_are_kept() {
+        let frustum = frustum();
+        for (min, max, side) in [
+            (vec3(4.0, -1.0, -1.0), vec3(6.0, 1.0, 1.0), "right"),
+            (vec3(-1.0, -6.0, -1.0), vec3(1.0, -4.0, 1.0), "bottom"),
+            (vec3(-1.0, -1.0, 4.0), vec3(1.0, 1.0, 6.0), "near"),
+            (vec3(-1.0, -1.0, -200.0), vec3(1.0, 1.0, -90.0), "far"),
+            // Containing the whole frustum, with corners outside every plane.
+            (Vec3::splat(-500.0), Vec3::splat(500.0), "all"),
+        ] {
+            assert!(frustum.intersects_aabb(&aabb(min, max)), "{side}");
+        }
+    }
+}
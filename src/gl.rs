@@ -1,6 +1,75 @@
-use std::ffi::CStr;
+use std::{ffi::CStr, os::raw::c_void};
 
-use crate::window::gl;
+use glutin::{
+    context::{ContextApi, ContextAttributesBuilder, GlProfile, Version},
+    prelude::GlDisplay,
+};
+use winit::raw_window_handle::RawWindowHandle;
+
+use crate::{error::ModelLoadingError, trace::TracedGl, window::gl};
+
+/// Version `build.rs` generates the bindings for. Direct state access, which the renderer uses
+/// when it can, needs at least 4.5.
+pub const TARGET_GL_VERSION: (u8, u8) = (4, 6);
+
+/// Oldest version the renderer works with, through the bind-to-edit path, as its shaders are
+/// GLSL 3.30.
+pub const MIN_GL_VERSION: (u8, u8) = (3, 3);
+
+/// Creates a core profile context of [`TARGET_GL_VERSION`], or of [`MIN_GL_VERSION`] if that
+/// isn't available, falling back to whatever the driver gives by default if neither is. That can
+/// be a legacy or compatibility context too old for the shaders, as on Mesa and macOS.
+///
+/// # Safety
+///
+/// `window`, if given, must be a valid window handle for the display.
+pub(crate) unsafe fn create_core_context<D: GlDisplay>(
+    display: &D,
+    config: &D::Config,
+    window: Option<RawWindowHandle>,
+) -> Result<D::NotCurrentContext, ModelLoadingError> {
+    for (major, minor) in [TARGET_GL_VERSION, MIN_GL_VERSION] {
+        let core_attributes = ContextAttributesBuilder::new()
+            .with_context_api(ContextApi::OpenGl(Some(Version::new(major, minor))))
+            .with_profile(GlProfile::Core)
+            .build(window);
+        match display.create_context(config, &core_attributes) {
+            Ok(context) => return Ok(context),
+            Err(err) => log::warn!("no GL {major}.{minor} core context ({err})"),
+        }
+    }
+    log::warn!("using the driver's default GL context");
+    let default_attributes = ContextAttributesBuilder::new().build(window);
+    Ok(display.create_context(config, &default_attributes)?)
+}
+
+/// Logs the version of the current context, warning if it's older than the bindings expect.
+pub fn log_context_version(gl: &gl::Gl) {
+    let (mut major, mut minor) = (0, 0);
+    unsafe {
+        gl.GetIntegerv(gl::MAJOR_VERSION, &mut major);
+        gl.GetIntegerv(gl::MINOR_VERSION, &mut minor);
+    }
+    log::info!("Created GL {major}.{minor} context");
+
+    let (target_major, target_minor) = TARGET_GL_VERSION;
+    let (min_major, min_minor) = MIN_GL_VERSION;
+    if (major, minor) < (min_major.into(), min_minor.into()) {
+        log::warn!(
+            "GL {major}.{minor} is older than the {min_major}.{min_minor} the shaders need, they \
+             will fail to compile"
+        );
+    } else if (major, minor) < (4, 5) {
+        log::warn!(
+            "GL {major}.{minor} has no direct state access, using the slower bind-to-edit path"
+        );
+    } else if (major, minor) < (target_major.into(), target_minor.into()) {
+        log::warn!(
+            "GL {major}.{minor} is older than the {target_major}.{target_minor} the bindings \
+             target, some functions may be unavailable"
+        );
+    }
+}
 
 pub fn get_gl_string(gl: &gl::Gl, variant: gl::types::GLenum) -> Option<&'static CStr> {
     unsafe {
@@ -8,3 +77,156 @@ pub fn get_gl_string(gl: &gl::Gl, variant: gl::types::GLenum) -> Option<&'static
         (!s.is_null()).then(|| CStr::from_ptr(s.cast()))
     }
 }
+
+/// Limits and extensions of the current context, for deciding at runtime which features are
+/// safe to use.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Largest width or height of a 2D texture.
+    pub max_texture_size: i32,
+    pub max_vertex_attribs: i32,
+    /// Most samples a multisampled renderbuffer or texture can have.
+    pub max_samples: i32,
+    /// Textures a fragment shader can sample from at once.
+    pub max_texture_image_units: i32,
+    /// Names of the supported extensions, e.g. `GL_ARB_direct_state_access`.
+    pub extensions: Vec<String>,
+}
+
+impl Capabilities {
+    pub fn query(gl: &gl::Gl) -> Self {
+        let integer = |name| {
+            let mut value = 0;
+            unsafe { gl.GetIntegerv(name, &mut value) };
+            value
+        };
+        let extensions = (0..integer(gl::NUM_EXTENSIONS).max(0) as gl::types::GLuint)
+            .filter_map(|index| unsafe {
+                let name = gl.GetStringi(gl::EXTENSIONS, index);
+                (!name.is_null())
+                    .then(|| CStr::from_ptr(name.cast()).to_string_lossy().into_owned())
+            })
+            .collect();
+        Self {
+            max_texture_size: integer(gl::MAX_TEXTURE_SIZE),
+            max_vertex_attribs: integer(gl::MAX_VERTEX_ATTRIBS),
+            max_samples: integer(gl::MAX_SAMPLES),
+            max_texture_image_units: integer(gl::MAX_TEXTURE_IMAGE_UNITS),
+            extensions,
+        }
+    }
+
+    pub fn has_extension(&self, name: &str) -> bool {
+        self.extensions.iter().any(|extension| extension == name)
+    }
+}
+
+/// Whether the renderer calls [`check_gl_error`] after uploads and draws. Polling `glGetError`
+/// stalls the pipeline, so it's off in release builds unless the `debug_gl_errors` feature is on.
+pub(crate) const CHECK_GL_ERRORS: bool = cfg!(any(debug_assertions, feature = "debug_gl_errors"));
+
+/// Drains the GL error flags, logging each error set since the last check along with `location`.
+pub fn check_gl_error(gl: &gl::Gl, location: &str) {
+    loop {
+        let error = unsafe { gl.GetError() };
+        if error == gl::NO_ERROR {
+            break;
+        }
+        log::error!("GL error {} ({error:#x}) at {location}", error_name(error));
+        // A lost context keeps reporting the loss instead of clearing it.
+        if error == gl::CONTEXT_LOST {
+            break;
+        }
+    }
+}
+
+pub(crate) fn error_name(error: gl::types::GLenum) -> &'static str {
+    match error {
+        gl::INVALID_ENUM => "GL_INVALID_ENUM",
+        gl::INVALID_VALUE => "GL_INVALID_VALUE",
+        gl::INVALID_OPERATION => "GL_INVALID_OPERATION",
+        gl::STACK_OVERFLOW => "GL_STACK_OVERFLOW",
+        gl::STACK_UNDERFLOW => "GL_STACK_UNDERFLOW",
+        gl::OUT_OF_MEMORY => "GL_OUT_OF_MEMORY",
+        gl::INVALID_FRAMEBUFFER_OPERATION => "GL_INVALID_FRAMEBUFFER_OPERATION",
+        gl::CONTEXT_LOST => "GL_CONTEXT_LOST",
+        _ => "unknown error",
+    }
+}
+
+/// Turns on `GL_DEBUG_OUTPUT` and forwards driver messages to the `log` crate, mapping the
+/// message severity to a log level. Notifications are disabled as most drivers emit one for nearly
+/// every buffer operation.
+pub fn enable_debug_output(gl: &TracedGl) {
+    // Debug output is core since 4.3, so older contexts may not have it.
+    if !gl.DebugMessageCallback.is_loaded() {
+        log::warn!("GL debug output is unavailable on this context");
+        return;
+    }
+    unsafe {
+        gl.Enable(gl::DEBUG_OUTPUT);
+        // Report messages on the thread and call that caused them, so the log lines up with the
+        // code.
+        gl.Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
+        gl.DebugMessageCallback(Some(debug_message_callback), std::ptr::null());
+        gl.DebugMessageControl(
+            gl::DONT_CARE,
+            gl::DONT_CARE,
+            gl::DEBUG_SEVERITY_NOTIFICATION,
+            0,
+            std::ptr::null(),
+            gl::FALSE,
+        );
+    }
+}
+
+extern "system" fn debug_message_callback(
+    source: gl::types::GLenum,
+    gltype: gl::types::GLenum,
+    id: gl::types::GLuint,
+    severity: gl::types::GLenum,
+    length: gl::types::GLsizei,
+    message: *const gl::types::GLchar,
+    _user_param: *mut c_void,
+) {
+    let message = unsafe { std::slice::from_raw_parts(message.cast::<u8>(), length as usize) };
+    let message = String::from_utf8_lossy(message);
+    let level = match severity {
+        gl::DEBUG_SEVERITY_HIGH => log::Level::Error,
+        gl::DEBUG_SEVERITY_MEDIUM => log::Level::Warn,
+        gl::DEBUG_SEVERITY_LOW => log::Level::Info,
+        _ => log::Level::Debug,
+    };
+    log::log!(
+        level,
+        "[GL {} {} {id}] {}",
+        debug_source_name(source),
+        debug_type_name(gltype),
+        message.trim_end()
+    );
+}
+
+fn debug_source_name(source: gl::types::GLenum) -> &'static str {
+    match source {
+        gl::DEBUG_SOURCE_API => "api",
+        gl::DEBUG_SOURCE_WINDOW_SYSTEM => "window system",
+        gl::DEBUG_SOURCE_SHADER_COMPILER => "shader compiler",
+        gl::DEBUG_SOURCE_THIRD_PARTY => "third party",
+        gl::DEBUG_SOURCE_APPLICATION => "application",
+        _ => "other",
+    }
+}
+
+fn debug_type_name(gltype: gl::types::GLenum) -> &'static str {
+    match gltype {
+        gl::DEBUG_TYPE_ERROR => "error",
+        gl::DEBUG_TYPE_DEPRECATED_BEHAVIOR => "deprecated",
+        gl::DEBUG_TYPE_UNDEFINED_BEHAVIOR => "undefined behavior",
+        gl::DEBUG_TYPE_PORTABILITY => "portability",
+        gl::DEBUG_TYPE_PERFORMANCE => "performance",
+        gl::DEBUG_TYPE_MARKER => "marker",
+        gl::DEBUG_TYPE_PUSH_GROUP => "push group",
+        gl::DEBUG_TYPE_POP_GROUP => "pop group",
+        _ => "other",
+    }
+}
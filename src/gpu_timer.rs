@@ -0,0 +1,77 @@
+use std::time::Duration;
+
+use crate::{gl::Capabilities, trace::TracedGl, window::gl};
+
+/// Spans that can be in flight at once. Results are read when the GPU has them, so this is how
+/// many frames late a result can be before its query is reused and the result lost.
+const QUERY_COUNT: usize = 4;
+
+/// Measures how long the GPU spends on the commands between [`GpuTimer::begin`] and
+/// [`GpuTimer::end`] with `GL_TIME_ELAPSED` queries. Waiting for a result would stall the CPU
+/// until the GPU caught up, so results come in a frame or two after the span they measure.
+#[derive(Debug)]
+pub struct GpuTimer {
+    queries: [gl::types::GLuint; QUERY_COUNT],
+    /// Ended queries whose results haven't been read yet, oldest first.
+    pending: Vec<usize>,
+    next: usize,
+    latest: Option<Duration>,
+}
+
+impl GpuTimer {
+    /// Creates the queries, or returns `None` if the context lacks `GL_ARB_timer_query`.
+    pub fn new(gl: &TracedGl) -> Option<Self> {
+        if !Capabilities::query(gl).has_extension("GL_ARB_timer_query") {
+            return None;
+        }
+        let mut queries = [0; QUERY_COUNT];
+        unsafe {
+            if gl.direct_state_access() {
+                gl.CreateQueries(gl::TIME_ELAPSED, QUERY_COUNT as i32, queries.as_mut_ptr());
+            } else {
+                gl.GenQueries(QUERY_COUNT as i32, queries.as_mut_ptr());
+            }
+        }
+        Some(Self {
+            queries,
+            pending: Vec::with_capacity(QUERY_COUNT),
+            next: 0,
+            latest: None,
+        })
+    }
+
+    /// Starts timing. Only one span can be timed at once.
+    pub fn begin(&mut self, gl: &TracedGl) {
+        // The oldest result is dropped if it still hasn't arrived.
+        self.pending.retain(|&query| query != self.next);
+        unsafe { gl.BeginQuery(gl::TIME_ELAPSED, self.queries[self.next]) };
+    }
+
+    /// Stops timing the span started by [`GpuTimer::begin`], and returns the GPU time of the
+    /// latest span whose result has arrived, if any has yet.
+    pub fn end(&mut self, gl: &TracedGl) -> Option<Duration> {
+        unsafe { gl.EndQuery(gl::TIME_ELAPSED) };
+        self.pending.push(self.next);
+        self.next = (self.next + 1) % QUERY_COUNT;
+
+        // Queries finish in order, so stop at the first one that hasn't.
+        while let Some(&query) = self.pending.first() {
+            let query = self.queries[query];
+            let mut available = 0;
+            unsafe { gl.GetQueryObjectiv(query, gl::QUERY_RESULT_AVAILABLE, &mut available) };
+            if available == 0 {
+                break;
+            }
+            let mut nanoseconds = 0;
+            unsafe { gl.GetQueryObjectui64v(query, gl::QUERY_RESULT, &mut nanoseconds) };
+            self.latest = Some(Duration::from_nanos(nanoseconds));
+            self.pending.remove(0);
+        }
+        self.latest
+    }
+
+    /// Deletes the GL queries. The `GpuTimer` must not be used afterwards.
+    pub fn delete(&self, gl: &TracedGl) {
+        unsafe { gl.DeleteQueries(QUERY_COUNT as i32, self.queries.as_ptr()) };
+    }
+}
@@ -0,0 +1,60 @@
+use glutin::{
+    api::egl::{context::PossiblyCurrentContext, device::Device, display::Display},
+    config::{ConfigSurfaceTypes, ConfigTemplateBuilder},
+    prelude::GlDisplay,
+};
+
+use crate::{
+    error::{Context, ModelLoadingError},
+    gl::create_core_context,
+    renderer::{Renderer, RendererSnapshot},
+};
+
+/// A current GL context without any window or surface, for rendering offscreen with
+/// [`Renderer::render_to_image`]. Uses the first EGL device, so it works without a display server.
+pub struct HeadlessContext {
+    display: Display,
+    _context: PossiblyCurrentContext,
+}
+
+impl HeadlessContext {
+    pub fn new() -> Result<Self, ModelLoadingError> {
+        let device = Device::query_devices()
+            .context("failed to query EGL devices")?
+            .next()
+            .ok_or_else(|| ModelLoadingError::ContextCreation("no EGL device available".into()))?;
+        let display = unsafe { Display::with_device(&device, None) }
+            .context("failed to create EGL display")?;
+
+        let template = ConfigTemplateBuilder::default()
+            .with_surface_type(ConfigSurfaceTypes::empty())
+            .build();
+        let config = unsafe { display.find_configs(template) }?
+            .next()
+            .ok_or_else(|| {
+                ModelLoadingError::ContextCreation("no surfaceless EGL config available".into())
+            })?;
+
+        let context = unsafe { create_core_context(&display, &config, None) }?
+            .make_current_surfaceless()
+            .context("failed to make headless context current")?;
+
+        Ok(Self {
+            display,
+            _context: context,
+        })
+    }
+
+    pub fn create_gl_renderer(&self) -> Result<Renderer, ModelLoadingError> {
+        Renderer::new(&self.display)
+    }
+
+    /// Builds a renderer on this context from one taken on another, see
+    /// [`Renderer::from_snapshot`].
+    pub fn create_gl_renderer_from_snapshot(
+        &self,
+        snapshot: &RendererSnapshot,
+    ) -> Result<Renderer, ModelLoadingError> {
+        Renderer::from_snapshot(&self.display, snapshot)
+    }
+}
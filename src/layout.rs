@@ -0,0 +1,333 @@
+use std::mem::offset_of;
+
+use bytemuck::pod_read_unaligned;
+use glam::Vec3;
+
+use crate::{
+    error::{bail, ensure, ModelLoadingError},
+    mesh::{Vertex, VERTEX_ATTRIBUTES},
+    trace::TracedGl,
+    window::gl,
+};
+
+/// Type of each component of a vertex attribute. Integer components are normalized, to 0..1 for
+/// unsigned types and -1..1 for signed ones, so shaders always read floats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttributeType {
+    F32,
+    U8,
+    I8,
+    U16,
+    I16,
+}
+
+impl AttributeType {
+    /// Size of one component in bytes.
+    pub fn size(self) -> usize {
+        match self {
+            AttributeType::U8 | AttributeType::I8 => 1,
+            AttributeType::U16 | AttributeType::I16 => 2,
+            AttributeType::F32 => 4,
+        }
+    }
+
+    fn gl_enum(self) -> gl::types::GLenum {
+        match self {
+            AttributeType::F32 => gl::FLOAT,
+            AttributeType::U8 => gl::UNSIGNED_BYTE,
+            AttributeType::I8 => gl::BYTE,
+            AttributeType::U16 => gl::UNSIGNED_SHORT,
+            AttributeType::I16 => gl::SHORT,
+        }
+    }
+}
+
+/// One field of a vertex, read by the shader input of the same name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attribute {
+    pub name: String,
+    pub components: usize,
+    pub ty: AttributeType,
+    /// Byte offset of the field from the start of the vertex.
+    pub offset: usize,
+}
+
+impl Attribute {
+    fn location(&self) -> gl::types::GLuint {
+        location_of(&self.name).expect("attribute names are checked by validate")
+    }
+}
+
+/// Describes which fields a vertex type has and where they are, so meshes can be built from
+/// any vertex format, e.g. positions only.
+///
+/// Attributes are matched to shader inputs by name, one of `position`, `color`, `normal`, `uv`
+/// and `tangent`. Every layout needs a 3 float `position`. Attributes left out read as white for
+/// `color` and zero otherwise, which the lit shader draws unlit.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AttributeLayout {
+    attributes: Vec<Attribute>,
+}
+
+impl AttributeLayout {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The layout of [`Vertex`], which the built-in loaders produce.
+    pub fn vertex() -> Self {
+        Self::new()
+            .attribute(
+                "position",
+                3,
+                AttributeType::F32,
+                offset_of!(Vertex, position),
+            )
+            .attribute("color", 3, AttributeType::F32, offset_of!(Vertex, color))
+            .attribute("normal", 3, AttributeType::F32, offset_of!(Vertex, normal))
+            .attribute("uv", 2, AttributeType::F32, offset_of!(Vertex, uv))
+            // Spans `bitangent_sign` too, which follows it.
+            .attribute(
+                "tangent",
+                4,
+                AttributeType::F32,
+                offset_of!(Vertex, tangent),
+            )
+    }
+
+    /// Adds an attribute with `components` values of type `ty` at byte `offset` into each vertex.
+    pub fn attribute(
+        mut self,
+        name: &str,
+        components: usize,
+        ty: AttributeType,
+        offset: usize,
+    ) -> Self {
+        self.attributes.push(Attribute {
+            name: name.to_owned(),
+            components,
+            ty,
+            offset,
+        });
+        self
+    }
+
+    pub fn attributes(&self) -> &[Attribute] {
+        &self.attributes
+    }
+
+    fn get(&self, name: &str) -> Option<&Attribute> {
+        self.attributes
+            .iter()
+            .find(|attribute| attribute.name == name)
+    }
+
+    /// Checks the layout describes a vertex of `vertex_size` bytes: every attribute is known,
+    /// appears once, has 1 to 4 components and fits inside the vertex.
+    pub fn validate(&self, vertex_size: usize) -> Result<(), ModelLoadingError> {
+        for (i, attribute) in self.attributes.iter().enumerate() {
+            let name = &attribute.name;
+            ensure!(
+                location_of(name).is_some(),
+                InvalidArgument,
+                "unknown vertex attribute {name:?}"
+            );
+            ensure!(
+                self.attributes[..i].iter().all(|other| &other.name != name),
+                InvalidArgument,
+                "vertex attribute {name:?} appears more than once"
+            );
+            ensure!(
+                (1..=4).contains(&attribute.components),
+                InvalidArgument,
+                "vertex attribute {name:?} has {} components, it needs 1 to 4",
+                attribute.components
+            );
+            let end = attribute.offset + attribute.components * attribute.ty.size();
+            ensure!(
+                end <= vertex_size,
+                InvalidArgument,
+                "vertex attribute {name:?} ends at byte {end}, past the {vertex_size} byte vertex"
+            );
+        }
+        match self.get("position") {
+            Some(Attribute {
+                components: 3,
+                ty: AttributeType::F32,
+                ..
+            }) => Ok(()),
+            Some(_) => bail!(
+                InvalidArgument,
+                "vertex attribute \"position\" must be 3 floats"
+            ),
+            None => bail!(
+                InvalidArgument,
+                "vertex layout has no \"position\" attribute"
+            ),
+        }
+    }
+
+    /// Reads the position of each `stride` byte vertex in `data`. The layout must be valid.
+    pub(crate) fn read_positions(&self, data: &[u8], stride: usize) -> Vec<Vec3> {
+        let offset = self.get("position").map_or(0, |position| position.offset);
+        data.chunks_exact(stride)
+            .map(|vertex| {
+                pod_read_unaligned::<[f32; 3]>(&vertex[offset..offset + size_of::<[f32; 3]>()])
+                    .into()
+            })
+            .collect()
+    }
+
+    /// Values the shader reads for the attributes the layout leaves out, by location.
+    pub(crate) fn missing_defaults(&self) -> Vec<(gl::types::GLuint, [f32; 4])> {
+        VERTEX_ATTRIBUTES
+            .into_iter()
+            .filter_map(|(location, name)| {
+                let name = name.to_str().expect("attribute names are ASCII");
+                let default = match name {
+                    "color" => [1.0; 4],
+                    _ => [0.0, 0.0, 0.0, 1.0],
+                };
+                self.get(name).is_none().then_some((location, default))
+            })
+            .collect()
+    }
+
+    /// Describes the layout on `vao` with direct state access, reading from binding 0.
+    pub(crate) unsafe fn configure_dsa(&self, gl: &TracedGl, vao: gl::types::GLuint) {
+        for attribute in &self.attributes {
+            let location = attribute.location();
+            gl.EnableVertexArrayAttrib(vao, location);
+            gl.VertexArrayAttribFormat(
+                vao,
+                location,
+                attribute.components as i32,
+                attribute.ty.gl_enum(),
+                (attribute.ty != AttributeType::F32).into(),
+                attribute.offset as u32,
+            );
+            gl.VertexArrayAttribBinding(vao, location, 0);
+        }
+    }
+
+    /// Describes the layout on the bound VAO, reading from the bound `GL_ARRAY_BUFFER`.
+    pub(crate) unsafe fn configure_bound(&self, gl: &TracedGl, stride: usize) {
+        for attribute in &self.attributes {
+            let location = attribute.location();
+            gl.EnableVertexAttribArray(location);
+            gl.VertexAttribPointer(
+                location,
+                attribute.components as i32,
+                attribute.ty.gl_enum(),
+                (attribute.ty != AttributeType::F32).into(),
+                stride as gl::types::GLsizei,
+                attribute.offset as *const _,
+            );
+        }
+    }
+}
+
+fn location_of(name: &str) -> Option<gl::types::GLuint> {
+    VERTEX_ATTRIBUTES
+        .into_iter()
+        .find_map(|(location, attribute)| {
+            (attribute.to_bytes() == name.as_bytes()).then_some(location)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions_only() -> AttributeLayout {
+        AttributeLayout::new().attribute("position", 3, AttributeType::F32, 0)
+    }
+
+    fn assert_invalid(layout: &AttributeLayout, vertex_size: usize, message: &str) {
+        let err = layout.validate(vertex_size).unwrap_err();
+        assert!(
+            matches!(&err, ModelLoadingError::InvalidArgument(text) if text.contains(message)),
+            "{err:?} isn't about {message:?}"
+        );
+    }
+
+    #[test]
+    fn vertex_layout_is_valid() {
+        assert!(AttributeLayout::vertex()
+            .validate(size_of::<Vertex>())
+            .is_ok());
+        assert!(positions_only().validate(12).is_ok());
+        // Padding after the last attribute is fine.
+        assert!(positions_only().validate(16).is_ok());
+        let packed = positions_only()
+            .attribute("color", 4, AttributeType::U8, 12)
+            .attribute("uv", 2, AttributeType::U16, 16);
+        assert!(packed.validate(20).is_ok());
+    }
+
+    #[test]
+    fn attributes_must_be_known_and_unique() {
+        assert_invalid(
+            &positions_only().attribute("weight", 1, AttributeType::F32, 12),
+            16,
+            "unknown vertex attribute \"weight\"",
+        );
+        assert_invalid(
+            &positions_only().attribute("position", 3, AttributeType::F32, 12),
+            24,
+            "more than once",
+        );
+    }
+
+    #[test]
+    fn attributes_need_1_to_4_components_inside_the_vertex() {
+        for components in [0, 5] {
+            assert_invalid(
+                &positions_only().attribute("uv", components, AttributeType::F32, 12),
+                40,
+                &format!("has {components} components"),
+            );
+        }
+        assert_invalid(
+            &positions_only().attribute("normal", 3, AttributeType::F32, 12),
+            20,
+            "ends at byte 24, past the 20 byte vertex",
+        );
+        assert_invalid(&positions_only(), 8, "ends at byte 12");
+    }
+
+    #[test]
+    fn position_must_be_3_floats() {
+        assert_invalid(
+            &AttributeLayout::new().attribute("normal", 3, AttributeType::F32, 0),
+            12,
+            "no \"position\" attribute",
+        );
+        assert_invalid(
+            &AttributeLayout::new().attribute("position", 2, AttributeType::F32, 0),
+            8,
+            "must be 3 floats",
+        );
+        assert_invalid(
+            &AttributeLayout::new().attribute("position", 3, AttributeType::I16, 0),
+            6,
+            "must be 3 floats",
+        );
+    }
+
+    #[test]
+    fn left_out_attributes_read_as_white_or_zero() {
+        let defaults = positions_only()
+            .attribute("normal", 3, AttributeType::F32, 12)
+            .missing_defaults();
+        let location = |name| location_of(name).unwrap();
+        assert_eq!(
+            defaults,
+            [
+                (location("color"), [1.0; 4]),
+                (location("uv"), [0.0, 0.0, 0.0, 1.0]),
+                (location("tangent"), [0.0, 0.0, 0.0, 1.0]),
+            ]
+        );
+    }
+}
@@ -1,3 +1,27 @@
+pub mod benchmark;
+pub mod camera;
+pub mod error;
+pub mod frame_timer;
+pub mod frustum;
 pub mod gl;
+pub mod gpu_timer;
+// glutin has no EGL backend on Apple platforms.
+#[cfg(not(target_vendor = "apple"))]
+pub mod headless;
+pub mod layout;
+pub mod light;
+pub mod material;
+pub mod mesh;
+pub mod mtl;
+#[cfg(feature = "net")]
+pub mod net;
+pub mod obj;
+pub mod ply;
 pub mod renderer;
+pub mod scene;
+pub mod shader;
+pub mod stl;
+pub mod texture;
+pub mod trace;
+pub mod transform;
 pub mod window;
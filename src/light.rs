@@ -0,0 +1,30 @@
+use glam::Vec3;
+
+use crate::{shader::ShaderProgram, trace::TracedGl};
+
+/// Most lights the built-in lit program takes. The `uLights` array is sized to match.
+pub const MAX_LIGHTS: usize = 8;
+
+/// A light shining on the scene, in world space.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Light {
+    /// Light from infinitely far away travelling along `dir`, like the sun.
+    Directional { dir: Vec3, color: Vec3 },
+    /// Light spreading out from `pos`, fading to nothing at `range`.
+    Point { pos: Vec3, color: Vec3, range: f32 },
+}
+
+impl Light {
+    /// Sets element `index` of the `uLights` uniform array on `program`.
+    pub(crate) fn apply(&self, gl: &TracedGl, program: &ShaderProgram, index: usize) {
+        let field = |name: &str| format!("uLights[{index}].{name}");
+        let (point, vector, color, range) = match *self {
+            Light::Directional { dir, color } => (false, dir.normalize_or_zero(), color, 0.0),
+            Light::Point { pos, color, range } => (true, pos, color, range),
+        };
+        program.set_uniform_bool(gl, &field("point"), point);
+        program.set_uniform_vec3(gl, &field("vector"), vector);
+        program.set_uniform_vec3(gl, &field("color"), color);
+        program.set_uniform_f32(gl, &field("range"), range);
+    }
+}
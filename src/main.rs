@@ -1,15 +1,28 @@
+use std::path::Path;
+
 use glutin::prelude::NotCurrentGlContext;
-use model_loading::window::GfWindow;
+use model_loading::{error::ModelLoadingError, window::GfWindow};
 use winit::event_loop::EventLoop;
 
 fn main() -> anyhow::Result<()> {
-    let event_loop = EventLoop::builder().build().unwrap();
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
+    let event_loop = EventLoop::builder().build()?;
 
-    let window = GfWindow::new(&event_loop)?;
+    let mut window = GfWindow::new(&event_loop)?;
 
     let surface = window.create_window_surface()?;
-    let context = window.create_context()?.make_current(&surface)?;
-    let renderer = window.create_gl_renderer();
+    let context = window
+        .create_context()?
+        .make_current(&surface)
+        .map_err(ModelLoadingError::from)?;
+    let renderer = match std::env::args_os().nth(1) {
+        Some(path) => window.create_gl_renderer_from_file(Path::new(&path))?,
+        None => window.create_gl_renderer()?,
+    };
+    if cfg!(debug_assertions) {
+        renderer.enable_debug_output();
+    }
 
-    window.run(event_loop, surface, renderer, context)
+    Ok(window.run(event_loop, surface, renderer, context)?)
 }
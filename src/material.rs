@@ -0,0 +1,75 @@
+use std::path::PathBuf;
+
+use glam::Vec3;
+
+use crate::{shader::ShaderProgram, trace::TracedGl};
+
+/// Phong reflectance of a surface. The ambient and diffuse terms scale the vertex color and
+/// texture, while the specular highlight is added on top.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Material {
+    pub ambient: Vec3,
+    pub diffuse: Vec3,
+    pub specular: Vec3,
+    /// Phong exponent; higher values give smaller, sharper highlights.
+    pub shininess: f32,
+    /// How much of the skybox the surface mirrors, from 0 for none to 1 for a perfect mirror.
+    /// Without a skybox there's nothing to reflect, so it has no effect.
+    pub reflectivity: f32,
+    /// Opacity multiplied into the texture's alpha. Meshes below 1 are transparent, and are
+    /// blended as set by [`crate::renderer::Renderer::set_blend_mode`].
+    pub alpha: f32,
+    /// Tangent-space normal map perturbing the surface normal, loaded by the renderer the first
+    /// time a mesh using it is drawn. The mesh needs tangents, see
+    /// [`crate::mesh::Mesh::compute_tangents`].
+    pub normal_map: Option<PathBuf>,
+}
+
+impl Default for Material {
+    /// A matte surface, as meshes were lit before materials existed.
+    fn default() -> Self {
+        Self {
+            ambient: Vec3::splat(0.2),
+            diffuse: Vec3::splat(0.8),
+            specular: Vec3::ZERO,
+            shininess: 32.0,
+            reflectivity: 0.0,
+            alpha: 1.0,
+            normal_map: None,
+        }
+    }
+}
+
+impl Material {
+    pub fn is_transparent(&self) -> bool {
+        self.alpha < 1.0
+    }
+
+    /// A polished surface mirroring `strength` (clamped to 0 to 1) of the skybox, with a sharp
+    /// white highlight, e.g. 0.9 for chrome.
+    pub fn reflective(strength: f32) -> Self {
+        Self {
+            specular: Vec3::ONE,
+            shininess: 128.0,
+            reflectivity: strength.clamp(0.0, 1.0),
+            ..Self::default()
+        }
+    }
+
+    /// Adds the normal map image at `path`, with X, Y and Z in the red, green and blue channels
+    /// and Y pointing along increasing V.
+    pub fn with_normal_map(mut self, path: impl Into<PathBuf>) -> Self {
+        self.normal_map = Some(path.into());
+        self
+    }
+
+    /// Sets the `uMaterial` uniform struct on `program`.
+    pub fn apply(&self, gl: &TracedGl, program: &ShaderProgram) {
+        program.set_uniform_vec3(gl, "uMaterial.ambient", self.ambient);
+        program.set_uniform_vec3(gl, "uMaterial.diffuse", self.diffuse);
+        program.set_uniform_vec3(gl, "uMaterial.specular", self.specular);
+        program.set_uniform_f32(gl, "uMaterial.shininess", self.shininess);
+        program.set_uniform_f32(gl, "uMaterial.reflectivity", self.reflectivity);
+        program.set_uniform_f32(gl, "uMaterial.alpha", self.alpha);
+    }
+}
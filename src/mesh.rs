@@ -0,0 +1,1491 @@
+use std::{collections::HashMap, ffi::CStr, path::Path};
+
+use bytemuck::{cast_slice, Pod, Zeroable};
+use glam::{BVec3, DVec3, Mat3, Mat4, Vec2, Vec3};
+use image::RgbaImage;
+
+use crate::{
+    error::{bail, ensure, ModelLoadingError},
+    gl::{check_gl_error, CHECK_GL_ERRORS},
+    layout::AttributeLayout,
+    material::Material,
+    obj::load_obj,
+    ply::load_ply,
+    stl::load_stl,
+    texture::Texture,
+    trace::TracedGl,
+    window::gl,
+};
+
+/// Attribute locations every [`crate::shader::ShaderProgram`] is linked with, so one VAO layout
+/// works with any program.
+pub(crate) const VERTEX_ATTRIBUTES: [(gl::types::GLuint, &CStr); 5] = [
+    (0, c"position"),
+    (1, c"color"),
+    (2, c"normal"),
+    (3, c"uv"),
+    // After the four locations of `INSTANCE_MATRIX_ATTRIBUTE`.
+    (8, c"tangent"),
+];
+
+/// First of the four consecutive locations the `mat4` per-instance transform is read from, one
+/// column each. It's only backed by a buffer during [`crate::renderer::Renderer::draw_instanced`].
+pub(crate) const INSTANCE_MATRIX_ATTRIBUTE: (gl::types::GLuint, &CStr) = (4, c"instance_matrix");
+
+/// Location of the per-vertex mean curvature, backed by its own buffer once
+/// [`Mesh::compute_curvature`] has been called and 0 before.
+pub(crate) const CURVATURE_ATTRIBUTE: (gl::types::GLuint, &CStr) = (9, c"curvature");
+
+#[repr(C)]
+#[derive(Debug, Pod, Clone, Copy, Zeroable)]
+pub struct Vertex {
+    pub position: Vec3,
+    pub color: Vec3,
+    pub normal: Vec3,
+    pub uv: Vec2,
+    /// Direction of increasing U across the surface, perpendicular to the normal, for normal
+    /// mapping. Zero until [`MeshData::compute_tangents`] fills it in.
+    pub tangent: Vec3,
+    /// Whether the direction of increasing V is `normal × tangent` (1) or the opposite (-1), as
+    /// with mirrored UVs. Shaders read it as the `w` of a `vec4 tangent`.
+    pub bitangent_sign: f32,
+}
+impl Default for Vertex {
+    fn default() -> Self {
+        Self::zeroed()
+    }
+}
+
+/// An axis-aligned bounding box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    /// Smallest box containing every point, or `None` if there are none.
+    pub fn from_points(points: impl IntoIterator<Item = Vec3>) -> Option<Self> {
+        points.into_iter().fold(None, |aabb: Option<Self>, point| {
+            Some(match aabb {
+                Some(aabb) => Self {
+                    min: aabb.min.min(point),
+                    max: aabb.max.max(point),
+                },
+                None => Self {
+                    min: point,
+                    max: point,
+                },
+            })
+        })
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    /// Box containing this one after it's transformed by `matrix`.
+    pub fn transformed(&self, matrix: &Mat4) -> Self {
+        let corners = (0..8).map(|i| {
+            let corner = Vec3::select(
+                BVec3::new(i & 1 != 0, i & 2 != 0, i & 4 != 0),
+                self.max,
+                self.min,
+            );
+            matrix.transform_point3(corner)
+        });
+        Self::from_points(corners).expect("a box has corners")
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) / 2.0
+    }
+
+    pub fn size(&self) -> Vec3 {
+        self.max - self.min
+    }
+}
+
+/// Indexed triangle geometry on the CPU side, for processing before it's uploaded as a [`Mesh`].
+#[derive(Debug, Clone, Default)]
+pub struct MeshData {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    /// Where the origin of the vertex positions is in the coordinates they were loaded in, for
+    /// positions moved closer to it to keep their precision as `f32`. Zero otherwise.
+    pub origin_offset: DVec3,
+}
+
+impl MeshData {
+    /// Whether any vertex has a normal, as opposed to the zero vector loaders use for a missing
+    /// one.
+    pub fn has_normals(&self) -> bool {
+        self.vertices
+            .iter()
+            .any(|vertex| vertex.normal != Vec3::ZERO)
+    }
+
+    /// Replaces every vertex normal with the average of the normals of the triangles sharing it,
+    /// weighted by triangle area so slivers don't skew the result.
+    pub fn compute_smooth_normals(&mut self) {
+        for vertex in &mut self.vertices {
+            vertex.normal = Vec3::ZERO;
+        }
+        for triangle in self.indices.chunks_exact(3) {
+            // The cross product's length is twice the triangle's area, which gives the weighting.
+            let normal = self.face_normal(triangle);
+            for &index in triangle {
+                self.vertices[index as usize].normal += normal;
+            }
+        }
+        for vertex in &mut self.vertices {
+            vertex.normal = vertex.normal.normalize_or_zero();
+        }
+    }
+
+    /// Computes each vertex's tangent from the UVs of the triangles sharing it, as described by
+    /// Lengyel, for tangent-space normal mapping. The normals have to be computed first, as the
+    /// tangents are made perpendicular to them. Triangles whose UVs have no area say nothing about
+    /// the tangent and are skipped, and vertices without a triangle to go by get an arbitrary
+    /// tangent perpendicular to their normal.
+    pub fn compute_tangents(&mut self) {
+        // Sums of the U and V directions of the triangles around each vertex.
+        let mut u_dirs = vec![Vec3::ZERO; self.vertices.len()];
+        let mut v_dirs = vec![Vec3::ZERO; self.vertices.len()];
+        for triangle in self.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| self.vertices[triangle[i] as usize]);
+            let (edge1, edge2) = (b.position - a.position, c.position - a.position);
+            let (uv1, uv2) = (b.uv - a.uv, c.uv - a.uv);
+            let det = uv1.perp_dot(uv2);
+            if det.abs() <= f32::EPSILON {
+                continue;
+            }
+            let u_dir = (edge1 * uv2.y - edge2 * uv1.y) / det;
+            let v_dir = (edge2 * uv1.x - edge1 * uv2.x) / det;
+            for &index in triangle {
+                u_dirs[index as usize] += u_dir;
+                v_dirs[index as usize] += v_dir;
+            }
+        }
+        for ((vertex, u_dir), v_dir) in self.vertices.iter_mut().zip(u_dirs).zip(v_dirs) {
+            let normal = vertex.normal;
+            // Gram-Schmidt, so the tangent is perpendicular to the normal.
+            let tangent = (u_dir - normal * normal.dot(u_dir)).normalize_or_zero();
+            vertex.tangent = match tangent {
+                Vec3::ZERO if normal != Vec3::ZERO => normal.any_orthonormal_vector(),
+                Vec3::ZERO => Vec3::X,
+                tangent => tangent,
+            };
+            vertex.bitangent_sign = if normal.cross(vertex.tangent).dot(v_dir) < 0.0 {
+                -1.0
+            } else {
+                1.0
+            };
+        }
+    }
+
+    /// Discrete mean curvature at each vertex, from the cotangent-weighted Laplacian over the
+    /// triangles around it and their mixed Voronoi areas (Meyer et al.). Positive where the
+    /// surface curves away from its normal like the outside of a sphere, whose curvature is one
+    /// over its radius. Vertices sharing a position are treated as one, so seams split for UVs or
+    /// normals don't break the one-ring, and ones on an open boundary are 0.
+    pub fn compute_curvature(&self) -> Vec<f32> {
+        // Index of the first vertex at each distinct position.
+        let mut welded = HashMap::new();
+        let ids: Vec<usize> = self
+            .vertices
+            .iter()
+            .enumerate()
+            .map(|(index, vertex)| {
+                *welded
+                    .entry(vertex.position.to_array().map(f32::to_bits))
+                    .or_insert(index)
+            })
+            .collect();
+
+        let mut laplacians = vec![Vec3::ZERO; self.vertices.len()];
+        let mut normals = vec![Vec3::ZERO; self.vertices.len()];
+        let mut areas = vec![0.0f32; self.vertices.len()];
+        // Triangles on each side of each edge, which is one along a boundary.
+        let mut edges: HashMap<(usize, usize), u32> = HashMap::new();
+        for triangle in self.indices.chunks_exact(3) {
+            let corners = [0, 1, 2].map(|i| ids[triangle[i] as usize]);
+            let [a, b, c] = corners.map(|id| self.vertices[id].position);
+            let normal = (b - a).cross(c - a);
+            let area = normal.length() / 2.0;
+            if area <= f32::EPSILON {
+                continue;
+            }
+            let positions = [a, b, c];
+            let cots = [0, 1, 2].map(|i| {
+                let p = positions[i];
+                let (u, v) = (positions[(i + 1) % 3] - p, positions[(i + 2) % 3] - p);
+                u.dot(v) / u.cross(v).length()
+            });
+            let obtuse = cots.iter().position(|&cot| cot < 0.0);
+            for i in 0..3 {
+                let (next, prev) = ((i + 1) % 3, (i + 2) % 3);
+                let corner = corners[i];
+                let (q, r) = (positions[next], positions[prev]);
+                // The angle at a corner weights the edge opposite it.
+                laplacians[corners[next]] += cots[i] * (r - q);
+                laplacians[corners[prev]] += cots[i] * (q - r);
+                normals[corner] += normal;
+                // The mixed area: the corner's Voronoi region, unless that reaches outside an
+                // obtuse triangle.
+                areas[corner] += match obtuse {
+                    None => {
+                        let p = positions[i];
+                        (p.distance_squared(r) * cots[next] + p.distance_squared(q) * cots[prev])
+                            / 8.0
+                    }
+                    Some(obtuse) if obtuse == i => area / 2.0,
+                    Some(_) => area / 4.0,
+                };
+                let other = corners[next];
+                *edges
+                    .entry((corner.min(other), corner.max(other)))
+                    .or_insert(0) += 1;
+            }
+        }
+        let mut boundary = vec![false; self.vertices.len()];
+        for ((a, b), count) in edges {
+            if count == 1 {
+                boundary[a] = true;
+                boundary[b] = true;
+            }
+        }
+
+        ids.iter()
+            .map(|&id| {
+                if boundary[id] || areas[id] == 0.0 {
+                    return 0.0;
+                }
+                // The Laplacian is -2H times the normal.
+                let laplacian = laplacians[id] / (2.0 * areas[id]);
+                -laplacian.dot(normals[id].normalize_or_zero()) / 2.0
+            })
+            .collect()
+    }
+
+    /// Gives every triangle its own three vertices with the face normal, for a faceted look.
+    pub fn compute_flat_normals(&mut self) {
+        let mut vertices = Vec::with_capacity(self.indices.len());
+        for triangle in self.indices.chunks_exact(3) {
+            let normal = self.face_normal(triangle).normalize_or_zero();
+            vertices.extend(triangle.iter().map(|&index| Vertex {
+                normal,
+                ..self.vertices[index as usize]
+            }));
+        }
+        self.indices = (0..vertices.len() as u32).collect();
+        self.vertices = vertices;
+    }
+
+    /// Adds the vertices and indices of `other` with `matrix` applied to them, e.g. to combine
+    /// meshes positioned by their own model matrices. Mirroring matrices reverse the winding to
+    /// keep the triangles facing the same way.
+    pub fn append_transformed(&mut self, other: &MeshData, matrix: &Mat4) {
+        let offset = self.vertices.len() as u32;
+        let normal_matrix = Mat3::from_mat4(*matrix).inverse().transpose();
+        // Mirroring flips the bitangent along with the winding.
+        let mirrored = matrix.determinant() < 0.0;
+        self.vertices
+            .extend(other.vertices.iter().map(|vertex| Vertex {
+                position: matrix.transform_point3(vertex.position),
+                normal: (normal_matrix * vertex.normal).normalize_or_zero(),
+                tangent: matrix.transform_vector3(vertex.tangent).normalize_or_zero(),
+                bitangent_sign: if mirrored {
+                    -vertex.bitangent_sign
+                } else {
+                    vertex.bitangent_sign
+                },
+                ..*vertex
+            }));
+        let start = self.indices.len();
+        self.indices
+            .extend(other.indices.iter().map(|&index| index + offset));
+        if mirrored {
+            for triangle in self.indices[start..].chunks_exact_mut(3) {
+                triangle.swap(1, 2);
+            }
+        }
+    }
+
+    /// Unnormalized normal of a counter-clockwise triangle.
+    fn face_normal(&self, triangle: &[u32]) -> Vec3 {
+        let [a, b, c] = [0, 1, 2].map(|i| self.vertices[triangle[i] as usize].position);
+        (b - a).cross(c - a)
+    }
+}
+
+/// Reads the first `size` bytes of `buffer`.
+fn read_buffer(gl: &TracedGl, buffer: gl::types::GLuint, size: usize) -> Vec<u8> {
+    let mut data = vec![0u8; size];
+    unsafe {
+        if gl.direct_state_access() {
+            gl.GetNamedBufferSubData(buffer, 0, size as isize, data.as_mut_ptr().cast());
+        } else {
+            // The copy target isn't part of any VAO's state, unlike the element array binding.
+            gl.BindBuffer(gl::COPY_WRITE_BUFFER, buffer);
+            gl.GetBufferSubData(
+                gl::COPY_WRITE_BUFFER,
+                0,
+                size as isize,
+                data.as_mut_ptr().cast(),
+            );
+            gl.BindBuffer(gl::COPY_WRITE_BUFFER, 0);
+        }
+    }
+    data
+}
+
+/// Checks that every index is one of `vertex_count` vertices, and that there are whole
+/// primitives of `mode`.
+pub(crate) fn validate_indices(
+    indices: &[u32],
+    vertex_count: usize,
+    mode: PrimitiveMode,
+) -> Result<(), ModelLoadingError> {
+    if let Some(&index) = indices
+        .iter()
+        .find(|&&index| index as usize >= vertex_count)
+    {
+        bail!(
+            InvalidData,
+            "index {index} is past the last of {vertex_count} vertices"
+        );
+    }
+    ensure!(
+        indices.len().is_multiple_of(mode.index_count()),
+        InvalidData,
+        "{} indices don't divide into {mode:?} of {} each",
+        indices.len(),
+        mode.index_count()
+    );
+    Ok(())
+}
+
+/// Triangle indices stored in the narrowest type that can address every vertex.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IndexBuffer {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+impl IndexBuffer {
+    /// Narrows `indices` to 16 bits when all `vertex_count` vertices can be addressed that way,
+    /// and every index fits.
+    pub fn new(indices: &[u32], vertex_count: usize) -> Self {
+        if vertex_count <= usize::from(u16::MAX) + 1
+            && indices.iter().all(|&index| index <= u32::from(u16::MAX))
+        {
+            IndexBuffer::U16(indices.iter().map(|&index| index as u16).collect())
+        } else {
+            IndexBuffer::U32(indices.to_vec())
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            IndexBuffer::U16(indices) => indices.len(),
+            IndexBuffer::U32(indices) => indices.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The element type to draw with, matching the layout of [`IndexBuffer::as_bytes`].
+    pub fn gl_type(&self) -> gl::types::GLenum {
+        match self {
+            IndexBuffer::U16(_) => gl::UNSIGNED_SHORT,
+            IndexBuffer::U32(_) => gl::UNSIGNED_INT,
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            IndexBuffer::U16(indices) => cast_slice(indices),
+            IndexBuffer::U32(indices) => cast_slice(indices),
+        }
+    }
+}
+
+/// How a mesh's indices are put together into primitives.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PrimitiveMode {
+    /// Every three indices form a triangle.
+    #[default]
+    Triangles,
+    /// Every two indices form a line segment, drawn [`crate::renderer::Renderer::line_width`]
+    /// pixels wide.
+    Lines,
+    /// Every index is a point, drawn as a square
+    /// [`crate::renderer::Renderer::point_size`] pixels across.
+    Points,
+}
+
+impl PrimitiveMode {
+    fn gl_enum(self) -> gl::types::GLenum {
+        match self {
+            PrimitiveMode::Triangles => gl::TRIANGLES,
+            PrimitiveMode::Lines => gl::LINES,
+            PrimitiveMode::Points => gl::POINTS,
+        }
+    }
+
+    /// Indices each primitive takes.
+    fn index_count(self) -> usize {
+        match self {
+            PrimitiveMode::Triangles => 3,
+            PrimitiveMode::Lines => 2,
+            PrimitiveMode::Points => 1,
+        }
+    }
+}
+
+/// Indexed geometry uploaded to the GPU, with its own model transform. The indices form
+/// triangles unless another [`PrimitiveMode`] is set.
+pub struct Mesh {
+    vao: gl::types::GLuint,
+    vbo: gl::types::GLuint,
+    ibo: gl::types::GLuint,
+    /// Number of vertices the vertex buffer has room for.
+    vertex_count: usize,
+    /// Size of one vertex in bytes, as described by `layout`.
+    stride: usize,
+    layout: AttributeLayout,
+    /// Values for the shader inputs `layout` doesn't provide, see [`AttributeLayout`].
+    attribute_defaults: Vec<(gl::types::GLuint, [f32; 4])>,
+    index_count: gl::types::GLsizei,
+    /// `GL_UNSIGNED_SHORT` or `GL_UNSIGNED_INT`, see [`IndexBuffer`].
+    index_type: gl::types::GLenum,
+    primitive_mode: PrimitiveMode,
+    texture: Option<Texture>,
+    /// Whether V runs up from the bottom of the texture rather than down from the top.
+    flip_uv_v: bool,
+    material: Material,
+    /// Bounds of the vertex positions, before `model_matrix`.
+    bounds: Option<Aabb>,
+    /// Copies of the vertex positions and indices kept for ray picking.
+    positions: Vec<Vec3>,
+    indices: Vec<u32>,
+    /// Per-vertex mean curvature, see [`Mesh::compute_curvature`].
+    curvature: Option<Curvature>,
+    /// What the mesh was loaded from, for display.
+    name: Option<String>,
+    /// See [`Mesh::origin_offset`].
+    origin_offset: DVec3,
+    pub model_matrix: Mat4,
+}
+
+impl Mesh {
+    /// Uploads the geometry, storing the indices as 16 bit when there are few enough vertices.
+    pub fn new(
+        gl: &TracedGl,
+        vertices: &[Vertex],
+        indices: &[u32],
+    ) -> Result<Self, ModelLoadingError> {
+        Self::with_layout(gl, vertices, &AttributeLayout::vertex(), indices)
+    }
+
+    /// Like [`Mesh::new`] for any vertex type, with `layout` saying where its attributes are.
+    /// Fails if the layout doesn't fit the vertex type, an index is past the last vertex or the
+    /// indices don't make whole triangles.
+    pub fn with_layout<V: Pod>(
+        gl: &TracedGl,
+        vertices: &[V],
+        layout: &AttributeLayout,
+        indices: &[u32],
+    ) -> Result<Self, ModelLoadingError> {
+        Self::upload(gl, vertices, layout, indices, PrimitiveMode::Triangles)
+    }
+
+    /// Like [`Mesh::new`], with the indices drawn as `mode` from the start, so line and point
+    /// meshes only need whole primitives of their own mode.
+    pub fn with_primitive_mode(
+        gl: &TracedGl,
+        vertices: &[Vertex],
+        indices: &[u32],
+        mode: PrimitiveMode,
+    ) -> Result<Self, ModelLoadingError> {
+        Self::upload(gl, vertices, &AttributeLayout::vertex(), indices, mode)
+    }
+
+    fn upload<V: Pod>(
+        gl: &TracedGl,
+        vertices: &[V],
+        layout: &AttributeLayout,
+        indices: &[u32],
+        mode: PrimitiveMode,
+    ) -> Result<Self, ModelLoadingError> {
+        let stride = size_of::<V>();
+        Self::upload_bytes(gl, cast_slice(vertices), stride, layout, indices, mode)
+    }
+
+    /// [`Mesh::upload`] for vertices already cast to bytes, `stride` each.
+    fn upload_bytes(
+        gl: &TracedGl,
+        vertex_data_as_bytes: &[u8],
+        stride: usize,
+        layout: &AttributeLayout,
+        indices: &[u32],
+        mode: PrimitiveMode,
+    ) -> Result<Self, ModelLoadingError> {
+        layout.validate(stride)?;
+        // A valid layout has a position, so the stride isn't zero.
+        let vertex_count = vertex_data_as_bytes.len() / stride;
+        validate_indices(indices, vertex_count, mode)?;
+        let positions = layout.read_positions(vertex_data_as_bytes, stride);
+        let indices_copy = indices.to_vec();
+        let indices = IndexBuffer::new(indices, vertex_count);
+        unsafe {
+            let (mut vao, mut vbo, mut ibo) = (0, 0, 0);
+            if gl.direct_state_access() {
+                gl.CreateVertexArrays(1, &mut vao);
+                gl.CreateBuffers(1, &mut vbo);
+                gl.CreateBuffers(1, &mut ibo);
+            } else {
+                gl.GenVertexArrays(1, &mut vao);
+                gl.GenBuffers(1, &mut vbo);
+                gl.GenBuffers(1, &mut ibo);
+            }
+            // Deleting the zero name is a no-op, so whatever did get allocated can be freed as one.
+            if let Some(failed) = [
+                (vao, "vertex array"),
+                (vbo, "vertex buffer"),
+                (ibo, "index buffer"),
+            ]
+            .into_iter()
+            .find_map(|(name, object)| (name == 0).then_some(object))
+            {
+                gl.DeleteBuffers(1, &vbo);
+                gl.DeleteBuffers(1, &ibo);
+                gl.DeleteVertexArrays(1, &vao);
+                log::error!("failed to allocate mesh {failed}");
+                return Err(ModelLoadingError::GlError(gl.GetError()));
+            }
+
+            let index_data_as_bytes = indices.as_bytes();
+            if gl.direct_state_access() {
+                upload_dsa(
+                    gl,
+                    [vao, vbo, ibo],
+                    (vertex_data_as_bytes, layout, stride),
+                    index_data_as_bytes,
+                );
+            } else {
+                upload_bound(
+                    gl,
+                    [vao, vbo, ibo],
+                    (vertex_data_as_bytes, layout, stride),
+                    index_data_as_bytes,
+                );
+            }
+            if CHECK_GL_ERRORS {
+                check_gl_error(gl, "mesh upload");
+            }
+
+            Ok(Self {
+                vao,
+                vbo,
+                ibo,
+                vertex_count,
+                stride,
+                layout: layout.clone(),
+                attribute_defaults: layout.missing_defaults(),
+                index_count: indices.len() as gl::types::GLsizei,
+                index_type: indices.gl_type(),
+                primitive_mode: mode,
+                texture: None,
+                flip_uv_v: false,
+                material: Material::default(),
+                bounds: Aabb::from_points(positions.iter().copied()),
+                positions,
+                indices: indices_copy,
+                curvature: None,
+                name: None,
+                origin_offset: DVec3::ZERO,
+                model_matrix: Mat4::IDENTITY,
+            })
+        }
+    }
+
+    /// Uploads a mesh copied with [`Mesh::snapshot`] again, with the same vertices, indices,
+    /// texture and settings. Curvature is computed again if the snapshotted mesh had it.
+    pub fn from_snapshot(
+        gl: &TracedGl,
+        snapshot: &MeshSnapshot,
+    ) -> Result<Self, ModelLoadingError> {
+        let mut mesh = Self::upload_bytes(
+            gl,
+            &snapshot.vertices,
+            snapshot.stride,
+            &snapshot.layout,
+            &snapshot.indices,
+            snapshot.primitive_mode,
+        )?;
+        mesh.flip_uv_v = snapshot.flip_uv_v;
+        mesh.material = snapshot.material.clone();
+        mesh.name = snapshot.name.clone();
+        mesh.origin_offset = snapshot.origin_offset;
+        mesh.model_matrix = snapshot.model_matrix;
+        if let Some(image) = &snapshot.texture {
+            let texture = Texture::from_image(gl, image).inspect_err(|_| mesh.delete(gl))?;
+            mesh.texture = Some(texture);
+        }
+        if snapshot.curvature {
+            mesh.compute_curvature(gl)
+                .inspect_err(|_| mesh.delete(gl))?;
+        }
+        Ok(mesh)
+    }
+
+    pub fn from_data(gl: &TracedGl, data: &MeshData) -> Result<Self, ModelLoadingError> {
+        let mut mesh = Self::new(gl, &data.vertices, &data.indices)?;
+        mesh.origin_offset = data.origin_offset;
+        Ok(mesh)
+    }
+
+    /// Loads the Wavefront OBJ file at `path` into a new mesh, computing smooth normals if the
+    /// file has none. OBJ UVs start at the bottom of the texture, so V is flipped. Its materials
+    /// are ignored, see [`crate::scene::Scene::load_obj`] for a mesh per material.
+    pub fn from_obj(gl: &TracedGl, path: &Path) -> Result<Self, ModelLoadingError> {
+        let mut data = load_obj(path)?;
+        if !data.has_normals() {
+            data.compute_smooth_normals();
+        }
+        let mut mesh = Self::from_data(gl, &data)?;
+        mesh.set_flip_uv_v(true);
+        Ok(mesh)
+    }
+
+    /// Loads the ASCII or binary little endian PLY file at `path` into a new mesh, computing
+    /// smooth normals if the file has none.
+    pub fn from_ply(gl: &TracedGl, path: &Path) -> Result<Self, ModelLoadingError> {
+        Self::from_data(gl, &load_ply(path)?)
+    }
+
+    /// Loads the ASCII or binary STL file at `path` into a new flat shaded mesh.
+    pub fn from_stl(gl: &TracedGl, path: &Path) -> Result<Self, ModelLoadingError> {
+        Self::from_data(gl, &load_stl(path)?)
+    }
+
+    /// Bounds of the mesh in model space, or `None` for a mesh without vertices.
+    pub fn bounds(&self) -> Option<Aabb> {
+        self.bounds
+    }
+
+    pub fn set_name(&mut self, name: impl Into<String>) {
+        self.name = Some(name.into());
+    }
+
+    /// Sets where the origin of the vertex positions is in the coordinates the mesh was loaded
+    /// in, see [`Mesh::origin_offset`].
+    pub fn set_origin_offset(&mut self, offset: DVec3) {
+        self.origin_offset = offset;
+    }
+
+    /// Where the origin of the vertex positions is in the coordinates the mesh was loaded in, in
+    /// double precision. Loaders of double precision positions, like [`Mesh::from_ply`], move
+    /// them to around their centroid before converting them to `f32`, which would round away
+    /// the detail of large coordinates, e.g. geospatial ones, and put the centroid here. Zero
+    /// unless the positions were moved.
+    pub fn origin_offset(&self) -> DVec3 {
+        self.origin_offset
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Sets the texture sampled with the vertex UVs, replacing (and deleting) any previous one.
+    pub fn set_texture(&mut self, gl: &TracedGl, texture: Texture) {
+        if let Some(old) = self.texture.replace(texture) {
+            old.delete(gl);
+        }
+    }
+
+    pub fn texture(&self) -> Option<&Texture> {
+        self.texture.as_ref()
+    }
+
+    /// Samples the texture at `1 - v` instead of `v`. Textures are uploaded top row first, which
+    /// suits UVs with their origin at the top left as in glTF, while OBJ and many other tools put
+    /// it at the bottom left. Off by default, and turned on by the loaders for such formats.
+    pub fn set_flip_uv_v(&mut self, flip: bool) {
+        self.flip_uv_v = flip;
+    }
+
+    pub fn flip_uv_v(&self) -> bool {
+        self.flip_uv_v
+    }
+
+    pub fn set_material(&mut self, material: Material) {
+        self.material = material;
+    }
+
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+
+    /// Overwrites the start of the vertex buffer with `vertices`, e.g. for animated geometry. The
+    /// index buffer is unchanged, so the vertices have to keep their meaning. Fails without
+    /// writing anything if there are more vertices than the mesh was created with, or they're a
+    /// different size from the ones it was created with.
+    pub fn update_vertices<V: Pod>(
+        &mut self,
+        gl: &TracedGl,
+        vertices: &[V],
+    ) -> Result<(), ModelLoadingError> {
+        ensure!(
+            size_of::<V>() == self.stride,
+            InvalidArgument,
+            "{} byte vertices don't match the mesh's {} byte layout",
+            size_of::<V>(),
+            self.stride
+        );
+        ensure!(
+            vertices.len() <= self.vertex_count,
+            InvalidArgument,
+            "{} vertices don't fit in a mesh created with {}",
+            vertices.len(),
+            self.vertex_count
+        );
+        let data = cast_slice::<V, u8>(vertices);
+        unsafe {
+            if gl.direct_state_access() {
+                gl.NamedBufferSubData(self.vbo, 0, data.len() as isize, data.as_ptr().cast());
+            } else {
+                gl.BindBuffer(gl::COPY_WRITE_BUFFER, self.vbo);
+                gl.BufferSubData(
+                    gl::COPY_WRITE_BUFFER,
+                    0,
+                    data.len() as isize,
+                    data.as_ptr().cast(),
+                );
+                gl.BindBuffer(gl::COPY_WRITE_BUFFER, 0);
+            }
+        }
+        let positions = self.layout.read_positions(data, self.stride);
+        self.positions[..positions.len()].copy_from_slice(&positions);
+        if vertices.len() == self.vertex_count {
+            self.bounds = Aabb::from_points(positions);
+        } else if let Some(new_bounds) = Aabb::from_points(positions) {
+            // The untouched tail still counts, so the bounds can only grow.
+            self.bounds = Some(
+                self.bounds
+                    .map_or(new_bounds, |bounds| bounds.union(&new_bounds)),
+            );
+        }
+        Ok(())
+    }
+
+    /// Sets how the indices are put together into primitives, triangles by default. The vertex
+    /// and index buffers stay as they are, so any mode can draw any mesh.
+    pub fn set_primitive_mode(&mut self, mode: PrimitiveMode) {
+        self.primitive_mode = mode;
+    }
+
+    pub fn primitive_mode(&self) -> PrimitiveMode {
+        self.primitive_mode
+    }
+
+    /// Triangles each draw of the mesh makes, none when it's drawn as lines or points.
+    pub fn triangle_count(&self) -> usize {
+        match self.primitive_mode {
+            PrimitiveMode::Triangles => self.index_count as usize / 3,
+            PrimitiveMode::Lines | PrimitiveMode::Points => 0,
+        }
+    }
+
+    /// Distance along `dir` (in multiples of its length) to the closest triangle hit by a ray from
+    /// `origin`, with the mesh transformed by `model`. Both sides of a triangle count as hits.
+    /// Meshes drawn as lines or points have no area to hit, so they never are.
+    pub fn intersect_ray(&self, model: &Mat4, origin: Vec3, dir: Vec3) -> Option<f32> {
+        if self.primitive_mode != PrimitiveMode::Triangles {
+            return None;
+        }
+        self.indices
+            .chunks_exact(3)
+            .filter_map(|triangle| {
+                let [a, b, c] =
+                    [0, 1, 2].map(|i| model.transform_point3(self.positions[triangle[i] as usize]));
+                ray_triangle(origin, dir, [a, b, c])
+            })
+            .min_by(f32::total_cmp)
+    }
+
+    /// Reverses the winding of every triangle, for models whose front faces wind clockwise and so
+    /// disappear with back-face culling. The copy of the indices kept for picking is flipped along
+    /// with the buffer, and normals are left alone. Lines and points have no winding, so meshes
+    /// drawn as them are left alone too.
+    pub fn flip_winding(&mut self, gl: &TracedGl) {
+        if self.primitive_mode != PrimitiveMode::Triangles {
+            return;
+        }
+        for triangle in self.indices.chunks_exact_mut(3) {
+            triangle.swap(1, 2);
+        }
+        // The same indices and vertex count narrow to the type the buffer was created with.
+        let indices = IndexBuffer::new(&self.indices, self.vertex_count);
+        let bytes = indices.as_bytes();
+        let size = bytes.len() as isize;
+        unsafe {
+            if gl.direct_state_access() {
+                gl.NamedBufferSubData(self.ibo, 0, size, bytes.as_ptr().cast());
+            } else {
+                gl.BindBuffer(gl::COPY_WRITE_BUFFER, self.ibo);
+                gl.BufferSubData(gl::COPY_WRITE_BUFFER, 0, size, bytes.as_ptr().cast());
+                gl.BindBuffer(gl::COPY_WRITE_BUFFER, 0);
+            }
+        }
+    }
+
+    /// Computes tangents for normal mapping as [`MeshData::compute_tangents`] does, from the
+    /// vertices on the GPU, and uploads them. Fails for meshes not drawn as triangles or not using
+    /// the [`Vertex`] layout, as custom layouts have nowhere to store them.
+    pub fn compute_tangents(&mut self, gl: &TracedGl) -> Result<(), ModelLoadingError> {
+        ensure!(
+            self.primitive_mode == PrimitiveMode::Triangles,
+            InvalidArgument,
+            "tangents need triangles, the mesh is drawn as {:?}",
+            self.primitive_mode
+        );
+        let mut data = self.read_back(gl).ok_or_else(|| {
+            ModelLoadingError::InvalidArgument(
+                "tangents can only be computed for meshes using the Vertex layout".into(),
+            )
+        })?;
+        data.compute_tangents();
+        self.update_vertices(gl, &data.vertices)
+    }
+
+    /// Computes every vertex's mean curvature as [`MeshData::compute_curvature`] does, from the
+    /// vertices on the GPU, and uploads it for [`crate::renderer::DebugView::Curvature`]. It isn't
+    /// kept up to date, so call it again after [`Mesh::update_vertices`]. Fails like
+    /// [`Mesh::compute_tangents`].
+    pub fn compute_curvature(&mut self, gl: &TracedGl) -> Result<(), ModelLoadingError> {
+        ensure!(
+            self.primitive_mode == PrimitiveMode::Triangles,
+            InvalidArgument,
+            "curvature needs triangles, the mesh is drawn as {:?}",
+            self.primitive_mode
+        );
+        let data = self.read_back(gl).ok_or_else(|| {
+            ModelLoadingError::InvalidArgument(
+                "curvature can only be computed for meshes using the Vertex layout".into(),
+            )
+        })?;
+        let values = data.compute_curvature();
+        let range = curvature_range(&values);
+        let bytes = cast_slice::<f32, u8>(&values);
+        let (location, _) = CURVATURE_ATTRIBUTE;
+        let mut buffer = self
+            .curvature
+            .as_ref()
+            .map_or(0, |curvature| curvature.buffer);
+        unsafe {
+            if gl.direct_state_access() {
+                if buffer == 0 {
+                    gl.CreateBuffers(1, &mut buffer);
+                }
+                gl.NamedBufferData(
+                    buffer,
+                    bytes.len() as isize,
+                    bytes.as_ptr().cast(),
+                    gl::STATIC_DRAW,
+                );
+                gl.VertexArrayVertexBuffer(
+                    self.vao,
+                    CURVATURE_BINDING,
+                    buffer,
+                    0,
+                    size_of::<f32>() as gl::types::GLsizei,
+                );
+                gl.EnableVertexArrayAttrib(self.vao, location);
+                gl.VertexArrayAttribFormat(self.vao, location, 1, gl::FLOAT, gl::FALSE, 0);
+                gl.VertexArrayAttribBinding(self.vao, location, CURVATURE_BINDING);
+            } else {
+                if buffer == 0 {
+                    gl.GenBuffers(1, &mut buffer);
+                }
+                gl.BindVertexArray(self.vao);
+                gl.BindBuffer(gl::ARRAY_BUFFER, buffer);
+                gl.BufferData(
+                    gl::ARRAY_BUFFER,
+                    bytes.len() as isize,
+                    bytes.as_ptr().cast(),
+                    gl::STATIC_DRAW,
+                );
+                gl.EnableVertexAttribArray(location);
+                gl.VertexAttribPointer(location, 1, gl::FLOAT, gl::FALSE, 0, std::ptr::null());
+                gl.BindVertexArray(0);
+                gl.BindBuffer(gl::ARRAY_BUFFER, 0);
+            }
+        }
+        self.curvature = Some(Curvature { buffer, range });
+        Ok(())
+    }
+
+    /// Curvature the debug view's color map spans either side of zero, or `None` if
+    /// [`Mesh::compute_curvature`] hasn't been called.
+    pub fn curvature_range(&self) -> Option<f32> {
+        self.curvature.as_ref().map(|curvature| curvature.range)
+    }
+
+    /// Copies everything the mesh was built from back to the CPU, reading the vertices and the
+    /// texture from the GPU, so [`Mesh::from_snapshot`] can build it again, e.g. on a new context
+    /// after a suspend.
+    pub fn snapshot(&self, gl: &TracedGl) -> MeshSnapshot {
+        MeshSnapshot {
+            vertices: read_buffer(gl, self.vbo, self.vertex_count * self.stride),
+            stride: self.stride,
+            layout: self.layout.clone(),
+            indices: self.indices.clone(),
+            primitive_mode: self.primitive_mode,
+            texture: self.texture.as_ref().map(|texture| texture.read_back(gl)),
+            flip_uv_v: self.flip_uv_v,
+            material: self.material.clone(),
+            curvature: self.curvature.is_some(),
+            name: self.name.clone(),
+            origin_offset: self.origin_offset,
+            model_matrix: self.model_matrix,
+        }
+    }
+
+    /// Reads the vertices and indices back from the GPU, or returns `None` for a mesh that
+    /// doesn't use the [`Vertex`] layout.
+    pub(crate) fn read_back(&self, gl: &TracedGl) -> Option<MeshData> {
+        if self.layout != AttributeLayout::vertex() || self.stride != size_of::<Vertex>() {
+            return None;
+        }
+        let vertices = read_buffer(gl, self.vbo, self.vertex_count * self.stride);
+        let index_count = self.index_count as usize;
+        let indices = if self.index_type == gl::UNSIGNED_SHORT {
+            let bytes = read_buffer(gl, self.ibo, index_count * size_of::<u16>());
+            bytes
+                .chunks_exact(size_of::<u16>())
+                .map(|index| u32::from(u16::from_ne_bytes([index[0], index[1]])))
+                .collect()
+        } else {
+            let bytes = read_buffer(gl, self.ibo, index_count * size_of::<u32>());
+            bytes
+                .chunks_exact(size_of::<u32>())
+                .map(|index| u32::from_ne_bytes(index.try_into().unwrap()))
+                .collect()
+        };
+        Some(MeshData {
+            vertices: bytemuck::pod_collect_to_vec(&vertices),
+            indices,
+            origin_offset: self.origin_offset,
+        })
+    }
+
+    /// Issues the draw call. The caller is responsible for binding a program and its uniforms.
+    pub(crate) fn draw(&self, gl: &TracedGl) {
+        unsafe {
+            self.set_attribute_defaults(gl);
+            gl.BindVertexArray(self.vao);
+            gl.DrawElements(
+                self.primitive_mode.gl_enum(),
+                self.index_count,
+                self.index_type,
+                std::ptr::null(),
+            );
+        }
+        if CHECK_GL_ERRORS {
+            check_gl_error(gl, "mesh draw");
+        }
+    }
+
+    /// Draws `count` copies of the mesh, reading a column-major `mat4` per copy from
+    /// `instance_buffer`. The instance attributes are only enabled for this call.
+    pub(crate) fn draw_instanced(
+        &self,
+        gl: &TracedGl,
+        instance_buffer: gl::types::GLuint,
+        count: gl::types::GLsizei,
+    ) {
+        const COLUMN_SIZE: usize = size_of::<[f32; 4]>();
+        let (first_location, _) = INSTANCE_MATRIX_ATTRIBUTE;
+        let locations = (0..4).map(|column| (first_location + column, column as usize));
+        unsafe {
+            self.set_attribute_defaults(gl);
+            if gl.direct_state_access() {
+                gl.VertexArrayVertexBuffer(
+                    self.vao,
+                    INSTANCE_BINDING,
+                    instance_buffer,
+                    0,
+                    size_of::<Mat4>() as gl::types::GLsizei,
+                );
+                gl.VertexArrayBindingDivisor(self.vao, INSTANCE_BINDING, 1);
+                for (location, column) in locations.clone() {
+                    gl.EnableVertexArrayAttrib(self.vao, location);
+                    gl.VertexArrayAttribFormat(
+                        self.vao,
+                        location,
+                        4,
+                        gl::FLOAT,
+                        gl::FALSE,
+                        (column * COLUMN_SIZE) as u32,
+                    );
+                    gl.VertexArrayAttribBinding(self.vao, location, INSTANCE_BINDING);
+                }
+                gl.BindVertexArray(self.vao);
+            } else {
+                gl.BindVertexArray(self.vao);
+                gl.BindBuffer(gl::ARRAY_BUFFER, instance_buffer);
+                for (location, column) in locations.clone() {
+                    gl.EnableVertexAttribArray(location);
+                    gl.VertexAttribPointer(
+                        location,
+                        4,
+                        gl::FLOAT,
+                        gl::FALSE,
+                        size_of::<Mat4>() as gl::types::GLsizei,
+                        (column * COLUMN_SIZE) as *const _,
+                    );
+                    gl.VertexAttribDivisor(location, 1);
+                }
+                gl.BindBuffer(gl::ARRAY_BUFFER, 0);
+            }
+
+            gl.DrawElementsInstanced(
+                self.primitive_mode.gl_enum(),
+                self.index_count,
+                self.index_type,
+                std::ptr::null(),
+                count,
+            );
+
+            // Back to the identity for ordinary draws of this VAO.
+            for (location, _) in locations {
+                if gl.direct_state_access() {
+                    gl.DisableVertexArrayAttrib(self.vao, location);
+                } else {
+                    gl.DisableVertexAttribArray(location);
+                }
+            }
+        }
+        if CHECK_GL_ERRORS {
+            check_gl_error(gl, "instanced mesh draw");
+        }
+    }
+
+    /// Sets the values inputs without an array read. They're part of the context rather than the
+    /// VAO, so every mesh sets its own before drawing.
+    unsafe fn set_attribute_defaults(&self, gl: &TracedGl) {
+        for (location, value) in &self.attribute_defaults {
+            gl.VertexAttrib4fv(*location, value.as_ptr());
+        }
+        let (first_location, _) = INSTANCE_MATRIX_ATTRIBUTE;
+        for (column, value) in Mat4::IDENTITY.to_cols_array_2d().iter().enumerate() {
+            gl.VertexAttrib4fv(first_location + column as gl::types::GLuint, value.as_ptr());
+        }
+        if self.curvature.is_none() {
+            gl.VertexAttrib1f(CURVATURE_ATTRIBUTE.0, 0.0);
+        }
+    }
+
+    /// Deletes the GL objects. The mesh must not be drawn afterwards.
+    pub fn delete(&self, gl: &TracedGl) {
+        unsafe {
+            gl.DeleteBuffers(1, &self.vbo);
+            gl.DeleteBuffers(1, &self.ibo);
+            gl.DeleteVertexArrays(1, &self.vao);
+            if let Some(curvature) = &self.curvature {
+                gl.DeleteBuffers(1, &curvature.buffer);
+            }
+        }
+        if let Some(texture) = &self.texture {
+            texture.delete(gl);
+        }
+    }
+}
+
+/// A [`Mesh`] copied back to the CPU by [`Mesh::snapshot`], holding no GL objects.
+#[derive(Debug, Clone)]
+pub struct MeshSnapshot {
+    /// The vertex buffer, `stride` bytes per vertex laid out as `layout` says.
+    vertices: Vec<u8>,
+    stride: usize,
+    layout: AttributeLayout,
+    indices: Vec<u32>,
+    primitive_mode: PrimitiveMode,
+    texture: Option<RgbaImage>,
+    flip_uv_v: bool,
+    material: Material,
+    /// Whether [`Mesh::compute_curvature`] had been called.
+    curvature: bool,
+    name: Option<String>,
+    origin_offset: DVec3,
+    model_matrix: Mat4,
+}
+
+/// Vertex buffer binding the instance transforms are read from. Binding 0 holds the vertices.
+const INSTANCE_BINDING: gl::types::GLuint = 1;
+/// Vertex buffer binding of the curvature buffer.
+const CURVATURE_BINDING: gl::types::GLuint = 2;
+
+/// Mean curvature uploaded by [`Mesh::compute_curvature`].
+struct Curvature {
+    buffer: gl::types::GLuint,
+    range: f32,
+}
+
+/// Magnitude 95% of the nonzero curvatures are within, so a few sharp corners don't wash out the
+/// color map for the rest of the surface. 1 for a mesh that's flat everywhere.
+fn curvature_range(values: &[f32]) -> f32 {
+    let mut magnitudes: Vec<f32> = values
+        .iter()
+        .map(|value| value.abs())
+        .filter(|&magnitude| magnitude > 0.0 && magnitude.is_finite())
+        .collect();
+    if magnitudes.is_empty() {
+        return 1.0;
+    }
+    magnitudes.sort_by(f32::total_cmp);
+    magnitudes[(magnitudes.len() - 1) * 95 / 100]
+}
+
+/// Möller–Trumbore ray-triangle intersection, returning the ray parameter of the hit.
+fn ray_triangle(origin: Vec3, dir: Vec3, [a, b, c]: [Vec3; 3]) -> Option<f32> {
+    const EPSILON: f32 = 1e-7;
+    let (edge1, edge2) = (b - a, c - a);
+    let p = dir.cross(edge2);
+    let determinant = edge1.dot(p);
+    // The ray is parallel to the triangle's plane.
+    if determinant.abs() < EPSILON {
+        return None;
+    }
+    let inverse_determinant = 1.0 / determinant;
+    let to_origin = origin - a;
+    let u = to_origin.dot(p) * inverse_determinant;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = to_origin.cross(edge1);
+    let v = dir.dot(q) * inverse_determinant;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let t = edge2.dot(q) * inverse_determinant;
+    (t > EPSILON).then_some(t)
+}
+
+/// Fills the buffers and describes the vertex layout with GL 4.5 direct state access.
+unsafe fn upload_dsa(
+    gl: &TracedGl,
+    [vao, vbo, ibo]: [gl::types::GLuint; 3],
+    (vertex_data, layout, stride): (&[u8], &AttributeLayout, usize),
+    index_data: &[u8],
+) {
+    gl.NamedBufferStorage(
+        vbo,
+        vertex_data.len() as isize,
+        vertex_data.as_ptr().cast(),
+        gl::DYNAMIC_STORAGE_BIT,
+    );
+    gl.NamedBufferStorage(
+        ibo,
+        index_data.len() as isize,
+        index_data.as_ptr().cast(),
+        gl::DYNAMIC_STORAGE_BIT,
+    );
+    gl.VertexArrayElementBuffer(vao, ibo);
+
+    gl.VertexArrayVertexBuffer(vao, 0, vbo, 0, stride as gl::types::GLsizei);
+    layout.configure_dsa(gl, vao);
+}
+
+/// The same as [`upload_dsa`] for contexts older than 4.5, by binding each object to edit it.
+unsafe fn upload_bound(
+    gl: &TracedGl,
+    [vao, vbo, ibo]: [gl::types::GLuint; 3],
+    (vertex_data, layout, stride): (&[u8], &AttributeLayout, usize),
+    index_data: &[u8],
+) {
+    gl.BindVertexArray(vao);
+    gl.BindBuffer(gl::ARRAY_BUFFER, vbo);
+    gl.BufferData(
+        gl::ARRAY_BUFFER,
+        vertex_data.len() as isize,
+        vertex_data.as_ptr().cast(),
+        gl::STATIC_DRAW,
+    );
+    // The element array binding is recorded in the bound VAO.
+    gl.BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ibo);
+    gl.BufferData(
+        gl::ELEMENT_ARRAY_BUFFER,
+        index_data.len() as isize,
+        index_data.as_ptr().cast(),
+        gl::STATIC_DRAW,
+    );
+
+    layout.configure_bound(gl, stride);
+
+    gl.BindVertexArray(0);
+    gl.BindBuffer(gl::ARRAY_BUFFER, 0);
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{vec2, vec3};
+
+    use super::*;
+
+    fn vertex(position: Vec3) -> Vertex {
+        Vertex {
+            position,
+            ..Default::default()
+        }
+    }
+
+    /// A sphere of `radius` around the origin, with outward normals and counter-clockwise
+    /// triangles seen from outside. Each pole is a single vertex.
+    fn sphere(radius: f32, rings: u32, segments: u32) -> MeshData {
+        let mut vertices = vec![vertex(Vec3::Y * radius)];
+        for ring in 1..rings {
+            let polar = std::f32::consts::PI * ring as f32 / rings as f32;
+            for segment in 0..segments {
+                let azimuth = std::f32::consts::TAU * segment as f32 / segments as f32;
+                let direction = vec3(
+                    polar.sin() * azimuth.cos(),
+                    polar.cos(),
+                    -polar.sin() * azimuth.sin(),
+                );
+                vertices.push(vertex(direction * radius));
+            }
+        }
+        vertices.push(vertex(-Vec3::Y * radius));
+        for vertex in &mut vertices {
+            vertex.normal = vertex.position.normalize();
+        }
+
+        let ring_start = |ring: u32| 1 + (ring - 1) * segments;
+        let bottom = vertices.len() as u32 - 1;
+        let mut indices = Vec::new();
+        for segment in 0..segments {
+            let next = (segment + 1) % segments;
+            indices.extend([0, ring_start(1) + segment, ring_start(1) + next]);
+            for ring in 1..rings - 1 {
+                let (upper, lower) = (ring_start(ring), ring_start(ring + 1));
+                indices.extend([upper + segment, lower + segment, lower + next]);
+                indices.extend([upper + segment, lower + next, upper + next]);
+            }
+            let last = ring_start(rings - 1);
+            indices.extend([last + segment, bottom, last + next]);
+        }
+        MeshData {
+            vertices,
+            indices,
+            ..Default::default()
+        }
+    }
+
+    /// A `size`x`size` grid of unit squares on the XZ plane facing +Y.
+    fn flat_grid(size: u32) -> MeshData {
+        let vertices = (0..=size)
+            .flat_map(|z| (0..=size).map(move |x| vec3(x as f32, 0.0, z as f32)))
+            .map(|position| Vertex {
+                normal: Vec3::Y,
+                ..vertex(position)
+            })
+            .collect();
+        let index = |x: u32, z: u32| z * (size + 1) + x;
+        let indices = (0..size)
+            .flat_map(|z| (0..size).map(move |x| (x, z)))
+            .flat_map(|(x, z)| {
+                [
+                    [index(x, z), index(x, z + 1), index(x + 1, z + 1)],
+                    [index(x, z), index(x + 1, z + 1), index(x + 1, z)],
+                ]
+            })
+            .flatten()
+            .collect();
+        MeshData {
+            vertices,
+            indices,
+            ..Default::default()
+        }
+    }
+
+    /// A unit square on the XY plane facing +Z, with UVs from its corners' positions through `uv`.
+    fn unit_quad(uv: impl Fn(Vec2) -> Vec2) -> MeshData {
+        let vertices = [
+            vec2(0.0, 0.0),
+            vec2(1.0, 0.0),
+            vec2(1.0, 1.0),
+            vec2(0.0, 1.0),
+        ]
+        .map(|corner| Vertex {
+            uv: uv(corner),
+            ..vertex(corner.extend(0.0))
+        })
+        .to_vec();
+        MeshData {
+            vertices,
+            indices: vec![0, 1, 2, 0, 2, 3],
+            ..Default::default()
+        }
+    }
+
+    fn assert_near(actual: Vec3, expected: Vec3) {
+        assert!(
+            actual.abs_diff_eq(expected, 1e-5),
+            "{actual} isn't {expected}"
+        );
+    }
+
+    #[test]
+    fn smooth_normals_average_the_faces_around_each_vertex() {
+        let mut data = unit_quad(|corner| corner);
+        data.compute_smooth_normals();
+        for vertex in &data.vertices {
+            assert_near(vertex.normal, Vec3::Z);
+        }
+
+        // A vertex shared by faces looking along +Z and +X leans halfway between them.
+        let mut data = MeshData {
+            vertices: [Vec3::ZERO, Vec3::X, Vec3::Y, -Vec3::Z]
+                .map(vertex)
+                .to_vec(),
+            indices: vec![0, 1, 2, 0, 3, 2],
+            ..Default::default()
+        };
+        data.compute_smooth_normals();
+        assert_near(data.vertices[0].normal, vec3(1.0, 0.0, 1.0).normalize());
+        assert_near(data.vertices[1].normal, Vec3::Z);
+        assert_near(data.vertices[3].normal, Vec3::X);
+    }
+
+    #[test]
+    fn tangents_follow_increasing_u() {
+        let mut data = unit_quad(|corner| corner);
+        data.compute_smooth_normals();
+        data.compute_tangents();
+        for vertex in &data.vertices {
+            assert_near(vertex.tangent, Vec3::X);
+            assert_eq!(vertex.bitangent_sign, 1.0);
+        }
+    }
+
+    #[test]
+    fn mirrored_uvs_flip_the_bitangent_sign() {
+        // Mirrored in U, the tangent turns around and V runs along the normal × tangent's opposite.
+        let mut data = unit_quad(|corner| vec2(1.0 - corner.x, corner.y));
+        data.compute_smooth_normals();
+        data.compute_tangents();
+        for vertex in &data.vertices {
+            assert_near(vertex.tangent, -Vec3::X);
+            assert_eq!(vertex.bitangent_sign, -1.0);
+        }
+
+        // Mirrored in V, only the sign changes.
+        let mut data = unit_quad(|corner| vec2(corner.x, 1.0 - corner.y));
+        data.compute_smooth_normals();
+        data.compute_tangents();
+        for vertex in &data.vertices {
+            assert_near(vertex.tangent, Vec3::X);
+            assert_eq!(vertex.bitangent_sign, -1.0);
+        }
+    }
+
+    #[test]
+    fn triangles_without_uv_area_are_skipped() {
+        // A triangle off the quad's first corner whose UVs all sit on one point.
+        let mut data = unit_quad(|corner| corner);
+        data.vertices.extend(
+            [vec3(-1.0, 0.0, 0.0), vec3(0.0, -1.0, 0.0)].map(|position| Vertex {
+                uv: Vec2::ZERO,
+                ..vertex(position)
+            }),
+        );
+        data.indices.extend([0, 5, 4]);
+        data.compute_smooth_normals();
+        data.compute_tangents();
+        assert_near(data.vertices[0].tangent, Vec3::X);
+        assert_eq!(data.vertices[0].bitangent_sign, 1.0);
+        // The triangle's own corners have nothing to go by but stay perpendicular to the normal.
+        for vertex in &data.vertices[4..] {
+            assert!(vertex.tangent.is_normalized());
+            assert!(vertex.tangent.dot(vertex.normal).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn sphere_curvature_is_inverse_radius() {
+        let curvature = sphere(2.0, 24, 48).compute_curvature();
+        for value in curvature {
+            assert!((value - 0.5).abs() < 0.02, "curvature {value}");
+        }
+    }
+
+    #[test]
+    fn inverted_sphere_curvature_is_negative() {
+        let mut data = sphere(2.0, 24, 48);
+        for vertex in &mut data.vertices {
+            vertex.normal = -vertex.normal;
+        }
+        for triangle in data.indices.chunks_exact_mut(3) {
+            triangle.swap(1, 2);
+        }
+        for value in data.compute_curvature() {
+            assert!((value + 0.5).abs() < 0.02, "curvature {value}");
+        }
+    }
+
+    #[test]
+    fn flat_curvature_is_zero_and_boundary_is_skipped() {
+        let curvature = flat_grid(4).compute_curvature();
+        assert!(curvature.iter().all(|value| value.abs() < 1e-5));
+    }
+
+    #[test]
+    fn curvature_welds_split_vertices() {
+        // The same sphere with its triangles split apart, as models with per-face UVs are.
+        let data = sphere(1.0, 12, 24);
+        let split = MeshData {
+            vertices: data
+                .indices
+                .iter()
+                .map(|&index| data.vertices[index as usize])
+                .collect(),
+            indices: (0..data.indices.len() as u32).collect(),
+            ..Default::default()
+        };
+        let (welded, split_curvature) = (data.compute_curvature(), split.compute_curvature());
+        for (index, value) in data.indices.iter().zip(split_curvature) {
+            assert!((welded[*index as usize] - value).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn indices_must_address_the_vertices() {
+        assert!(validate_indices(&[0, 1, 2, 2, 1, 3], 4, PrimitiveMode::Triangles).is_ok());
+        let err = validate_indices(&[0, 1, 4], 4, PrimitiveMode::Triangles).unwrap_err();
+        assert!(matches!(err, ModelLoadingError::InvalidData(_)), "{err:?}");
+    }
+
+    #[test]
+    fn indices_must_make_whole_primitives() {
+        let err = validate_indices(&[0, 1, 2, 3], 4, PrimitiveMode::Triangles).unwrap_err();
+        assert!(matches!(err, ModelLoadingError::InvalidData(_)), "{err:?}");
+        assert!(validate_indices(&[0, 1, 2, 3], 4, PrimitiveMode::Lines).is_ok());
+        assert!(validate_indices(&[0, 1, 2], 4, PrimitiveMode::Points).is_ok());
+    }
+
+    #[test]
+    fn indices_past_16_bits_are_kept_whole() {
+        let indices = IndexBuffer::new(&[0, 70000], 10);
+        assert_eq!(indices, IndexBuffer::U32(vec![0, 70000]));
+        assert_eq!(IndexBuffer::new(&[0, 9], 10), IndexBuffer::U16(vec![0, 9]));
+    }
+}
@@ -0,0 +1,215 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use glam::{vec3, Vec3};
+
+use crate::{
+    error::{bail, Context, ModelLoadingError},
+    material::Material,
+    scene::Fetch,
+};
+
+/// A material defined by `newmtl` in a Wavefront `.mtl` library.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MtlMaterial {
+    pub material: Material,
+    /// Image named by `map_Kd`, multiplied into the diffuse color.
+    pub diffuse_texture: Option<PathBuf>,
+}
+
+/// Parses the `.mtl` library at `path` into its materials by name. Texture paths are resolved
+/// against `base`, usually the directory of the OBJ file referencing the library.
+pub fn load_mtl(
+    path: &Path,
+    base: &Path,
+) -> Result<HashMap<String, MtlMaterial>, ModelLoadingError> {
+    let source = fs::read_to_string(path).map_err(ModelLoadingError::file_io(path))?;
+    parse_mtl(&source, base).with_context(|| format!("failed to parse {}", path.display()))
+}
+
+/// Parses the `Kd`, `Ka`, `Ks`, `Ns`, `d` and `map_Kd` statements of each material, starting from
+/// [`Material::default`]. Other statements are logged once and otherwise ignored.
+pub fn parse_mtl(
+    source: &str,
+    base: &Path,
+) -> Result<HashMap<String, MtlMaterial>, ModelLoadingError> {
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, MtlMaterial)> = None;
+    let mut unsupported = BTreeSet::new();
+
+    for (line_no, line) in source.lines().enumerate() {
+        let line_no = line_no + 1;
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        if keyword.starts_with('#') {
+            continue;
+        }
+        if keyword == "newmtl" {
+            let name = tokens.collect::<Vec<_>>().join(" ");
+            if name.is_empty() {
+                bail!("line {line_no}: newmtl without a name");
+            }
+            let material = MtlMaterial {
+                material: Material::default(),
+                diffuse_texture: None,
+            };
+            materials.extend(current.replace((name, material)));
+            continue;
+        }
+
+        let Some((_, mtl)) = current.as_mut() else {
+            bail!("line {line_no}: {keyword} before the first newmtl");
+        };
+        let material = &mut mtl.material;
+        match keyword {
+            "Kd" => material.diffuse = parse_color(tokens).context(format!("line {line_no}"))?,
+            "Ka" => material.ambient = parse_color(tokens).context(format!("line {line_no}"))?,
+            "Ks" => material.specular = parse_color(tokens).context(format!("line {line_no}"))?,
+            // An exponent of 0 would light every angle as brightly as the mirror direction.
+            "Ns" => {
+                material.shininess = parse_number(tokens)
+                    .context(format!("line {line_no}"))?
+                    .max(1.0)
+            }
+            "d" => material.alpha = parse_number(tokens).context(format!("line {line_no}"))?,
+            // Options like `-s 2 2 2` come before the file name.
+            "map_Kd" => match tokens.last() {
+                Some(file) => mtl.diffuse_texture = Some(base.join(file.replace('\\', "/"))),
+                None => bail!("line {line_no}: map_Kd without a file name"),
+            },
+            _ => {
+                unsupported.insert(keyword);
+            }
+        }
+    }
+    materials.extend(current);
+
+    if !unsupported.is_empty() {
+        let unsupported: Vec<_> = unsupported.into_iter().collect();
+        log::warn!(
+            "ignoring unsupported MTL statements: {}",
+            unsupported.join(", ")
+        );
+    }
+    Ok(materials)
+}
+
+/// Loads every library in `names`, read with `fetch`, into one map. Later libraries win for
+/// materials defined twice, and libraries that fail to load are logged and skipped, leaving their
+/// materials undefined. Texture paths are left as written, relative to the OBJ file, for `fetch`
+/// to resolve too.
+pub fn load_libraries(names: &[String], fetch: &Fetch) -> HashMap<String, MtlMaterial> {
+    let mut materials = HashMap::new();
+    for name in names {
+        let library = fetch(name).and_then(|bytes| {
+            let source = String::from_utf8(bytes)?;
+            parse_mtl(&source, Path::new(""))
+        });
+        match library {
+            Ok(library) => materials.extend(library),
+            Err(err) => log::warn!("skipping material library {name:?}: {err:#}"),
+        }
+    }
+    materials
+}
+
+/// An `r [g b]` color, where a lone `r` is a gray. Spectral and XYZ colors aren't supported.
+fn parse_color<'a>(tokens: impl Iterator<Item = &'a str>) -> Result<Vec3, ModelLoadingError> {
+    let components = tokens
+        .map(|token| {
+            token
+                .parse()
+                .with_context(|| format!("invalid number {token:?}"))
+        })
+        .collect::<Result<Vec<f32>, _>>()?;
+    match components[..] {
+        [gray] => Ok(Vec3::splat(gray)),
+        [r, g, b] => Ok(vec3(r, g, b)),
+        _ => bail!(UnsupportedFormat, "expected an RGB color"),
+    }
+}
+
+fn parse_number<'a>(mut tokens: impl Iterator<Item = &'a str>) -> Result<f32, ModelLoadingError> {
+    let token = tokens.next().context("expected a number")?;
+    token
+        .parse()
+        .with_context(|| format!("invalid number {token:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn materials_are_read_by_name() {
+        let mtl = "\
+newmtl shiny red
+Kd 1 0 0
+Ks 0.5
+Ns 0
+d 0.25
+map_Kd -s 2 2 2 textures\\red.png
+newmtl plain
+";
+        let materials = parse_mtl(mtl, Path::new("models")).unwrap();
+        let red = &materials["shiny red"];
+        assert_eq!(red.material.diffuse, Vec3::X);
+        assert_eq!(red.material.specular, Vec3::splat(0.5));
+        assert_eq!(red.material.shininess, 1.0);
+        assert_eq!(red.material.alpha, 0.25);
+        assert_eq!(
+            red.diffuse_texture.as_deref(),
+            Some(Path::new("models/textures/red.png"))
+        );
+        assert_eq!(materials["plain"].material, Material::default());
+    }
+
+    #[test]
+    fn invalid_libraries_are_rejected() {
+        for mtl in [
+            "Kd 1 1 1\n",
+            "newmtl\n",
+            "newmtl a\nKd 1 1\n",
+            "newmtl a\nNs shiny\n",
+            "newmtl a\nmap_Kd\n",
+        ] {
+            assert!(parse_mtl(mtl, Path::new("")).is_err(), "{mtl:?}");
+        }
+    }
+
+    #[test]
+    fn libraries_that_fail_to_load_are_skipped() {
+        let files = HashMap::from([
+            (
+                "good.mtl",
+                "newmtl good\nKd 0 1 0\nmap_Kd textures/good.png\n",
+            ),
+            ("bad.mtl", "Kd 1 0 0\n"),
+            ("later.mtl", "newmtl good\nKd 0 0 1\n"),
+        ]);
+        let fetch = |name: &str| match files.get(name) {
+            Some(source) => Ok(source.as_bytes().to_vec()),
+            None => Err(ModelLoadingError::InvalidArgument(format!(
+                "no file {name:?}"
+            ))),
+        };
+        let names = ["missing.mtl", "bad.mtl", "good.mtl"].map(str::to_owned);
+        let materials = load_libraries(&names, &fetch);
+        assert_eq!(materials.len(), 1);
+        assert_eq!(materials["good"].material.diffuse, Vec3::Y);
+        // Left for `fetch` to resolve, like the libraries.
+        assert_eq!(
+            materials["good"].diffuse_texture.as_deref(),
+            Some(Path::new("textures/good.png"))
+        );
+
+        let names = ["good.mtl", "later.mtl"].map(str::to_owned);
+        let materials = load_libraries(&names, &fetch);
+        assert_eq!(materials["good"].material.diffuse, Vec3::Z);
+    }
+}
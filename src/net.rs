@@ -0,0 +1,71 @@
+use std::time::Duration;
+
+use reqwest::{blocking::Client, redirect, Url};
+
+use crate::{
+    error::{ensure, Context, ModelLoadingError},
+    scene::Scene,
+    trace::TracedGl,
+};
+
+/// Downloads models, and the files they refer to, over HTTP(S).
+pub struct HttpLoader {
+    client: Client,
+}
+
+impl HttpLoader {
+    /// A loader whose requests each give up after `timeout`.
+    pub fn new(timeout: Duration) -> Result<Self, ModelLoadingError> {
+        let client = Client::builder()
+            .timeout(timeout)
+            .redirect(redirect::Policy::limited(10))
+            .build()?;
+        Ok(Self { client })
+    }
+
+    /// Loads the OBJ model at `url` like [`Scene::load_obj_from_memory`], fetching its material
+    /// libraries and textures relative to where it was redirected to.
+    pub fn load_obj(&self, gl: &TracedGl, url: &str) -> Result<Scene, ModelLoadingError> {
+        let (url, bytes) = self.get(url)?;
+        let source = String::from_utf8(bytes).with_context(|| format!("failed to load {url}"))?;
+        Scene::load_obj_from_memory(gl, &source, &|name| self.get_relative(&url, name))
+            .with_context(|| format!("failed to load {url}"))
+    }
+
+    /// Loads the `.gltf` or `.glb` model at `url` like [`Scene::load_gltf_from_memory`], fetching
+    /// its buffers and images relative to where it was redirected to.
+    pub fn load_gltf(&self, gl: &TracedGl, url: &str) -> Result<Scene, ModelLoadingError> {
+        let (url, bytes) = self.get(url)?;
+        Scene::load_gltf_from_memory(gl, &bytes, &|uri| self.get_relative(&url, uri))
+            .with_context(|| format!("failed to load {url}"))
+    }
+
+    /// The body of `url`, along with the URL it came from after following redirects.
+    fn get(&self, url: &str) -> Result<(Url, Vec<u8>), ModelLoadingError> {
+        let response = self
+            .client
+            .get(url)
+            .send()
+            .with_context(|| format!("failed to fetch {url}"))?;
+        let status = response.status();
+        ensure!(
+            status.is_success(),
+            Network,
+            "failed to fetch {url}: server answered {status}"
+        );
+        let url = response.url().clone();
+        let bytes = response
+            .bytes()
+            .with_context(|| format!("failed to read {url}"))?;
+        Ok((url, bytes.to_vec()))
+    }
+
+    /// Resolves `reference`, as written in the model at `base`, and fetches it. References are
+    /// already percent-encoded, as any URL is.
+    fn get_relative(&self, base: &Url, reference: &str) -> Result<Vec<u8>, ModelLoadingError> {
+        let url = base.join(reference).map_err(|err| {
+            ModelLoadingError::InvalidData(format!("bad URI {reference:?}: {err}"))
+        })?;
+        self.get(url.as_str()).map(|(_, bytes)| bytes)
+    }
+}
@@ -0,0 +1,301 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs,
+    path::Path,
+};
+
+use glam::{vec2, vec3, Mat4, Vec2, Vec3};
+
+use crate::{
+    error::{bail, Context, ModelLoadingError},
+    mesh::{MeshData, Vertex},
+};
+
+/// Color given to vertices when the OBJ doesn't provide one.
+pub const DEFAULT_COLOR: Vec3 = vec3(0.8, 0.8, 0.8);
+
+/// Reference to a position and optional UV and normal, as zero-based indices.
+type FaceVertex = (usize, Option<usize>, Option<usize>);
+
+/// Faces of an OBJ file drawn with one material, as switched to by `usemtl`.
+#[derive(Debug, Clone, Default)]
+pub struct ObjGroup {
+    /// Name given to `usemtl`, or `None` for faces before the first one.
+    pub material: Option<String>,
+    pub data: MeshData,
+}
+
+/// Geometry of an OBJ file split by material, along with the material libraries it references.
+#[derive(Debug, Clone, Default)]
+pub struct ObjModel {
+    /// In order of first use. Faces switching back to an earlier material join its group.
+    pub groups: Vec<ObjGroup>,
+    /// File names given to `mtllib`, relative to the OBJ file.
+    pub material_libraries: Vec<String>,
+}
+
+impl ObjModel {
+    /// Every group's faces as one triangle list, ignoring their materials.
+    pub fn merged(&self) -> MeshData {
+        let mut merged = MeshData::default();
+        for group in &self.groups {
+            merged.append_transformed(&group.data, &Mat4::IDENTITY);
+        }
+        merged
+    }
+}
+
+/// Parses a Wavefront OBJ file into an indexed triangle list, see [`parse_obj_model`].
+pub fn load_obj(path: &Path) -> Result<MeshData, ModelLoadingError> {
+    Ok(load_obj_model(path)?.merged())
+}
+
+pub fn parse_obj(source: &str) -> Result<MeshData, ModelLoadingError> {
+    Ok(parse_obj_model(source)?.merged())
+}
+
+pub fn load_obj_model(path: &Path) -> Result<ObjModel, ModelLoadingError> {
+    let source = fs::read_to_string(path).map_err(ModelLoadingError::file_io(path))?;
+    parse_obj_model(&source).with_context(|| format!("failed to parse {}", path.display()))
+}
+
+/// Parses a Wavefront OBJ file into an indexed triangle list per material.
+///
+/// Polygons are triangulated as a fan around their first vertex, and each unique `v/vt/vn` triple
+/// becomes one [`Vertex`] in each group using it. Statements other than geometry, `usemtl`,
+/// `mtllib`, groups and smoothing are logged once and otherwise ignored.
+pub fn parse_obj_model(source: &str) -> Result<ObjModel, ModelLoadingError> {
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut uvs = Vec::new();
+
+    let mut model = ObjModel {
+        groups: vec![ObjGroup::default()],
+        ..Default::default()
+    };
+    // Vertices of each group, by the triple they were made from.
+    let mut unique_vertices: Vec<HashMap<FaceVertex, u32>> = vec![HashMap::new()];
+    let mut current = 0;
+    let mut unsupported = BTreeSet::new();
+
+    for (line_no, line) in source.lines().enumerate() {
+        let line_no = line_no + 1;
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+
+        match keyword {
+            "v" => positions.push(parse_vec3(&mut tokens).context(format!("line {line_no}"))?),
+            "vn" => normals.push(parse_vec3(&mut tokens).context(format!("line {line_no}"))?),
+            "vt" => uvs.push(parse_uv(&mut tokens).context(format!("line {line_no}"))?),
+            "f" => {
+                let MeshData {
+                    vertices, indices, ..
+                } = &mut model.groups[current].data;
+                let mut face = Vec::new();
+                for token in tokens {
+                    let key = parse_face_vertex(token, positions.len(), uvs.len(), normals.len())
+                        .with_context(|| {
+                        format!("line {line_no}: bad face vertex {token:?}")
+                    })?;
+                    let index = *unique_vertices[current].entry(key).or_insert_with(|| {
+                        vertices.push(Vertex {
+                            position: positions[key.0],
+                            color: DEFAULT_COLOR,
+                            normal: key.2.map_or(Vec3::ZERO, |normal| normals[normal]),
+                            uv: key.1.map_or(Vec2::ZERO, |uv| uvs[uv]),
+                            ..Default::default()
+                        });
+                        (vertices.len() - 1) as u32
+                    });
+                    face.push(index);
+                }
+                if face.len() < 3 {
+                    bail!("line {line_no}: face has fewer than 3 vertices");
+                }
+                for i in 1..face.len() - 1 {
+                    indices.extend_from_slice(&[face[0], face[i], face[i + 1]]);
+                }
+            }
+            "usemtl" => {
+                let name = tokens.collect::<Vec<_>>().join(" ");
+                current = match model
+                    .groups
+                    .iter()
+                    .position(|group| group.material.as_ref() == Some(&name))
+                {
+                    Some(group) => group,
+                    None => {
+                        model.groups.push(ObjGroup {
+                            material: Some(name),
+                            data: MeshData::default(),
+                        });
+                        unique_vertices.push(HashMap::new());
+                        model.groups.len() - 1
+                    }
+                };
+            }
+            "mtllib" => model.material_libraries.extend(tokens.map(str::to_owned)),
+            // Groups, objects and smoothing don't affect geometry.
+            "g" | "o" | "s" => (),
+            _ if keyword.starts_with('#') => (),
+            _ => {
+                unsupported.insert(keyword);
+            }
+        }
+    }
+
+    if !unsupported.is_empty() {
+        let unsupported: Vec<_> = unsupported.into_iter().collect();
+        log::warn!(
+            "ignoring unsupported OBJ statements: {}",
+            unsupported.join(", ")
+        );
+    }
+    model.groups.retain(|group| !group.data.indices.is_empty());
+    if model.groups.is_empty() {
+        bail!("no faces found");
+    }
+    Ok(model)
+}
+
+fn parse_vec3<'a>(tokens: &mut impl Iterator<Item = &'a str>) -> Result<Vec3, ModelLoadingError> {
+    let mut component = || -> Result<f32, ModelLoadingError> {
+        let token = tokens.next().context("expected 3 components")?;
+        token
+            .parse()
+            .with_context(|| format!("invalid number {token:?}"))
+    };
+    Ok(vec3(component()?, component()?, component()?))
+}
+
+/// Texture coordinates have an optional `v` (and `w`, which is ignored) component.
+fn parse_uv<'a>(tokens: &mut impl Iterator<Item = &'a str>) -> Result<Vec2, ModelLoadingError> {
+    let mut component = |default: Option<f32>| -> Result<f32, ModelLoadingError> {
+        match (tokens.next(), default) {
+            (Some(token), _) => token
+                .parse()
+                .with_context(|| format!("invalid number {token:?}")),
+            (None, Some(default)) => Ok(default),
+            (None, None) => bail!("expected at least 1 component"),
+        }
+    };
+    Ok(vec2(component(None)?, component(Some(0.0))?))
+}
+
+/// Parses one `v`, `v/vt`, `v//vn` or `v/vt/vn` reference into zero-based indices.
+fn parse_face_vertex(
+    token: &str,
+    position_count: usize,
+    uv_count: usize,
+    normal_count: usize,
+) -> Result<(usize, Option<usize>, Option<usize>), ModelLoadingError> {
+    let mut parts = token.split('/');
+    let position = resolve_index(parts.next().unwrap_or_default(), position_count)?;
+    let uv = match parts.next() {
+        Some("") | None => None,
+        Some(part) => Some(resolve_index(part, uv_count)?),
+    };
+    let normal = match parts.next() {
+        Some("") | None => None,
+        Some(part) => Some(resolve_index(part, normal_count)?),
+    };
+    Ok((position, uv, normal))
+}
+
+/// OBJ indices are one-based, and negative indices count back from the latest element.
+fn resolve_index(part: &str, count: usize) -> Result<usize, ModelLoadingError> {
+    let index: i64 = part.parse()?;
+    let resolved = match index {
+        1.. => index - 1,
+        ..=-1 => count as i64 + index,
+        0 => bail!("index 0 is not valid"),
+    };
+    if resolved < 0 || resolved >= count as i64 {
+        bail!("index {index} out of range");
+    }
+    Ok(resolved as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions(data: &MeshData) -> Vec<Vec3> {
+        data.indices
+            .iter()
+            .map(|&index| data.vertices[index as usize].position)
+            .collect()
+    }
+
+    #[test]
+    fn negative_indices_count_back_from_the_latest_element() {
+        let obj = "v 0 0 0\nv 1 0 0\nvt 0.5 0.5\nvn 0 0 1\nv 0 1 0\nf -3/-1/-1 -2/-1/-1 -1/-1/-1\n";
+        let data = parse_obj(obj).unwrap();
+        assert_eq!(positions(&data), [Vec3::ZERO, Vec3::X, Vec3::Y]);
+        assert!(data.vertices.iter().all(|v| v.uv == vec2(0.5, 0.5)));
+        assert!(data.vertices.iter().all(|v| v.normal == Vec3::Z));
+    }
+
+    #[test]
+    fn indices_out_of_range_are_rejected() {
+        for face in ["f 1 2 4", "f 0 1 2", "f -4 1 2", "f 1/2 2/1 3/1"] {
+            let obj = format!("v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\n{face}\n");
+            let err = parse_obj(&obj).unwrap_err();
+            assert!(err.to_string().contains("line 5"), "{face}: {err}");
+        }
+    }
+
+    #[test]
+    fn triples_are_shared_within_a_material_group_only() {
+        let obj = "\
+v 0 0 0
+v 1 0 0
+v 0 1 0
+v 1 1 0
+vt 0 0
+vt 1 1
+usemtl a
+f 1/1 2/1 3/1
+f 3/1 2/1 4/1
+usemtl b
+f 1/1 2/1 3/1
+f 1/2 2/1 3/1
+usemtl a
+f 4/1 3/1 1/1
+";
+        let model = parse_obj_model(obj).unwrap();
+        let [a, b] = &model.groups[..] else {
+            panic!("expected two groups, got {}", model.groups.len());
+        };
+        assert_eq!(a.material.as_deref(), Some("a"));
+        // The four corners, with the face after switching back to `a` reusing them.
+        assert_eq!(a.data.vertices.len(), 4);
+        assert_eq!(a.data.indices, [0, 1, 2, 2, 1, 3, 3, 2, 0]);
+        // Its own copies of the first three, and a fourth for the corner with another UV.
+        assert_eq!(b.data.vertices.len(), 4);
+        assert_eq!(b.data.indices, [0, 1, 2, 3, 1, 2]);
+        assert_eq!(b.data.vertices[3].uv, vec2(1.0, 1.0));
+    }
+
+    #[test]
+    fn polygons_are_split_into_a_fan() {
+        let obj = "v 0 0 0\nv 1 0 0\nv 2 1 0\nv 1 2 0\nv 0 1 0\nf 1 2 3 4 5\n";
+        let data = parse_obj(obj).unwrap();
+        assert_eq!(data.indices, [0, 1, 2, 0, 2, 3, 0, 3, 4]);
+        let err = parse_obj("v 0 0 0\nv 1 0 0\nf 1 2\n").unwrap_err();
+        assert!(err.to_string().contains("fewer than 3"), "{err}");
+    }
+
+    #[test]
+    fn material_libraries_are_listed_in_order() {
+        let obj =
+            "mtllib first.mtl second.mtl\nmtllib third.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n";
+        let model = parse_obj_model(obj).unwrap();
+        assert_eq!(
+            model.material_libraries,
+            ["first.mtl", "second.mtl", "third.mtl"]
+        );
+    }
+}
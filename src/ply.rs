@@ -0,0 +1,548 @@
+use std::{fs, path::Path};
+
+use glam::{DVec3, Vec3};
+
+use crate::{
+    error::{bail, ensure, Context, ModelLoadingError},
+    mesh::{MeshData, Vertex},
+};
+
+/// Parses an ASCII or binary little endian PLY file into an indexed triangle list.
+///
+/// Vertices take their position from `x y z`, and `nx ny nz` and `red green blue` when present.
+/// Faces are triangulated as a fan around their first vertex. Positions stored as doubles are
+/// moved to around their centroid, computed in double precision, before they're rounded to `f32`,
+/// with the centroid kept as [`MeshData::origin_offset`].
+pub fn load_ply(path: &Path) -> Result<MeshData, ModelLoadingError> {
+    let bytes = fs::read(path).map_err(ModelLoadingError::file_io(path))?;
+    parse_ply(&bytes).with_context(|| format!("failed to parse {}", path.display()))
+}
+
+pub fn parse_ply(bytes: &[u8]) -> Result<MeshData, ModelLoadingError> {
+    let (header, body) = split_header(bytes)?;
+    let (format, elements) = parse_header(header)?;
+    let mut body = match format {
+        Format::Ascii => {
+            Body::Ascii(std::str::from_utf8(body).context("ASCII body is not valid UTF-8")?)
+        }
+        Format::BinaryLittleEndian => Body::Binary(body),
+    };
+
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    let mut origin_offset = DVec3::ZERO;
+    for element in &elements {
+        match element.name.as_str() {
+            "vertex" => (vertices, origin_offset) = read_vertices(&mut body, element)?,
+            "face" => indices = read_faces(&mut body, element, vertices.len())?,
+            _ => {
+                for _ in 0..element.count {
+                    for property in &element.properties {
+                        property.skip(&mut body)?;
+                    }
+                }
+            }
+        }
+    }
+
+    if indices.is_empty() {
+        bail!("no faces found");
+    }
+    let mut data = MeshData {
+        vertices,
+        indices,
+        origin_offset,
+    };
+    if !data.has_normals() {
+        data.compute_smooth_normals();
+    }
+    Ok(data)
+}
+
+/// Color given to vertices when the PLY doesn't provide one.
+const DEFAULT_COLOR: Vec3 = Vec3::ONE;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ScalarType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl ScalarType {
+    fn parse(name: &str) -> Result<Self, ModelLoadingError> {
+        Ok(match name {
+            "char" | "int8" => ScalarType::I8,
+            "uchar" | "uint8" => ScalarType::U8,
+            "short" | "int16" => ScalarType::I16,
+            "ushort" | "uint16" => ScalarType::U16,
+            "int" | "int32" => ScalarType::I32,
+            "uint" | "uint32" => ScalarType::U32,
+            "float" | "float32" => ScalarType::F32,
+            "double" | "float64" => ScalarType::F64,
+            _ => bail!("unknown property type {name:?}"),
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            ScalarType::I8 | ScalarType::U8 => 1,
+            ScalarType::I16 | ScalarType::U16 => 2,
+            ScalarType::I32 | ScalarType::U32 | ScalarType::F32 => 4,
+            ScalarType::F64 => 8,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Property {
+    Scalar {
+        name: String,
+        ty: ScalarType,
+    },
+    List {
+        name: String,
+        count_ty: ScalarType,
+        item_ty: ScalarType,
+    },
+}
+
+impl Property {
+    fn name(&self) -> &str {
+        match self {
+            Property::Scalar { name, .. } | Property::List { name, .. } => name,
+        }
+    }
+
+    /// Type of the first value the property reads, a list's count.
+    fn leading_type(&self) -> ScalarType {
+        match self {
+            Property::Scalar { ty, .. } => *ty,
+            Property::List { count_ty, .. } => *count_ty,
+        }
+    }
+
+    fn skip(&self, body: &mut Body) -> Result<(), ModelLoadingError> {
+        match self {
+            Property::Scalar { ty, .. } => {
+                body.read(*ty)?;
+            }
+            Property::List {
+                count_ty, item_ty, ..
+            } => {
+                let count = body.read(*count_ty)? as usize;
+                for _ in 0..count {
+                    body.read(*item_ty)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+/// The element data following the header still to be read, read one scalar at a time.
+enum Body<'a> {
+    Ascii(&'a str),
+    Binary(&'a [u8]),
+}
+
+impl Body<'_> {
+    /// `count` records of `record`, one value of each type, capped at how many of them the rest
+    /// of the body could hold, so counts in the file can't allocate more than its size.
+    fn capacity(&self, count: usize, record: &[ScalarType]) -> usize {
+        let (remaining, record_size) = match self {
+            // A digit and the whitespace after it per value.
+            Body::Ascii(text) => (text.len(), 2 * record.len()),
+            Body::Binary(bytes) => (bytes.len(), record.iter().map(|ty| ty.size()).sum()),
+        };
+        count.min(remaining / record_size.max(1))
+    }
+
+    fn read(&mut self, ty: ScalarType) -> Result<f64, ModelLoadingError> {
+        match self {
+            Body::Ascii(text) => {
+                let rest = text.trim_start_matches(|c: char| c.is_ascii_whitespace());
+                let end = rest
+                    .find(|c: char| c.is_ascii_whitespace())
+                    .unwrap_or(rest.len());
+                let (token, rest) = rest.split_at(end);
+                *text = rest;
+                ensure!(!token.is_empty(), "unexpected end of data");
+                token
+                    .parse()
+                    .with_context(|| format!("invalid number {token:?}"))
+            }
+            Body::Binary(bytes) => {
+                ensure!(bytes.len() >= ty.size(), "unexpected end of data");
+                let (value, rest) = bytes.split_at(ty.size());
+                *bytes = rest;
+                Ok(match ty {
+                    ScalarType::I8 => i8::from_le_bytes(value.try_into()?).into(),
+                    ScalarType::U8 => u8::from_le_bytes(value.try_into()?).into(),
+                    ScalarType::I16 => i16::from_le_bytes(value.try_into()?).into(),
+                    ScalarType::U16 => u16::from_le_bytes(value.try_into()?).into(),
+                    ScalarType::I32 => i32::from_le_bytes(value.try_into()?).into(),
+                    ScalarType::U32 => u32::from_le_bytes(value.try_into()?).into(),
+                    ScalarType::F32 => f32::from_le_bytes(value.try_into()?).into(),
+                    ScalarType::F64 => f64::from_le_bytes(value.try_into()?),
+                })
+            }
+        }
+    }
+}
+
+/// Splits the file after the `end_header` line.
+fn split_header(bytes: &[u8]) -> Result<(&str, &[u8]), ModelLoadingError> {
+    const END: &[u8] = b"end_header";
+    let end = bytes
+        .windows(END.len())
+        .position(|window| window == END)
+        .context("missing end_header")?;
+    let newline = bytes[end..]
+        .iter()
+        .position(|&byte| byte == b'\n')
+        .context("missing newline after end_header")?;
+    let header = std::str::from_utf8(&bytes[..end]).context("header is not valid UTF-8")?;
+    Ok((header, &bytes[end + newline + 1..]))
+}
+
+fn parse_header(header: &str) -> Result<(Format, Vec<Element>), ModelLoadingError> {
+    let mut lines = header.lines();
+    ensure!(lines.next().map(str::trim) == Some("ply"), "not a PLY file");
+
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    for line in lines {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens.as_slice() {
+            ["format", "ascii", _] => format = Some(Format::Ascii),
+            ["format", "binary_little_endian", _] => format = Some(Format::BinaryLittleEndian),
+            ["format", "binary_big_endian", _] => {
+                bail!(UnsupportedFormat, "big endian PLY files are not supported")
+            }
+            ["format", other, _] => bail!(UnsupportedFormat, "unsupported PLY format {other:?}"),
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .with_context(|| format!("invalid element count {count:?}"))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count_ty, item_ty, name] => elements
+                .last_mut()
+                .context("property before any element")?
+                .properties
+                .push(Property::List {
+                    name: name.to_string(),
+                    count_ty: ScalarType::parse(count_ty)?,
+                    item_ty: ScalarType::parse(item_ty)?,
+                }),
+            ["property", ty, name] => elements
+                .last_mut()
+                .context("property before any element")?
+                .properties
+                .push(Property::Scalar {
+                    name: name.to_string(),
+                    ty: ScalarType::parse(ty)?,
+                }),
+            ["comment" | "obj_info", ..] | [] => (),
+            _ => bail!("invalid header line {line:?}"),
+        }
+    }
+    Ok((format.context("missing format line")?, elements))
+}
+
+/// Reads the vertices along with the offset their positions were moved by, see [`parse_ply`].
+fn read_vertices(
+    body: &mut Body,
+    element: &Element,
+) -> Result<(Vec<Vertex>, DVec3), ModelLoadingError> {
+    let has = |name: &str| element.properties.iter().any(|p| p.name() == name);
+    ensure!(
+        has("x") && has("y") && has("z"),
+        "vertex element has no x, y and z properties"
+    );
+    let double_precision = element.properties.iter().any(|property| {
+        matches!(
+            property,
+            Property::Scalar { name, ty: ScalarType::F64 } if ["x", "y", "z"].contains(&name.as_str())
+        )
+    });
+    // 8 bit colors are 0-255, wider ones are assumed to be normalized already.
+    let color_scale = match element.properties.iter().find(|p| p.name() == "red") {
+        Some(Property::Scalar {
+            ty: ScalarType::U8, ..
+        }) => 1.0 / 255.0,
+        _ => 1.0,
+    };
+
+    let record: Vec<ScalarType> = element
+        .properties
+        .iter()
+        .map(Property::leading_type)
+        .collect();
+    let capacity = body.capacity(element.count, &record);
+    let mut vertices = Vec::with_capacity(capacity);
+    let mut positions = Vec::with_capacity(capacity);
+    for _ in 0..element.count {
+        let mut vertex = Vertex {
+            color: DEFAULT_COLOR,
+            ..Default::default()
+        };
+        let mut position = DVec3::ZERO;
+        for property in &element.properties {
+            let Property::Scalar { name, ty } = property else {
+                property.skip(body)?;
+                continue;
+            };
+            let value = body.read(*ty)?;
+            match name.as_str() {
+                "x" => position.x = value,
+                "y" => position.y = value,
+                "z" => position.z = value,
+                _ => (),
+            }
+            let value = value as f32;
+            match name.as_str() {
+                "nx" => vertex.normal.x = value,
+                "ny" => vertex.normal.y = value,
+                "nz" => vertex.normal.z = value,
+                "red" => vertex.color.x = value * color_scale,
+                "green" => vertex.color.y = value * color_scale,
+                "blue" => vertex.color.z = value * color_scale,
+                _ => (),
+            }
+        }
+        vertices.push(vertex);
+        positions.push(position);
+    }
+
+    let origin_offset = if double_precision && !positions.is_empty() {
+        positions.iter().sum::<DVec3>() / positions.len() as f64
+    } else {
+        DVec3::ZERO
+    };
+    for (vertex, position) in vertices.iter_mut().zip(positions) {
+        vertex.position = (position - origin_offset).as_vec3();
+    }
+    Ok((vertices, origin_offset))
+}
+
+fn read_faces(
+    body: &mut Body,
+    element: &Element,
+    vertex_count: usize,
+) -> Result<Vec<u32>, ModelLoadingError> {
+    let mut indices = Vec::new();
+    for face_no in 0..element.count {
+        for property in &element.properties {
+            let Property::List {
+                name,
+                count_ty,
+                item_ty,
+            } = property
+            else {
+                property.skip(body)?;
+                continue;
+            };
+            if name != "vertex_indices" && name != "vertex_index" {
+                property.skip(body)?;
+                continue;
+            }
+
+            let count = body.read(*count_ty)? as usize;
+            ensure!(count >= 3, "face {face_no} has fewer than 3 vertices");
+            let mut face = Vec::with_capacity(body.capacity(count, &[*item_ty]));
+            for _ in 0..count {
+                let index = body.read(*item_ty)?;
+                ensure!(
+                    index >= 0.0 && (index as usize) < vertex_count,
+                    "face {face_no}: vertex index {index} out of range"
+                );
+                face.push(index as u32);
+            }
+            for i in 1..face.len() - 1 {
+                indices.extend_from_slice(&[face[0], face[i], face[i + 1]]);
+            }
+        }
+    }
+    Ok(indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn double_positions_are_recentered_before_rounding() {
+        // A centimeter apart on top of geocentric coordinates, far more than f32 can resolve.
+        let ply = "ply
+format ascii 1.0
+element vertex 3
+property double x
+property double y
+property double z
+element face 1
+property list uchar int vertex_indices
+end_header
+4510023.00 612000.50 4448000.25
+4510023.01 612000.50 4448000.25
+4510023.00 612000.51 4448000.25
+3 0 1 2
+";
+        let data = parse_ply(ply.as_bytes()).unwrap();
+        let centroid = DVec3::new(4510023.0 + 0.01 / 3.0, 612000.5 + 0.01 / 3.0, 4448000.25);
+        assert!(data.origin_offset.abs_diff_eq(centroid, 1e-6));
+        let positions: Vec<Vec3> = data.vertices.iter().map(|v| v.position).collect();
+        let edge = positions[1] - positions[0];
+        assert!(edge.abs_diff_eq(Vec3::new(0.01, 0.0, 0.0), 1e-6), "{edge}");
+        let centered: Vec3 = positions.iter().sum::<Vec3>() / 3.0;
+        assert!(centered.abs_diff_eq(Vec3::ZERO, 1e-6), "{centered}");
+    }
+
+    #[test]
+    fn float_positions_are_kept_in_place() {
+        let ply = "ply
+format ascii 1.0
+element vertex 3
+property float x
+property float y
+property float z
+element face 1
+property list uchar int vertex_indices
+end_header
+10 0 0
+11 0 0
+10 1 0
+3 0 1 2
+";
+        let data = parse_ply(ply.as_bytes()).unwrap();
+        assert_eq!(data.origin_offset, DVec3::ZERO);
+        assert_eq!(data.vertices[1].position, Vec3::new(11.0, 0.0, 0.0));
+    }
+
+    /// A single triangle or quad face over the vertices, in ASCII.
+    fn ascii_ply(vertex_properties: &str, vertices: &str, face: &str) -> String {
+        let vertex_count = vertices.lines().count();
+        format!(
+            "ply\nformat ascii 1.0\nelement vertex {vertex_count}\n{vertex_properties}\
+             element face 1\nproperty list uchar int vertex_indices\nend_header\n{vertices}{face}\n"
+        )
+    }
+
+    const XYZ: &str = "property float x\nproperty float y\nproperty float z\n";
+
+    #[test]
+    fn ascii_files_are_read() {
+        let ply = ascii_ply(
+            &format!("{XYZ}property float nx\nproperty float ny\nproperty float nz\n"),
+            "0 0 0 0 0 1\n1 0 0 0 0 1\n0 1 0 0 0 1\n",
+            "3 0 1 2",
+        );
+        let data = parse_ply(ply.as_bytes()).unwrap();
+        let positions: Vec<Vec3> = data.vertices.iter().map(|v| v.position).collect();
+        assert_eq!(positions, [Vec3::ZERO, Vec3::X, Vec3::Y]);
+        assert!(data.vertices.iter().all(|v| v.normal == Vec3::Z));
+        assert_eq!(data.indices, [0, 1, 2]);
+    }
+
+    #[test]
+    fn binary_little_endian_files_are_read() {
+        let mut ply = b"ply\nformat binary_little_endian 1.0\nelement vertex 3\n".to_vec();
+        ply.extend_from_slice(XYZ.as_bytes());
+        ply.extend_from_slice(
+            b"element face 1\nproperty list uchar uint vertex_indices\nend_header\n",
+        );
+        for position in [[0.0f32, 0.0, 0.0], [2.0, 0.0, 0.0], [0.0, 0.0, -3.5]] {
+            for coordinate in position {
+                ply.extend_from_slice(&coordinate.to_le_bytes());
+            }
+        }
+        ply.push(3);
+        for index in [2u32, 1, 0] {
+            ply.extend_from_slice(&index.to_le_bytes());
+        }
+        let data = parse_ply(&ply).unwrap();
+        assert_eq!(data.vertices[1].position, Vec3::new(2.0, 0.0, 0.0));
+        assert_eq!(data.vertices[2].position, Vec3::new(0.0, 0.0, -3.5));
+        assert_eq!(data.indices, [2, 1, 0]);
+    }
+
+    #[test]
+    fn byte_colors_are_normalized() {
+        let ply = ascii_ply(
+            &format!("{XYZ}property uchar red\nproperty uchar green\nproperty uchar blue\n"),
+            "0 0 0 255 0 51\n1 0 0 0 255 0\n0 1 0 0 0 255\n",
+            "3 0 1 2",
+        );
+        let data = parse_ply(ply.as_bytes()).unwrap();
+        assert!(data.vertices[0]
+            .color
+            .abs_diff_eq(Vec3::new(1.0, 0.0, 0.2), 1e-6));
+        assert_eq!(data.vertices[1].color, Vec3::Y);
+    }
+
+    #[test]
+    fn vertices_without_colors_are_white() {
+        let ply = ascii_ply(XYZ, "0 0 0\n1 0 0\n0 1 0\n", "3 0 1 2");
+        let data = parse_ply(ply.as_bytes()).unwrap();
+        assert!(data.vertices.iter().all(|v| v.color == DEFAULT_COLOR));
+    }
+
+    #[test]
+    fn quads_are_split_into_a_fan() {
+        let ply = ascii_ply(XYZ, "0 0 0\n1 0 0\n1 1 0\n0 1 0\n", "4 0 1 2 3");
+        let data = parse_ply(ply.as_bytes()).unwrap();
+        assert_eq!(data.indices, [0, 1, 2, 0, 2, 3]);
+    }
+
+    #[test]
+    fn big_endian_files_are_rejected() {
+        let ply = b"ply\nformat binary_big_endian 1.0\nelement vertex 0\nend_header\n";
+        let err = parse_ply(ply).unwrap_err();
+        assert!(
+            matches!(err, ModelLoadingError::UnsupportedFormat(_)),
+            "{err:?}"
+        );
+    }
+
+    #[test]
+    fn face_indices_past_the_vertices_are_rejected() {
+        let ply = ascii_ply(XYZ, "0 0 0\n1 0 0\n0 1 0\n", "3 0 1 3");
+        let err = parse_ply(ply.as_bytes()).unwrap_err();
+        assert!(matches!(err, ModelLoadingError::InvalidData(_)), "{err:?}");
+        assert!(err.to_string().contains("out of range"), "{err}");
+    }
+
+    #[test]
+    fn counts_past_the_end_of_the_file_fail_without_allocating_them() {
+        let mut ply = b"ply\nformat binary_little_endian 1.0\nelement vertex 4000000000\n".to_vec();
+        ply.extend_from_slice(XYZ.as_bytes());
+        ply.extend_from_slice(b"end_header\n");
+        ply.extend_from_slice(&[0; 12]);
+        let err = parse_ply(&ply).unwrap_err();
+        assert!(err.to_string().contains("unexpected end of data"), "{err}");
+
+        let ply = ascii_ply(XYZ, "0 0 0\n1 0 0\n0 1 0\n", "255 0 1 2");
+        let err = parse_ply(ply.as_bytes()).unwrap_err();
+        assert!(err.to_string().contains("unexpected end of data"), "{err}");
+    }
+}
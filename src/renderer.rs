@@ -1,11 +1,34 @@
-use std::{ffi::CString, ops::Deref};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    f32::consts::TAU,
+    ffi::CString,
+    fs,
+    ops::Deref,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
 
-use bytemuck::{cast_slice, offset_of, Pod, Zeroable};
-use glam::{vec2, vec3, Vec2, Vec3};
+use bitflags::bitflags;
+use bytemuck::cast_slice;
+use glam::{vec2, vec3, Mat3, Mat4, Vec2, Vec3};
 use glutin::prelude::GlDisplay;
+use image::{imageops, RgbaImage};
 
 use crate::{
-    gl::get_gl_string,
+    benchmark::{flythrough_view, BenchConfig, BenchReport, FrameTimeStats},
+    camera::{Camera, CameraMode},
+    error::{bail, ensure, Context, ModelLoadingError},
+    frustum::Frustum,
+    gl::{enable_debug_output, get_gl_string, log_context_version, Capabilities},
+    gpu_timer::GpuTimer,
+    light::{Light, MAX_LIGHTS},
+    mesh::{Aabb, Mesh, MeshSnapshot, PrimitiveMode, Vertex},
+    scene::{pick_closest, MeshId, Node, Scene},
+    shader::{ShaderLibrary, ShaderProgram, MATRICES_BINDING},
+    texture::{CubeMap, Texture},
+    trace::{write_trace, TracedGl},
+    transform::Transform,
     window::gl::{self, types::GLfloat},
 };
 
@@ -16,206 +39,4472 @@ fn load_gl_fn_ptrs<D: GlDisplay>(gl_display: &D) -> gl::Gl {
     });
 
     if let Some(renderer) = get_gl_string(&gl, gl::RENDERER) {
-        println!("Running on {}", renderer.to_string_lossy());
+        log::info!("Running on {}", renderer.to_string_lossy());
     }
     if let Some(version) = get_gl_string(&gl, gl::VERSION) {
-        println!("OpenGL Version {}", version.to_string_lossy());
+        log::info!("OpenGL Version {}", version.to_string_lossy());
     }
     if let Some(shaders_version) = get_gl_string(&gl, gl::SHADING_LANGUAGE_VERSION) {
-        println!("Shaders version on {}", shaders_version.to_string_lossy());
+        log::info!("Shaders version on {}", shaders_version.to_string_lossy());
     }
+    log_context_version(&gl);
 
     gl
 }
 
-pub struct Renderer {
-    program: gl::types::GLuint,
-    vao: gl::types::GLuint,
-    vbo: gl::types::GLuint,
-    gl: gl::Gl,
+/// Which triangles are discarded before rasterization. Front faces wind counter-clockwise, so
+/// meshes need consistent winding for culling to look right; see [`Mesh::flip_winding`] for
+/// models exported the other way round.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CullMode {
+    #[default]
+    None,
+    Back,
+    Front,
 }
 
-impl Renderer {
-    pub fn new<D: GlDisplay>(gl_display: &D) -> Self {
-        let gl = load_gl_fn_ptrs(gl_display);
-        unsafe {
-            let vertex_shader = create_shader(&gl, gl::VERTEX_SHADER, VERTEX_SHADER_SOURCE);
-            let fragment_shader = create_shader(&gl, gl::FRAGMENT_SHADER, FRAGMENT_SHADER_SOURCE);
+impl CullMode {
+    fn gl_face(self) -> Option<gl::types::GLenum> {
+        match self {
+            CullMode::None => None,
+            CullMode::Back => Some(gl::BACK),
+            CullMode::Front => Some(gl::FRONT),
+        }
+    }
+}
+
+/// How a fragment's depth, or the stencil reference value, is compared against what's in the
+/// depth or stencil buffer to decide whether the fragment is drawn.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CompareFunc {
+    Never,
+    /// Drawn when less than what's there. For depth, that's closer, so the nearest surface wins.
+    #[default]
+    Less,
+    Equal,
+    /// Like `Less`, but also passes fragments at the same depth, e.g. to draw over an earlier pass
+    /// of the same geometry.
+    LessEqual,
+    Greater,
+    NotEqual,
+    GreaterEqual,
+    Always,
+}
+
+impl CompareFunc {
+    fn gl_enum(self) -> gl::types::GLenum {
+        match self {
+            CompareFunc::Never => gl::NEVER,
+            CompareFunc::Less => gl::LESS,
+            CompareFunc::Equal => gl::EQUAL,
+            CompareFunc::LessEqual => gl::LEQUAL,
+            CompareFunc::Greater => gl::GREATER,
+            CompareFunc::NotEqual => gl::NOTEQUAL,
+            CompareFunc::GreaterEqual => gl::GEQUAL,
+            CompareFunc::Always => gl::ALWAYS,
+        }
+    }
+}
+
+/// What happens to the stencil value of a pixel, see [`Renderer::set_stencil_op`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StencilOp {
+    #[default]
+    Keep,
+    Zero,
+    /// Sets it to the reference value given to [`Renderer::set_stencil_func`].
+    Replace,
+    /// Adds one, stopping at the largest value.
+    Increment,
+    /// Adds one, going back to zero past the largest value.
+    IncrementWrap,
+    /// Subtracts one, stopping at zero.
+    Decrement,
+    /// Subtracts one, going to the largest value below zero.
+    DecrementWrap,
+    Invert,
+}
 
-            let program = gl.CreateProgram();
+impl StencilOp {
+    fn gl_enum(self) -> gl::types::GLenum {
+        match self {
+            StencilOp::Keep => gl::KEEP,
+            StencilOp::Zero => gl::ZERO,
+            StencilOp::Replace => gl::REPLACE,
+            StencilOp::Increment => gl::INCR,
+            StencilOp::IncrementWrap => gl::INCR_WRAP,
+            StencilOp::Decrement => gl::DECR,
+            StencilOp::DecrementWrap => gl::DECR_WRAP,
+            StencilOp::Invert => gl::INVERT,
+        }
+    }
+}
 
-            gl.AttachShader(program, vertex_shader);
-            gl.AttachShader(program, fragment_shader);
+/// How transparent meshes, those whose [`crate::material::Material::alpha`] is below 1, are
+/// combined with what's behind them. They're drawn after the opaque ones, furthest first, without
+/// writing depth.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BlendMode {
+    /// Alpha is ignored and every mesh is drawn solid, in scene order.
+    #[default]
+    Opaque,
+    /// Mixes in the mesh's color by its alpha, like tinted glass.
+    AlphaBlend,
+    /// Adds the mesh's color scaled by its alpha, brightening what's behind, e.g. for glows.
+    Additive,
+}
 
-            gl.LinkProgram(program);
+impl BlendMode {
+    fn gl_factors(self) -> Option<(gl::types::GLenum, gl::types::GLenum)> {
+        match self {
+            BlendMode::Opaque => None,
+            BlendMode::AlphaBlend => Some((gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA)),
+            BlendMode::Additive => Some((gl::SRC_ALPHA, gl::ONE)),
+        }
+    }
+}
 
-            gl.UseProgram(program);
+/// Replaces the shading of every mesh with a visualization of its geometry, see
+/// [`Renderer::set_debug_view`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DebugView {
+    /// Meshes are drawn with the active program.
+    #[default]
+    None,
+    /// Colors each vertex by its mean curvature through the turbo color map, blue where the
+    /// surface is concave through green where it's flat to red where it's convex, to spot dents
+    /// and bumps in scanned or subdivided surfaces. See [`Mesh::compute_curvature`].
+    Curvature,
+}
 
-            gl.DeleteShader(vertex_shader);
-            gl.DeleteShader(fragment_shader);
+/// How the built-in programs light meshes, see [`Renderer::set_shading`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShadingModel {
+    /// Phong lighting from the scene's lights.
+    Lit,
+    /// The base color, vertex colors and texture alone.
+    Unlit,
+    /// Cel shading: the lit model with the diffuse light of each light rounded up to one of
+    /// `bands` levels of equal width, and highlights cut off sharply instead of fading.
+    Toon { bands: u32 },
+}
 
-            let mut vao = std::mem::zeroed();
-            gl.CreateVertexArrays(1, &mut vao);
-            assert_ne!(vao, 0);
+/// Settings of the screen-space ambient occlusion pass, see [`Renderer::set_ssao`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SsaoParams {
+    /// How far around each fragment, in view space units, surfaces count as occluding it.
+    pub radius: f32,
+    /// How far in front of a sample a surface has to be to occlude it, which keeps surfaces from
+    /// occluding themselves.
+    pub bias: f32,
+    /// Points sampled in the hemisphere above each fragment, from 1 to [`MAX_SSAO_SAMPLES`].
+    pub samples: u32,
+    /// Exponent of the unoccluded share, darkening creases further the higher it is.
+    pub intensity: f32,
+}
 
-            let mut vbo = std::mem::zeroed();
-            gl.CreateBuffers(1, &mut vbo);
-            assert_ne!(vbo, 0);
-            let vertex_data_as_bytes = cast_slice::<Vertex, u8>(&VERTEX_DATA);
-            gl.NamedBufferStorage(
-                vbo,
-                vertex_data_as_bytes.len() as isize,
-                vertex_data_as_bytes.as_ptr() as *const _,
-                gl::DYNAMIC_STORAGE_BIT,
-            );
+impl Default for SsaoParams {
+    /// Sized for models fit to a unit cube, as loaded ones are.
+    fn default() -> Self {
+        Self {
+            radius: 0.1,
+            bias: 0.02,
+            samples: 32,
+            intensity: 1.5,
+        }
+    }
+}
 
-            gl.VertexArrayVertexBuffer(
-                vao,
-                0,
-                vbo,
-                0,
-                std::mem::size_of::<Vertex>() as gl::types::GLsizei,
-            );
+/// Most samples [`SsaoParams::samples`] can ask for, the size of the kernel in the shader.
+pub const MAX_SSAO_SAMPLES: u32 = 64;
 
-            let pos_attrib = gl.GetAttribLocation(program, b"position\0".as_ptr() as *const _);
-            gl.EnableVertexArrayAttrib(vao, pos_attrib as u32);
-            gl.VertexArrayAttribFormat(vao, pos_attrib as u32, 2, gl::FLOAT, false as u8, 0);
-            gl.VertexArrayAttribBinding(vao, pos_attrib as u32, 0);
-
-            let color_attrib = gl.GetAttribLocation(program, b"color\0".as_ptr() as *const _);
-            gl.EnableVertexArrayAttrib(vao, color_attrib as u32);
-            gl.VertexArrayAttribFormat(
-                vao,
-                color_attrib as u32,
-                (size_of::<Vec3>() / size_of::<f32>()) as i32,
-                gl::FLOAT,
-                false as u8,
-                offset_of!(Vertex, color) as u32,
-            );
-            gl.VertexArrayAttribBinding(vao, color_attrib as u32, 0);
+/// Lines [`Renderer::set_edge_outline`] draws where the depth or normals around a pixel change
+/// sharply. Both are measured with a Sobel filter over the 3x3 pixels around it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EdgeStyle {
+    /// Change in view depth across the pixel, as a fraction of the pixel's own depth, above which
+    /// it's on a silhouette.
+    pub depth_threshold: f32,
+    /// Change in the unit normal across the pixel above which it's on a crease, e.g. 0.5 for
+    /// folds of about 30 degrees, or `f32::INFINITY` to outline silhouettes alone.
+    pub normal_threshold: f32,
+    pub color: Vec3,
+    /// Distance in pixels to the neighbors each pixel is compared with, which widens the lines.
+    pub thickness: f32,
+}
+
+impl Default for EdgeStyle {
+    /// Black lines around silhouettes and folds sharper than about 30 degrees.
+    fn default() -> Self {
+        Self {
+            depth_threshold: 0.1,
+            normal_threshold: 0.5,
+            color: Vec3::ZERO,
+            thickness: 1.0,
+        }
+    }
+}
+
+bitflags! {
+    /// Buffers cleared at the start of a frame, see [`Renderer::set_clear_flags`].
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct ClearFlags: gl::types::GLbitfield {
+        const COLOR = gl::COLOR_BUFFER_BIT;
+        const DEPTH = gl::DEPTH_BUFFER_BIT;
+        const STENCIL = gl::STENCIL_BUFFER_BIT;
+    }
+}
+
+impl Default for ClearFlags {
+    fn default() -> Self {
+        ClearFlags::COLOR | ClearFlags::DEPTH
+    }
+}
+
+/// How view space is flattened onto the viewport. The planes are distances along the view
+/// direction.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    /// Things shrink with distance, with `fov` the vertical field of view in radians.
+    Perspective { fov: f32, near: f32, far: f32 },
+    /// Things keep their size whatever the distance, as for technical drawings. `height` world
+    /// units fit the viewport vertically, and the width follows the aspect ratio so nothing is
+    /// stretched.
+    Orthographic { height: f32, near: f32, far: f32 },
+}
+
+impl Projection {
+    pub fn matrix(&self, (width, height): (i32, i32)) -> Mat4 {
+        let aspect = width.max(1) as f32 / height.max(1) as f32;
+        match *self {
+            Projection::Perspective { fov, near, far } => {
+                Mat4::perspective_rh_gl(fov, aspect, near, far)
+            }
+            Projection::Orthographic { height, near, far } => {
+                let (half_width, half_height) = (height * aspect / 2.0, height / 2.0);
+                Mat4::orthographic_rh_gl(
+                    -half_width,
+                    half_width,
+                    -half_height,
+                    half_height,
+                    near,
+                    far,
+                )
+            }
+        }
+    }
+
+    /// Distances to the near and far clipping planes.
+    pub fn clip_planes(&self) -> (f32, f32) {
+        match *self {
+            Projection::Perspective { near, far, .. }
+            | Projection::Orthographic { near, far, .. } => (near, far),
+        }
+    }
+
+    /// The same projection with a field of view of `fov` if it's a perspective one.
+    fn with_fov(self, fov: f32) -> Self {
+        match self {
+            Projection::Perspective { near, far, .. } => Projection::Perspective { fov, near, far },
+            orthographic => orthographic,
+        }
+    }
+}
+
+/// How many meshes the last frame drew, and how many it skipped for being outside the view.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DrawStats {
+    pub drawn: usize,
+    pub culled: usize,
+    /// Triangles of the drawn meshes, counting every copy [`Renderer::set_array`] makes.
+    pub triangles: usize,
+}
+
+/// Per-instance transforms for [`Renderer::draw_instanced`], created on first use.
+#[derive(Default)]
+struct InstanceBuffer {
+    buffer: gl::types::GLuint,
+}
 
-            Self {
+/// Copies of every mesh laid out along the world axes by [`Renderer::set_array`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct InstanceArray {
+    counts: [u32; 3],
+    spacing: Vec3,
+}
+
+impl InstanceArray {
+    /// `counts` raised to at least 1 and, if that makes more than [`MAX_ARRAY_INSTANCES`] copies,
+    /// with the largest lowered until it doesn't.
+    fn capped_counts(counts: [u32; 3]) -> [u32; 3] {
+        // A count over the cap is too many copies on its own, and clamping it first keeps the loop
+        // below to a few thousand passes however large the counts are.
+        let mut counts = counts.map(|count| count.clamp(1, MAX_ARRAY_INSTANCES as u32));
+        let total = |counts: &[u32; 3]| {
+            counts
+                .iter()
+                .map(|&count| u64::from(count))
+                .product::<u64>()
+        };
+        while total(&counts) > MAX_ARRAY_INSTANCES {
+            let largest = counts.iter_mut().max().expect("there are three counts");
+            *largest -= 1;
+        }
+        counts
+    }
+
+    /// Offset of every copy from the original, the original's own zero offset included.
+    fn offsets(&self) -> impl Iterator<Item = Vec3> + '_ {
+        let [nx, ny, nz] = self.counts;
+        (0..nz).flat_map(move |z| {
+            (0..ny).flat_map(move |y| {
+                (0..nx).map(move |x| vec3(x as f32, y as f32, z as f32) * self.spacing)
+            })
+        })
+    }
+
+    /// Offset of the furthest copy, so the boxes at no offset and this one contain every copy.
+    fn extent(&self) -> Vec3 {
+        let [nx, ny, nz] = self.counts.map(|count| (count - 1) as f32);
+        vec3(nx, ny, nz) * self.spacing
+    }
+}
+
+/// Line geometry of the last grid drawn, kept until a different size is asked for.
+struct Grid {
+    size: f32,
+    divisions: u32,
+    mesh: Mesh,
+}
+
+/// Quad and program [`Renderer::draw_billboard`] draws with, created the first time it's called.
+struct Billboard {
+    /// A unit square in the XY plane centered on the origin, with V running down from the top.
+    mesh: Mesh,
+    program: ShaderProgram,
+}
+
+/// Environment drawn behind the scene, see [`Renderer::set_skybox`].
+struct Skybox {
+    cube_map: CubeMap,
+    /// Unit cube around the camera, seen from the inside.
+    mesh: Mesh,
+    program: ShaderProgram,
+}
+
+/// Multisampled color and depth renderbuffers the window's frames are drawn into before being
+/// resolved to the default framebuffer.
+struct MsaaTarget {
+    framebuffer: gl::types::GLuint,
+    renderbuffers: [gl::types::GLuint; 2],
+}
+
+/// Depth of the scene as seen from the first directional light, which the lit program compares
+/// fragments against to tell whether the light reaches them.
+struct ShadowMap {
+    framebuffer: gl::types::GLuint,
+    /// Depth texture sampled with depth comparison, reading as lit outside the light's view.
+    texture: gl::types::GLuint,
+    resolution: u32,
+    /// Draws meshes' depth alone, from the light.
+    program: ShaderProgram,
+    /// World to light clip space as of the last depth pass, or `None` if there was no directional
+    /// light or nothing to cast a shadow then.
+    light_matrix: Cell<Option<Mat4>>,
+}
+
+impl ShadowMap {
+    fn new(gl: &TracedGl, resolution: u32) -> Result<Self, ModelLoadingError> {
+        let program = ShaderProgram::from_sources(
+            gl,
+            SHADOW_VERTEX_SHADER_SOURCE,
+            SHADOW_FRAGMENT_SHADER_SOURCE,
+        )
+        .context("failed to build the shadow map program")?;
+        let size = resolution as gl::types::GLsizei;
+        let parameters = [
+            (gl::TEXTURE_MIN_FILTER, gl::LINEAR),
+            (gl::TEXTURE_MAG_FILTER, gl::LINEAR),
+            (gl::TEXTURE_WRAP_S, gl::CLAMP_TO_BORDER),
+            (gl::TEXTURE_WRAP_T, gl::CLAMP_TO_BORDER),
+            (gl::TEXTURE_COMPARE_MODE, gl::COMPARE_REF_TO_TEXTURE),
+            (gl::TEXTURE_COMPARE_FUNC, gl::LEQUAL),
+        ];
+        // The far plane's depth, which nothing is behind.
+        let border = [1.0f32; 4];
+        let (mut framebuffer, mut texture) = (0, 0);
+        unsafe {
+            let status = if gl.direct_state_access() {
+                gl.CreateTextures(gl::TEXTURE_2D, 1, &mut texture);
+                gl.TextureStorage2D(texture, 1, gl::DEPTH_COMPONENT24, size, size);
+                for (name, value) in parameters {
+                    gl.TextureParameteri(texture, name, value as i32);
+                }
+                gl.TextureParameterfv(texture, gl::TEXTURE_BORDER_COLOR, border.as_ptr());
+                gl.CreateFramebuffers(1, &mut framebuffer);
+                gl.NamedFramebufferTexture(framebuffer, gl::DEPTH_ATTACHMENT, texture, 0);
+                gl.NamedFramebufferDrawBuffer(framebuffer, gl::NONE);
+                gl.NamedFramebufferReadBuffer(framebuffer, gl::NONE);
+                gl.CheckNamedFramebufferStatus(framebuffer, gl::FRAMEBUFFER)
+            } else {
+                gl.GenTextures(1, &mut texture);
+                gl.BindTexture(gl::TEXTURE_2D, texture);
+                gl.TexImage2D(
+                    gl::TEXTURE_2D,
+                    0,
+                    gl::DEPTH_COMPONENT24 as i32,
+                    size,
+                    size,
+                    0,
+                    gl::DEPTH_COMPONENT,
+                    gl::FLOAT,
+                    std::ptr::null(),
+                );
+                for (name, value) in parameters {
+                    gl.TexParameteri(gl::TEXTURE_2D, name, value as i32);
+                }
+                gl.TexParameterfv(gl::TEXTURE_2D, gl::TEXTURE_BORDER_COLOR, border.as_ptr());
+                gl.BindTexture(gl::TEXTURE_2D, 0);
+                gl.GenFramebuffers(1, &mut framebuffer);
+                gl.BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+                gl.FramebufferTexture2D(
+                    gl::FRAMEBUFFER,
+                    gl::DEPTH_ATTACHMENT,
+                    gl::TEXTURE_2D,
+                    texture,
+                    0,
+                );
+                gl.DrawBuffer(gl::NONE);
+                gl.ReadBuffer(gl::NONE);
+                let status = gl.CheckFramebufferStatus(gl::FRAMEBUFFER);
+                gl.BindFramebuffer(gl::FRAMEBUFFER, 0);
+                status
+            };
+            let shadow_map = Self {
+                framebuffer,
+                texture,
+                resolution,
                 program,
-                vao,
-                vbo,
-                gl,
+                light_matrix: Cell::new(None),
+            };
+            if status != gl::FRAMEBUFFER_COMPLETE {
+                log::error!("{resolution}x{resolution} shadow map framebuffer is incomplete");
+                shadow_map.delete(gl);
+                return Err(ModelLoadingError::GlError(status));
             }
+            Ok(shadow_map)
         }
     }
 
-    pub fn draw(&self) {
-        self.draw_with_clear_color(0.1, 0.1, 0.1, 0.9)
+    /// Binds the depth texture to [`SHADOW_MAP_TEXTURE_UNIT`].
+    fn bind(&self, gl: &TracedGl) {
+        unsafe {
+            if gl.direct_state_access() {
+                gl.BindTextureUnit(SHADOW_MAP_TEXTURE_UNIT, self.texture);
+            } else {
+                gl.ActiveTexture(gl::TEXTURE0 + SHADOW_MAP_TEXTURE_UNIT);
+                gl.BindTexture(gl::TEXTURE_2D, self.texture);
+            }
+        }
     }
 
-    pub fn draw_with_clear_color(
-        &self,
-        red: GLfloat,
-        green: GLfloat,
-        blue: GLfloat,
-        alpha: GLfloat,
-    ) {
+    fn delete(&self, gl: &TracedGl) {
+        self.program.delete(gl);
         unsafe {
-            self.gl.UseProgram(self.program);
+            gl.DeleteFramebuffers(1, &self.framebuffer);
+            gl.DeleteTextures(1, &self.texture);
+        }
+    }
+}
 
-            self.gl.BindVertexArray(self.vao);
-            self.gl.BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+/// Orthographic view along `direction` taking in the sphere around `bounds`, mapping world space
+/// to the light's clip space. Everything in the sphere is between a third and all of the way to
+/// the far plane, and receivers behind it still compare as further away than its casters.
+fn light_matrix(direction: Vec3, bounds: Aabb) -> Mat4 {
+    let center = bounds.center();
+    let radius = ((bounds.max - bounds.min).length() / 2.0).max(1e-3);
+    let up = if direction.y.abs() > 0.99 {
+        Vec3::Z
+    } else {
+        Vec3::Y
+    };
+    let view = Mat4::look_at_rh(center - direction * 2.0 * radius, center, up);
+    let projection =
+        Mat4::orthographic_rh_gl(-radius, radius, -radius, radius, radius, 3.0 * radius);
+    projection * view
+}
 
-            self.gl.ClearColor(red, green, blue, alpha);
-            self.gl.Clear(gl::COLOR_BUFFER_BIT);
-            self.gl.DrawArrays(gl::TRIANGLES, 0, 3);
+/// The framebuffers, viewport and scissor test bound before a pass drawing elsewhere, which it
+/// puts back afterwards.
+struct DrawTarget {
+    draw_framebuffer: gl::types::GLuint,
+    read_framebuffer: gl::types::GLuint,
+    /// `(x, y, width, height)`, from the bottom left.
+    viewport: [i32; 4],
+    scissor: bool,
+}
+
+impl DrawTarget {
+    unsafe fn current(gl: &TracedGl) -> Self {
+        let (mut draw_framebuffer, mut read_framebuffer) = (0, 0);
+        gl.GetIntegerv(gl::DRAW_FRAMEBUFFER_BINDING, &mut draw_framebuffer);
+        gl.GetIntegerv(gl::READ_FRAMEBUFFER_BINDING, &mut read_framebuffer);
+        let mut viewport = [0; 4];
+        gl.GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+        Self {
+            draw_framebuffer: draw_framebuffer as gl::types::GLuint,
+            read_framebuffer: read_framebuffer as gl::types::GLuint,
+            viewport,
+            scissor: gl.IsEnabled(gl::SCISSOR_TEST) == gl::TRUE,
+        }
+    }
+
+    fn size(&self) -> (i32, i32) {
+        (self.viewport[2], self.viewport[3])
+    }
+
+    unsafe fn restore(&self, gl: &TracedGl) {
+        gl.BindFramebuffer(gl::DRAW_FRAMEBUFFER, self.draw_framebuffer);
+        gl.BindFramebuffer(gl::READ_FRAMEBUFFER, self.read_framebuffer);
+        let [x, y, width, height] = self.viewport;
+        gl.Viewport(x, y, width, height);
+        if self.scissor {
+            gl.Enable(gl::SCISSOR_TEST);
+        }
+    }
+}
+
+/// Textures of one size with framebuffers drawing into them, for passes drawn at the size of the
+/// viewport.
+struct RenderTargets {
+    size: (i32, i32),
+    textures: Vec<gl::types::GLuint>,
+    framebuffers: Vec<gl::types::GLuint>,
+}
+
+impl RenderTargets {
+    /// Textures of the internal `formats`, and a framebuffer for each of `framebuffers`, which
+    /// pair the attachments with the index of the texture in `formats` they draw into.
+    fn new(
+        gl: &TracedGl,
+        size: (i32, i32),
+        formats: &[gl::types::GLenum],
+        framebuffers: &[&[(gl::types::GLenum, usize)]],
+    ) -> Result<Self, ModelLoadingError> {
+        unsafe {
+            let textures: Vec<_> = formats
+                .iter()
+                .map(|&format| create_render_texture(gl, format, size))
+                .collect();
+            let (framebuffers, statuses): (Vec<_>, Vec<_>) = framebuffers
+                .iter()
+                .map(|attachments| {
+                    let attachments: Vec<_> = attachments
+                        .iter()
+                        .map(|&(attachment, index)| (attachment, textures[index]))
+                        .collect();
+                    create_texture_framebuffer(gl, &attachments)
+                })
+                .unzip();
+            let targets = Self {
+                size,
+                textures,
+                framebuffers,
+            };
+            if let Some(status) = statuses
+                .into_iter()
+                .find(|status| *status != gl::FRAMEBUFFER_COMPLETE)
+            {
+                targets.delete(gl);
+                return Err(ModelLoadingError::GlError(status));
+            }
+            Ok(targets)
+        }
+    }
+
+    /// The targets in `slot`, replaced by new ones as for [`RenderTargets::new`] first unless
+    /// they're already `size`.
+    fn sized<'a>(
+        slot: &'a mut Option<Self>,
+        gl: &TracedGl,
+        size: (i32, i32),
+        formats: &[gl::types::GLenum],
+        framebuffers: &[&[(gl::types::GLenum, usize)]],
+    ) -> Result<&'a Self, ModelLoadingError> {
+        if let Some(targets) = slot.take_if(|targets| targets.size != size) {
+            targets.delete(gl);
+        }
+        if slot.is_none() {
+            *slot = Some(Self::new(gl, size, formats, framebuffers)?);
         }
+        Ok(slot.as_ref().expect("the targets were created above"))
     }
 
-    pub fn resize(&self, width: i32, height: i32) {
+    fn delete(&self, gl: &TracedGl) {
         unsafe {
-            self.gl.Viewport(0, 0, width, height);
+            gl.DeleteFramebuffers(self.framebuffers.len() as i32, self.framebuffers.as_ptr());
+            gl.DeleteTextures(self.textures.len() as i32, self.textures.as_ptr());
         }
     }
 }
 
-impl Deref for Renderer {
-    type Target = gl::Gl;
+/// View space normals and depth of the opaque triangles, drawn ahead of the scene for the effects
+/// that read them, [`Renderer::set_ssao`] and [`Renderer::set_edge_outline`].
+struct NormalDepthPass {
+    /// Draws meshes' view space normals, and their depth.
+    program: ShaderProgram,
+    /// A depth and an RGBA8 normal texture, sized to the viewport of the last draw.
+    targets: RefCell<Option<RenderTargets>>,
+}
 
-    fn deref(&self) -> &Self::Target {
-        &self.gl
+impl NormalDepthPass {
+    fn new(gl: &TracedGl) -> Result<Self, ModelLoadingError> {
+        let program = ShaderProgram::from_sources(
+            gl,
+            NORMAL_DEPTH_VERTEX_SHADER_SOURCE,
+            NORMAL_DEPTH_FRAGMENT_SHADER_SOURCE,
+        )
+        .context("failed to build the normal and depth program")?;
+        Ok(Self {
+            program,
+            targets: RefCell::new(None),
+        })
+    }
+
+    fn delete(&self, gl: &TracedGl) {
+        self.program.delete(gl);
+        if let Some(targets) = self.targets.borrow_mut().take() {
+            targets.delete(gl);
+        }
     }
 }
 
-impl Drop for Renderer {
-    fn drop(&mut self) {
-        unsafe {
-            self.gl.DeleteProgram(self.program);
-            self.gl.DeleteBuffers(1, &self.vbo);
-            self.gl.DeleteVertexArrays(1, &self.vao);
+/// The textures [`NormalDepthPass`] drew for the draw under way.
+#[derive(Debug, Clone, Copy)]
+struct NormalDepth {
+    depth: gl::types::GLuint,
+    normals: gl::types::GLuint,
+    size: (i32, i32),
+    /// Bottom left corner of the viewport they were drawn for, which pixel `(0, 0)` of the
+    /// textures is at.
+    origin: Vec2,
+}
+
+/// Screen-space ambient occlusion, darkening the ambient light of the lit program where nearby
+/// surfaces hide a fragment from it, see [`Renderer::set_ssao`].
+struct Ssao {
+    params: SsaoParams,
+    /// Measures the occlusion of each pixel from the normals and depth.
+    occlusion_program: ShaderProgram,
+    /// Averages the occlusion over the 4x4 pixels the noise repeats over, within surfaces.
+    blur_program: ShaderProgram,
+    /// Random rotations of the kernel about the normal in its red and green, one per pixel in
+    /// 4x4 tiles, which the blur turns from banding into a smooth shade.
+    noise: Texture,
+    /// The occlusion and the blurred occlusion, sized to the viewport of the last draw.
+    targets: RefCell<Option<RenderTargets>>,
+    /// Bottom left corner of the viewport the occlusion was measured in for the draw under way,
+    /// or `None` outside of one.
+    origin: Cell<Option<Vec2>>,
+}
+
+impl Ssao {
+    fn new(gl: &TracedGl, params: SsaoParams) -> Result<Self, ModelLoadingError> {
+        let build = |name, fragment| {
+            ShaderProgram::from_sources(gl, FULL_SCREEN_VERTEX_SHADER_SOURCE, fragment)
+                .with_context(|| format!("failed to build the SSAO {name} program"))
+        };
+        let occlusion_program = build("occlusion", SSAO_FRAGMENT_SHADER_SOURCE)?;
+        let blur_program = build("blur", SSAO_BLUR_FRAGMENT_SHADER_SOURCE)
+            .inspect_err(|_| occlusion_program.delete(gl))?;
+        let noise = Texture::from_image(gl, &ssao_noise()).inspect_err(|_| {
+            occlusion_program.delete(gl);
+            blur_program.delete(gl);
+        })?;
+
+        let program = &occlusion_program;
+        program.set_uniform_i32(gl, "uDepth", 0);
+        program.set_uniform_i32(gl, "uNormals", 1);
+        program.set_uniform_i32(gl, "uNoise", 2);
+        for (index, sample) in ssao_kernel(params.samples).into_iter().enumerate() {
+            program.set_uniform_vec3(gl, &format!("uKernel[{index}]"), sample);
+        }
+        program.set_uniform_i32(gl, "uSampleCount", params.samples as i32);
+        program.set_uniform_f32(gl, "uRadius", params.radius);
+        program.set_uniform_f32(gl, "uBias", params.bias);
+        program.set_uniform_f32(gl, "uIntensity", params.intensity);
+        blur_program.set_uniform_i32(gl, "uOcclusion", 0);
+        blur_program.set_uniform_i32(gl, "uDepth", 1);
+        blur_program.set_uniform_f32(gl, "uRadius", params.radius);
+
+        Ok(Self {
+            params,
+            occlusion_program,
+            blur_program,
+            noise,
+            targets: RefCell::new(None),
+            origin: Cell::new(None),
+        })
+    }
+
+    fn delete(&self, gl: &TracedGl) {
+        self.occlusion_program.delete(gl);
+        self.blur_program.delete(gl);
+        self.noise.delete(gl);
+        if let Some(targets) = self.targets.borrow_mut().take() {
+            targets.delete(gl);
         }
     }
 }
 
-unsafe fn create_shader(
-    gl: &gl::Gl,
-    shader: gl::types::GLenum,
-    source: &[u8],
+/// Lines drawn over the scene where its depth or normals change sharply, see
+/// [`Renderer::set_edge_outline`].
+struct EdgeOutline {
+    style: EdgeStyle,
+    program: ShaderProgram,
+}
+
+impl EdgeOutline {
+    fn new(gl: &TracedGl, style: EdgeStyle) -> Result<Self, ModelLoadingError> {
+        let program = ShaderProgram::from_sources(
+            gl,
+            FULL_SCREEN_VERTEX_SHADER_SOURCE,
+            EDGE_OUTLINE_FRAGMENT_SHADER_SOURCE,
+        )
+        .context("failed to build the edge outline program")?;
+        program.set_uniform_i32(gl, "uDepth", 0);
+        program.set_uniform_i32(gl, "uNormals", 1);
+        program.set_uniform_f32(gl, "uDepthThreshold", style.depth_threshold);
+        program.set_uniform_f32(gl, "uNormalThreshold", style.normal_threshold);
+        program.set_uniform_vec3(gl, "uColor", style.color);
+        program.set_uniform_f32(gl, "uThickness", style.thickness);
+        Ok(Self { style, program })
+    }
+}
+
+/// Allocates a `width`x`height` texture of `internal_format` without mipmaps, read without
+/// filtering and clamped at the edges, to draw into.
+unsafe fn create_render_texture(
+    gl: &TracedGl,
+    internal_format: gl::types::GLenum,
+    (width, height): (i32, i32),
 ) -> gl::types::GLuint {
-    let shader = gl.CreateShader(shader);
-    gl.ShaderSource(
-        shader,
-        1,
-        [source.as_ptr().cast()].as_ptr(),
-        std::ptr::null(),
-    );
-    gl.CompileShader(shader);
-    shader
-}
-
-#[repr(C)]
-#[derive(Pod, Clone, Copy, Zeroable)]
-pub struct Vertex {
-    pub position: Vec2,
-    pub color: Vec3,
+    let parameters = [
+        (gl::TEXTURE_MIN_FILTER, gl::NEAREST),
+        (gl::TEXTURE_MAG_FILTER, gl::NEAREST),
+        (gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE),
+        (gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE),
+    ];
+    let mut texture = 0;
+    if gl.direct_state_access() {
+        gl.CreateTextures(gl::TEXTURE_2D, 1, &mut texture);
+        gl.TextureStorage2D(texture, 1, internal_format, width, height);
+        for (name, value) in parameters {
+            gl.TextureParameteri(texture, name, value as i32);
+        }
+    } else {
+        // Nothing is uploaded, but the format and type still have to suit the internal format.
+        let (format, kind) = match internal_format {
+            gl::DEPTH_COMPONENT24 => (gl::DEPTH_COMPONENT, gl::FLOAT),
+            _ => (gl::RGBA, gl::UNSIGNED_BYTE),
+        };
+        gl.GenTextures(1, &mut texture);
+        gl.BindTexture(gl::TEXTURE_2D, texture);
+        gl.TexImage2D(
+            gl::TEXTURE_2D,
+            0,
+            internal_format as i32,
+            width,
+            height,
+            0,
+            format,
+            kind,
+            std::ptr::null(),
+        );
+        for (name, value) in parameters {
+            gl.TexParameteri(gl::TEXTURE_2D, name, value as i32);
+        }
+        gl.BindTexture(gl::TEXTURE_2D, 0);
+    }
+    texture
 }
-impl Default for Vertex {
-    fn default() -> Self {
-        Self::zeroed()
+
+/// A framebuffer drawing into the `(attachment, texture)` pairs, and its completeness status.
+/// Without direct state access this leaves no framebuffer bound.
+unsafe fn create_texture_framebuffer(
+    gl: &TracedGl,
+    attachments: &[(gl::types::GLenum, gl::types::GLuint)],
+) -> (gl::types::GLuint, gl::types::GLenum) {
+    let mut framebuffer = 0;
+    if gl.direct_state_access() {
+        gl.CreateFramebuffers(1, &mut framebuffer);
+        for &(attachment, texture) in attachments {
+            gl.NamedFramebufferTexture(framebuffer, attachment, texture, 0);
+        }
+        let status = gl.CheckNamedFramebufferStatus(framebuffer, gl::FRAMEBUFFER);
+        (framebuffer, status)
+    } else {
+        gl.GenFramebuffers(1, &mut framebuffer);
+        gl.BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+        for &(attachment, texture) in attachments {
+            gl.FramebufferTexture2D(gl::FRAMEBUFFER, attachment, gl::TEXTURE_2D, texture, 0);
+        }
+        let status = gl.CheckFramebufferStatus(gl::FRAMEBUFFER);
+        gl.BindFramebuffer(gl::FRAMEBUFFER, 0);
+        (framebuffer, status)
     }
 }
 
-static VERTEX_DATA: [Vertex; 3] = [
-    Vertex {
-        position: vec2(-0.5, -0.5),
-        color: vec3(1.0, 0.0, 0.0),
-    },
-    Vertex {
-        position: vec2(0.0, 0.5),
-        color: vec3(0.0, 1.0, 0.0),
-    },
-    Vertex {
-        position: vec2(0.5, -0.5),
-        color: vec3(0.0, 0.0, 1.0),
-    },
-];
+/// Binds the 2D texture `texture` to texture unit `unit`.
+unsafe fn bind_texture_unit(gl: &TracedGl, unit: gl::types::GLuint, texture: gl::types::GLuint) {
+    if gl.direct_state_access() {
+        gl.BindTextureUnit(unit, texture);
+    } else {
+        gl.ActiveTexture(gl::TEXTURE0 + unit);
+        gl.BindTexture(gl::TEXTURE_2D, texture);
+    }
+}
 
-const VERTEX_SHADER_SOURCE: &[u8] = b"
-#version 100
-precision mediump float;
+/// Uniform numbers in `0..1` from a xorshift generator, the same for every run from `seed`, which
+/// must not be 0.
+fn random_floats(mut seed: u32) -> impl FnMut() -> f32 {
+    move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+        (seed >> 8) as f32 / (1 << 24) as f32
+    }
+}
+
+/// `samples` points in the unit hemisphere around +Z, closer to the center on the whole where
+/// occluders matter most.
+fn ssao_kernel(samples: u32) -> Vec<Vec3> {
+    let mut random = random_floats(0x9e37_79b9);
+    (0..samples)
+        .map(|index| {
+            let direction = vec3(random() * 2.0 - 1.0, random() * 2.0 - 1.0, random());
+            let scale = (index as f32 / samples as f32).powi(2);
+            direction.normalize_or(Vec3::Z) * random() * (0.1 + 0.9 * scale)
+        })
+        .collect()
+}
 
-attribute vec2 position;
-attribute vec3 color;
+/// 4x4 random directions in the XY plane, mapped from `-1..1` to the red and green channels.
+fn ssao_noise() -> RgbaImage {
+    let mut random = random_floats(0x85eb_ca6b);
+    RgbaImage::from_fn(4, 4, |_, _| {
+        let angle = random() * TAU;
+        let channel = |value: f32| ((value * 0.5 + 0.5) * 255.0).round() as u8;
+        image::Rgba([channel(angle.cos()), channel(angle.sin()), 0, 255])
+    })
+}
 
-varying vec3 v_color;
+/// A [`Renderer`]'s meshes and settings copied to the CPU by [`Renderer::snapshot`], holding no
+/// GL objects, so [`Renderer::from_snapshot`] can build it again once they're gone, e.g. after a
+/// suspend destroyed the context.
+#[derive(Debug, Clone)]
+pub struct RendererSnapshot {
+    meshes: Vec<MeshSnapshot>,
+    nodes: Vec<Node>,
+    /// Normal maps that were loaded or inserted, without the ones that failed to load.
+    normal_maps: Vec<(PathBuf, RgbaImage)>,
+    active_program: String,
+    toon_bands: u32,
+    normal_mapping: bool,
+    debug_view: DebugView,
+    model_matrix: Mat4,
+    view_matrix: Mat4,
+    projection: Projection,
+    viewport_size: (i32, i32),
+    lights: Vec<Light>,
+    clear_color: [GLfloat; 4],
+    clear_flags: ClearFlags,
+    base_color: Option<Vec3>,
+    framebuffer_srgb: bool,
+    scissor: Option<(i32, i32, i32, i32)>,
+    point_size: f32,
+    line_width: f32,
+    wireframe: bool,
+    wireframe_overlay: Option<Vec3>,
+    array: Option<InstanceArray>,
+    polygon_offset: Option<(f32, f32)>,
+    cull_mode: CullMode,
+    depth_test: bool,
+    depth_func: CompareFunc,
+    stencil_test: bool,
+    stencil_func: (CompareFunc, u8, u8),
+    stencil_op: [StencilOp; 3],
+    blend_mode: BlendMode,
+    auto_rotate_speed: Option<f32>,
+    /// Spin angle reached when the snapshot was taken.
+    auto_rotate_angle: f32,
+    /// Value of the `uTime` uniform when the snapshot was taken.
+    time: Duration,
+    show_grid: bool,
+    msaa_samples: u32,
+    shadow_resolution: Option<u32>,
+    ssao: Option<SsaoParams>,
+    edge_outline: Option<EdgeStyle>,
+    skybox: Option<[RgbaImage; 6]>,
+    #[cfg(feature = "net")]
+    http_timeout: Duration,
+}
 
-void main() {
-    gl_Position = vec4(position, 0.0, 1.0);
-    v_color = color;
+pub struct Renderer {
+    programs: ShaderLibrary,
+    /// Name of the program in `programs` that meshes are drawn with.
+    active_program: String,
+    scene: Scene,
+    /// Bound for meshes without a texture so the shader doesn't need a separate path.
+    white_texture: Texture,
+    /// Normal maps of the materials drawn so far, by path. `None` for ones that failed to load,
+    /// which are drawn without.
+    normal_maps: RefCell<HashMap<PathBuf, Option<Texture>>>,
+    /// Whether materials' normal maps are applied, e.g. off to compare with the plain surface.
+    normal_mapping: bool,
+    debug_view: DebugView,
+    /// Bands of the toon program, see [`Renderer::set_shading`].
+    toon_bands: u32,
+    /// Draws [`DebugView::Curvature`], built the first time it's used.
+    curvature_program: Option<ShaderProgram>,
+    /// Uniform buffer behind every program's `Matrices` block, see
+    /// [`Renderer::update_camera_ubo`].
+    matrices_buffer: gl::types::GLuint,
+    /// Transform applied to the whole scene, on top of each mesh's own `model_matrix`.
+    pub model_matrix: Mat4,
+    pub view_matrix: Mat4,
+    projection: Projection,
+    viewport_size: (i32, i32),
+    /// Lights shining on the scene, at most [`MAX_LIGHTS`].
+    lights: Vec<Light>,
+    /// RGBA color the framebuffer is cleared to before each frame.
+    clear_color: [GLfloat; 4],
+    clear_flags: ClearFlags,
+    /// Color every mesh is drawn in instead of its vertex colors, if set.
+    base_color: Option<Vec3>,
+    /// Whether `GL_FRAMEBUFFER_SRGB` is enabled.
+    framebuffer_srgb: bool,
+    /// `(x, y, width, height)` box clears and draws are confined to, from the bottom left.
+    scissor: Option<(i32, i32, i32, i32)>,
+    /// Pixel sizes of meshes drawn as points and lines, see [`PrimitiveMode`].
+    point_size: f32,
+    line_width: f32,
+    /// Draw triangle edges only, for looking at mesh topology.
+    wireframe: bool,
+    /// Color of the edges drawn over filled triangles, if they are.
+    wireframe_overlay: Option<Vec3>,
+    /// Copies every mesh is drawn as, if more than one.
+    array: Option<InstanceArray>,
+    /// Depth slope factor and units filled triangles are pushed back by.
+    polygon_offset: Option<(f32, f32)>,
+    cull_mode: CullMode,
+    depth_test: bool,
+    depth_func: CompareFunc,
+    stencil_test: bool,
+    /// Comparison, reference value and mask for the stencil test.
+    stencil_func: (CompareFunc, u8, u8),
+    /// Operations for failing the stencil test, failing the depth test and passing both.
+    stencil_op: [StencilOp; 3],
+    blend_mode: BlendMode,
+    /// Speed the scene spins about the Y axis at, in radians per second.
+    auto_rotate_speed: Option<f32>,
+    /// Spin angle reached at `auto_rotate_start`, kept when auto-rotation stops.
+    auto_rotate_angle: f32,
+    auto_rotate_start: Instant,
+    /// Zero point of the `uTime` uniform.
+    start_time: Instant,
+    grid: RefCell<Option<Grid>>,
+    /// Edges of a unit cube, created the first time a box is drawn with [`Renderer::draw_aabb`].
+    box_lines: RefCell<Option<Mesh>>,
+    billboard: RefCell<Option<Billboard>>,
+    /// `None` when the context can't time GPU work.
+    gpu_timer: RefCell<Option<GpuTimer>>,
+    show_grid: bool,
+    draw_stats: Cell<DrawStats>,
+    instance_buffer: RefCell<InstanceBuffer>,
+    /// Samples per pixel of `msaa_target`, or 0 to draw straight to the default framebuffer.
+    msaa_samples: u32,
+    msaa_target: Option<MsaaTarget>,
+    /// `None` while shadows are off, see [`Renderer::enable_shadows`].
+    shadow_map: Option<ShadowMap>,
+    skybox: Option<Skybox>,
+    /// `Some` while an effect reads it, see [`NormalDepthPass`].
+    normal_depth: Option<NormalDepthPass>,
+    /// `None` while SSAO is off, see [`Renderer::set_ssao`].
+    ssao: Option<Ssao>,
+    edge_outline: Option<EdgeOutline>,
+    /// Bound for the full-screen passes, whose vertices come from `gl_VertexID` alone.
+    full_screen_vertex_array: gl::types::GLuint,
+    /// How long each request of [`Renderer::load_obj_url`] and [`Renderer::load_gltf_url`] may
+    /// take.
+    #[cfg(feature = "net")]
+    http_timeout: Duration,
+    gl: TracedGl,
 }
-\0";
 
-const FRAGMENT_SHADER_SOURCE: &[u8] = b"
-#version 100
-precision mediump float;
+impl Renderer {
+    pub fn new<D: GlDisplay>(gl_display: &D) -> Result<Self, ModelLoadingError> {
+        let mut renderer = Self::empty(gl_display)?;
+        let mesh = Mesh::new(&renderer.gl, &VERTEX_DATA, &INDEX_DATA)?;
+        renderer.add_mesh(mesh);
+        Ok(renderer)
+    }
+
+    /// Creates a renderer drawing the meshes of the Wavefront OBJ file at `path` with their
+    /// materials, see [`Scene::load_obj`], centered and scaled to fit in a unit cube.
+    pub fn from_obj<D: GlDisplay>(gl_display: &D, path: &Path) -> Result<Self, ModelLoadingError> {
+        let mut renderer = Self::empty(gl_display)?;
+        let scene = Scene::load_obj(&renderer.gl, path)?;
+        renderer.add_scene(scene);
+        Ok(renderer)
+    }
 
-varying vec3 v_color;
+    /// Creates a renderer drawing the mesh in the PLY file at `path`, centered and scaled to fit
+    /// in a unit cube.
+    pub fn from_ply<D: GlDisplay>(gl_display: &D, path: &Path) -> Result<Self, ModelLoadingError> {
+        let mut renderer = Self::empty(gl_display)?;
+        let mesh = Mesh::from_ply(&renderer.gl, path)?;
+        renderer.add_scene(Scene {
+            meshes: vec![mesh],
+            ..Default::default()
+        });
+        Ok(renderer)
+    }
 
-void main() {
-    gl_FragColor = vec4(v_color, 1.0);
+    /// Creates a renderer drawing the mesh in the STL file at `path`, centered and scaled to fit
+    /// in a unit cube.
+    pub fn from_stl<D: GlDisplay>(gl_display: &D, path: &Path) -> Result<Self, ModelLoadingError> {
+        let mut renderer = Self::empty(gl_display)?;
+        let mesh = Mesh::from_stl(&renderer.gl, path)?;
+        renderer.add_scene(Scene {
+            meshes: vec![mesh],
+            ..Default::default()
+        });
+        Ok(renderer)
+    }
+
+    /// Creates a renderer drawing every OBJ file in `dir` side by side, see
+    /// [`Scene::load_dir`].
+    pub fn from_dir<D: GlDisplay>(gl_display: &D, dir: &Path) -> Result<Self, ModelLoadingError> {
+        let mut renderer = Self::empty(gl_display)?;
+        let scene = Scene::load_dir(&renderer.gl, dir)?;
+        renderer.add_scene(scene);
+        Ok(renderer)
+    }
+
+    /// Creates a renderer drawing every mesh in the `.gltf` or `.glb` file at `path`, centered
+    /// and scaled to fit in a unit cube.
+    pub fn from_gltf<D: GlDisplay>(gl_display: &D, path: &Path) -> Result<Self, ModelLoadingError> {
+        let mut renderer = Self::empty(gl_display)?;
+        let scene = Scene::load_gltf(&renderer.gl, path)?;
+        renderer.add_scene(scene);
+        Ok(renderer)
+    }
+
+    /// Creates a renderer drawing the model loaded by [`Scene::load_file`], centered and scaled to
+    /// fit in a unit cube.
+    pub fn from_file<D: GlDisplay>(gl_display: &D, path: &Path) -> Result<Self, ModelLoadingError> {
+        let mut renderer = Self::empty(gl_display)?;
+        let scene = Scene::load_file(&renderer.gl, path)?;
+        renderer.add_scene(scene);
+        Ok(renderer)
+    }
+
+    /// Builds the renderer `snapshot` was taken of again, with its meshes, lights, effects and
+    /// every other setting, uploading everything from the snapshot rather than loading it from
+    /// disk. Programs added with [`Renderer::add_program`] only exist on the GPU, so they aren't
+    /// in the snapshot. They have to be added again, and the lit program is used meanwhile if one
+    /// of them was active.
+    pub fn from_snapshot<D: GlDisplay>(
+        gl_display: &D,
+        snapshot: &RendererSnapshot,
+    ) -> Result<Self, ModelLoadingError> {
+        let mut renderer = Self::empty(gl_display)?;
+        for mesh in &snapshot.meshes {
+            let mesh = Mesh::from_snapshot(&renderer.gl, mesh)?;
+            renderer.add_mesh(mesh);
+        }
+        renderer.scene.nodes = snapshot.nodes.clone();
+        for (path, image) in &snapshot.normal_maps {
+            let texture = Texture::from_image(&renderer.gl, image)?;
+            renderer.insert_normal_map(path.clone(), texture);
+        }
+
+        renderer.set_shading(ShadingModel::Toon {
+            bands: snapshot.toon_bands,
+        })?;
+        if renderer.use_program(&snapshot.active_program).is_err() {
+            log::warn!(
+                "the {:?} program has to be added again, drawing with the lit one until then",
+                snapshot.active_program
+            );
+            renderer.set_shading(ShadingModel::Lit)?;
+        }
+        renderer.normal_mapping = snapshot.normal_mapping;
+        renderer.set_debug_view(snapshot.debug_view)?;
+
+        renderer.model_matrix = snapshot.model_matrix;
+        renderer.view_matrix = snapshot.view_matrix;
+        renderer.projection = snapshot.projection;
+        let (width, height) = snapshot.viewport_size;
+        renderer.resize(width, height);
+        renderer.lights = snapshot.lights.clone();
+        renderer.clear_color = snapshot.clear_color;
+        renderer.clear_flags = snapshot.clear_flags;
+        renderer.base_color = snapshot.base_color;
+        renderer.set_framebuffer_srgb(snapshot.framebuffer_srgb);
+        renderer.set_scissor(snapshot.scissor)?;
+        renderer.set_point_size(snapshot.point_size);
+        renderer.set_line_width(snapshot.line_width);
+        renderer.wireframe = snapshot.wireframe;
+        renderer.wireframe_overlay = snapshot.wireframe_overlay;
+        renderer.array = snapshot.array;
+        renderer.polygon_offset = snapshot.polygon_offset;
+        renderer.cull_mode = snapshot.cull_mode;
+        renderer.depth_test = snapshot.depth_test;
+        renderer.depth_func = snapshot.depth_func;
+        renderer.apply_depth_state();
+        renderer.stencil_test = snapshot.stencil_test;
+        renderer.stencil_func = snapshot.stencil_func;
+        renderer.stencil_op = snapshot.stencil_op;
+        renderer.apply_stencil_state();
+        renderer.blend_mode = snapshot.blend_mode;
+        renderer.auto_rotate_speed = snapshot.auto_rotate_speed;
+        renderer.auto_rotate_angle = snapshot.auto_rotate_angle;
+        // Carries on from the same time rather than jumping back to zero.
+        renderer.start_time = Instant::now()
+            .checked_sub(snapshot.time)
+            .unwrap_or_else(Instant::now);
+        renderer.show_grid = snapshot.show_grid;
+        #[cfg(feature = "net")]
+        {
+            renderer.http_timeout = snapshot.http_timeout;
+        }
+
+        renderer.set_msaa_samples(snapshot.msaa_samples);
+        if let Some(resolution) = snapshot.shadow_resolution {
+            renderer.enable_shadows(resolution)?;
+        }
+        renderer.set_ssao(snapshot.ssao)?;
+        renderer.set_edge_outline(snapshot.edge_outline)?;
+        if let Some(faces) = &snapshot.skybox {
+            let cube_map = CubeMap::from_images(&renderer.gl, faces)?;
+            renderer.install_skybox(cube_map)?;
+        }
+        Ok(renderer)
+    }
+
+    /// Copies the meshes and settings to the CPU, reading the vertices and textures back from
+    /// the GPU, for [`Renderer::from_snapshot`] to build the renderer again from.
+    pub fn snapshot(&self) -> RendererSnapshot {
+        RendererSnapshot {
+            meshes: self
+                .scene
+                .meshes
+                .iter()
+                .map(|mesh| mesh.snapshot(&self.gl))
+                .collect(),
+            nodes: self.scene.nodes.clone(),
+            normal_maps: self
+                .normal_maps
+                .borrow()
+                .iter()
+                .filter_map(|(path, texture)| {
+                    Some((path.clone(), texture.as_ref()?.read_back(&self.gl)))
+                })
+                .collect(),
+            active_program: self.active_program.clone(),
+            toon_bands: self.toon_bands,
+            normal_mapping: self.normal_mapping,
+            debug_view: self.debug_view,
+            model_matrix: self.model_matrix,
+            view_matrix: self.view_matrix,
+            projection: self.projection,
+            viewport_size: self.viewport_size,
+            lights: self.lights.clone(),
+            clear_color: self.clear_color,
+            clear_flags: self.clear_flags,
+            base_color: self.base_color,
+            framebuffer_srgb: self.framebuffer_srgb,
+            scissor: self.scissor,
+            point_size: self.point_size,
+            line_width: self.line_width,
+            wireframe: self.wireframe,
+            wireframe_overlay: self.wireframe_overlay,
+            array: self.array,
+            polygon_offset: self.polygon_offset,
+            cull_mode: self.cull_mode,
+            depth_test: self.depth_test,
+            depth_func: self.depth_func,
+            stencil_test: self.stencil_test,
+            stencil_func: self.stencil_func,
+            stencil_op: self.stencil_op,
+            blend_mode: self.blend_mode,
+            auto_rotate_speed: self.auto_rotate_speed,
+            auto_rotate_angle: self.current_auto_rotate_angle(),
+            time: self.start_time.elapsed(),
+            show_grid: self.show_grid,
+            msaa_samples: self.msaa_samples,
+            shadow_resolution: self.shadow_resolution(),
+            ssao: self.ssao(),
+            edge_outline: self.edge_outline(),
+            skybox: self
+                .skybox
+                .as_ref()
+                .map(|skybox| skybox.cube_map.read_back(&self.gl)),
+            #[cfg(feature = "net")]
+            http_timeout: self.http_timeout,
+        }
+    }
+
+    /// Replaces every mesh with the model loaded by [`Scene::load_file`], e.g. to pick up a fresh
+    /// export. If loading fails the current meshes are kept. Everything else, such as the camera,
+    /// lights and programs, stays as it was.
+    pub fn replace_scene_from_file(&mut self, path: &Path) -> Result<(), ModelLoadingError> {
+        let scene = Scene::load_file(&self.gl, path)?;
+        self.replace_scene(scene);
+        Ok(())
+    }
+
+    /// Replaces every mesh with the OBJ model at `url`, as [`Renderer::replace_scene_from_file`]
+    /// does, fetching its material libraries and textures relative to it. Blocks until every
+    /// request is done or has failed.
+    #[cfg(feature = "net")]
+    pub fn load_obj_url(&mut self, url: &str) -> Result<(), ModelLoadingError> {
+        let scene = crate::net::HttpLoader::new(self.http_timeout)?.load_obj(&self.gl, url)?;
+        self.replace_scene(scene);
+        Ok(())
+    }
+
+    /// Replaces every mesh with the `.gltf` or `.glb` model at `url`, as
+    /// [`Renderer::replace_scene_from_file`] does, fetching its buffers and images relative to it.
+    /// Blocks until every request is done or has failed.
+    #[cfg(feature = "net")]
+    pub fn load_gltf_url(&mut self, url: &str) -> Result<(), ModelLoadingError> {
+        let scene = crate::net::HttpLoader::new(self.http_timeout)?.load_gltf(&self.gl, url)?;
+        self.replace_scene(scene);
+        Ok(())
+    }
+
+    /// Sets how long each request made by [`Renderer::load_obj_url`] and
+    /// [`Renderer::load_gltf_url`] may take, from connecting to reading the last byte. 30 seconds
+    /// by default.
+    #[cfg(feature = "net")]
+    pub fn set_http_timeout(&mut self, timeout: Duration) {
+        self.http_timeout = timeout;
+    }
+
+    #[cfg(feature = "net")]
+    pub fn http_timeout(&self) -> Duration {
+        self.http_timeout
+    }
+
+    fn replace_scene(&mut self, scene: Scene) {
+        for mesh in &self.scene.meshes {
+            mesh.delete(&self.gl);
+        }
+        self.scene = Scene::default();
+        self.add_scene(scene);
+    }
+
+    /// Loaded models come in any size and position, so fit them to what the default camera sees.
+    fn add_scene(&mut self, mut scene: Scene) {
+        scene.normalize_to_unit_cube();
+        self.scene.append(scene);
+    }
+
+    fn empty<D: GlDisplay>(gl_display: &D) -> Result<Self, ModelLoadingError> {
+        let gl = TracedGl::new(load_gl_fn_ptrs(gl_display));
+        unsafe {
+            let mut programs = ShaderLibrary::new();
+            let built_in = [
+                (
+                    LIT_PROGRAM,
+                    VERTEX_SHADER_SOURCE,
+                    PHONG_FRAGMENT_SHADER_SOURCE,
+                ),
+                (
+                    TOON_PROGRAM,
+                    VERTEX_SHADER_SOURCE,
+                    PHONG_FRAGMENT_SHADER_SOURCE,
+                ),
+                (
+                    UNLIT_PROGRAM,
+                    UNLIT_VERTEX_SHADER_SOURCE,
+                    FRAGMENT_SHADER_SOURCE,
+                ),
+            ];
+            for (name, vertex_source, fragment_source) in built_in {
+                let program = ShaderProgram::from_sources(&gl, vertex_source, fragment_source)
+                    .with_context(|| format!("failed to build the built-in {name} program"))
+                    .inspect_err(|_| programs.delete(&gl))?;
+                // Sampler types can't share a unit, so the shadow map keeps its own even while
+                // shadows are off.
+                program.set_uniform_i32(&gl, "uShadowMap", SHADOW_MAP_TEXTURE_UNIT as i32);
+                programs.insert(name, program);
+            }
+            programs
+                .get(TOON_PROGRAM)
+                .expect("the toon program was built above")
+                .set_uniform_i32(&gl, "uToonBands", DEFAULT_TOON_BANDS as i32);
+
+            let white_texture =
+                Texture::solid(&gl, [255; 4]).inspect_err(|_| programs.delete(&gl))?;
+
+            let mut matrices_buffer = 0;
+            let size = size_of::<[Mat4; 3]>() as isize;
+            if gl.direct_state_access() {
+                gl.CreateBuffers(1, &mut matrices_buffer);
+                gl.NamedBufferData(matrices_buffer, size, std::ptr::null(), gl::DYNAMIC_DRAW);
+            } else {
+                gl.GenBuffers(1, &mut matrices_buffer);
+                gl.BindBuffer(gl::COPY_WRITE_BUFFER, matrices_buffer);
+                gl.BufferData(
+                    gl::COPY_WRITE_BUFFER,
+                    size,
+                    std::ptr::null(),
+                    gl::DYNAMIC_DRAW,
+                );
+                gl.BindBuffer(gl::COPY_WRITE_BUFFER, 0);
+            }
+            gl.BindBufferBase(gl::UNIFORM_BUFFER, MATRICES_BINDING, matrices_buffer);
+
+            gl.Enable(gl::DEPTH_TEST);
+
+            let mut viewport = [0; 4];
+            gl.GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+
+            let mut full_screen_vertex_array = 0;
+            if gl.direct_state_access() {
+                gl.CreateVertexArrays(1, &mut full_screen_vertex_array);
+            } else {
+                gl.GenVertexArrays(1, &mut full_screen_vertex_array);
+            }
+
+            let camera = CameraMode::default();
+
+            Ok(Self {
+                programs,
+                active_program: LIT_PROGRAM.to_owned(),
+                scene: Scene::default(),
+                white_texture,
+                normal_maps: RefCell::new(HashMap::new()),
+                normal_mapping: true,
+                debug_view: DebugView::None,
+                toon_bands: DEFAULT_TOON_BANDS,
+                curvature_program: None,
+                matrices_buffer,
+                model_matrix: Mat4::IDENTITY,
+                view_matrix: camera.view_matrix(),
+                projection: Projection::Perspective {
+                    fov: camera.fov(),
+                    near: DEFAULT_CLIP_PLANES.0,
+                    far: DEFAULT_CLIP_PLANES.1,
+                },
+                viewport_size: (viewport[2], viewport[3]),
+                lights: vec![Light::Directional {
+                    dir: DEFAULT_LIGHT_DIRECTION.normalize(),
+                    color: Vec3::ONE,
+                }],
+                clear_color: DEFAULT_CLEAR_COLOR,
+                clear_flags: ClearFlags::default(),
+                base_color: None,
+                framebuffer_srgb: false,
+                scissor: None,
+                point_size: 1.0,
+                line_width: 1.0,
+                wireframe: false,
+                wireframe_overlay: None,
+                array: None,
+                polygon_offset: None,
+                cull_mode: CullMode::None,
+                depth_test: true,
+                depth_func: CompareFunc::Less,
+                stencil_test: false,
+                stencil_func: (CompareFunc::Always, 0, u8::MAX),
+                stencil_op: [StencilOp::Keep; 3],
+                blend_mode: BlendMode::Opaque,
+                auto_rotate_speed: None,
+                auto_rotate_angle: 0.0,
+                auto_rotate_start: Instant::now(),
+                start_time: Instant::now(),
+                grid: RefCell::new(None),
+                box_lines: RefCell::new(None),
+                billboard: RefCell::new(None),
+                gpu_timer: RefCell::new(GpuTimer::new(&gl)),
+                show_grid: true,
+                draw_stats: Cell::new(DrawStats::default()),
+                instance_buffer: RefCell::default(),
+                msaa_samples: 0,
+                msaa_target: None,
+                shadow_map: None,
+                skybox: None,
+                normal_depth: None,
+                ssao: None,
+                edge_outline: None,
+                full_screen_vertex_array,
+                #[cfg(feature = "net")]
+                http_timeout: Duration::from_secs(30),
+                gl,
+            })
+        }
+    }
+
+    /// Clears the window and draws the scene, the same as [`Renderer::clear`] followed by
+    /// [`Renderer::draw_at`] with `model_matrix`.
+    pub fn draw(&self) {
+        let [red, green, blue, alpha] = self.clear_color;
+        self.draw_with_clear_color(red, green, blue, alpha)
+    }
+
+    /// Clears the window to the clear color, to start a frame of [`Renderer::draw_at`] and
+    /// [`Renderer::draw_mesh`] calls.
+    pub fn clear(&self) {
+        self.draw_to_window(|| {
+            self.clear_to(self.clear_color);
+            self.draw_skybox(self.view_matrix, self.projection, self.viewport_size);
+        });
+    }
+
+    /// Like [`Renderer::clear`], but clears to the given color, leaving the one set with
+    /// [`Renderer::set_clear_color`] as it is.
+    pub fn clear_with_color(&self, red: GLfloat, green: GLfloat, blue: GLfloat, alpha: GLfloat) {
+        self.draw_to_window(|| {
+            self.clear_to([red, green, blue, alpha]);
+            self.draw_skybox(self.view_matrix, self.projection, self.viewport_size);
+        });
+    }
+
+    /// Draws a single mesh over the last frame drawn, the way meshes in the scene are drawn. The
+    /// mesh doesn't have to be part of the scene, e.g. for a gizmo or a mesh being previewed.
+    pub fn draw_mesh(&self, mesh: &Mesh) {
+        let view_projection = self.projection.matrix(self.viewport_size) * self.view_matrix;
+        self.update_camera_ubo();
+        self.draw_to_window(|| {
+            let program = self.prepare_program(self.view_matrix);
+            let model = self.scene_matrix() * mesh.model_matrix;
+            self.prepare_mesh(program, mesh, view_projection, model);
+            self.set_blending(self.blends(mesh));
+            mesh.draw(&self.gl);
+            self.set_blending(false);
+        });
+    }
+
+    /// Draws `mesh` like [`Renderer::draw_mesh`] with an outline of `color` around it, on top of
+    /// everything else, e.g. to show it's selected. The outline is a copy of the mesh scaled up
+    /// by `thickness` (0.05 is 5% larger) about its bounds, drawn where the stencil buffer says
+    /// the mesh itself wasn't. The stencil buffer is cleared, and the stencil and depth settings
+    /// are restored afterwards.
+    pub fn draw_outlined(&self, mesh: &Mesh, color: Vec3, thickness: f32) {
+        let view_projection = self.projection.matrix(self.viewport_size) * self.view_matrix;
+        let model = self.scene_matrix() * mesh.model_matrix;
+        let center = mesh.bounds().map_or(Vec3::ZERO, |bounds| bounds.center());
+        let outline_model = model
+            * Mat4::from_translation(center)
+            * Mat4::from_scale(Vec3::splat(1.0 + thickness))
+            * Mat4::from_translation(-center);
+        self.update_camera_ubo();
+        self.draw_to_window(|| {
+            let program = self.prepare_program(self.view_matrix);
+            self.prepare_mesh(program, mesh, view_projection, model);
+            unsafe {
+                self.gl.Enable(gl::STENCIL_TEST);
+                self.gl.StencilMask(u8::MAX.into());
+                self.gl.Clear(gl::STENCIL_BUFFER_BIT);
+                self.gl.StencilFunc(gl::ALWAYS, 1, u8::MAX.into());
+                self.gl.StencilOp(gl::KEEP, gl::KEEP, gl::REPLACE);
+            }
+            self.set_blending(self.blends(mesh));
+            mesh.draw(&self.gl);
+            self.set_blending(false);
+
+            let unlit = self
+                .programs
+                .get(UNLIT_PROGRAM)
+                .expect("programs are never removed from the library");
+            unlit.set_uniform_mat4(&self.gl, "uMatrix", &(view_projection * outline_model));
+            unlit.set_uniform_mat4(&self.gl, "uModel", &outline_model);
+            unlit.set_uniform_f32(&self.gl, "uMaterial.alpha", 1.0);
+            unlit.set_uniform_bool(&self.gl, "uUseVertexColor", false);
+            unlit.set_uniform_vec3(&self.gl, "uBaseColor", color);
+            self.white_texture.bind(&self.gl, 0);
+            unsafe {
+                self.gl.UseProgram(unlit.id());
+                self.gl.StencilFunc(gl::NOTEQUAL, 1, u8::MAX.into());
+                self.gl.StencilMask(0);
+                self.gl.Disable(gl::DEPTH_TEST);
+            }
+            mesh.draw(&self.gl);
+            self.apply_stencil_state();
+            self.apply_depth_state();
+        });
+    }
+
+    /// Draws the scene with `model` in place of `model_matrix`, over the last frame drawn rather
+    /// than clearing first. Calling it once per position after [`Renderer::clear`] draws the scene
+    /// several times in one frame without touching `model_matrix`. [`Renderer::draw_stats`]
+    /// counts the last call only.
+    pub fn draw_at(&self, model: &Mat4) {
+        self.draw_to_window(|| {
+            let stats = self.draw_scene(
+                self.view_matrix,
+                self.projection,
+                self.viewport_size,
+                self.scene_matrix_for(model),
+            );
+            self.draw_stats.set(stats);
+        });
+    }
+
+    /// Like [`Renderer::draw`], but clears to the given color for this frame only, leaving the
+    /// one set with [`Renderer::set_clear_color`] as it is.
+    pub fn draw_with_clear_color(
+        &self,
+        red: GLfloat,
+        green: GLfloat,
+        blue: GLfloat,
+        alpha: GLfloat,
+    ) {
+        self.draw_to_window(|| {
+            self.draw_frame(
+                self.viewport_size,
+                [red, green, blue, alpha],
+                self.scene_matrix(),
+            )
+        });
+    }
+
+    /// Draws the scene twice side by side, through `left` in the left half of the window and
+    /// `right` in the right half. Each half gets its own aspect ratio, so neither is stretched.
+    /// The halves cover the whole window even with a box set by [`Renderer::set_scissor`].
+    pub fn draw_split(&self, left: &Camera, right: &Camera) {
+        let (width, height) = self.viewport_size;
+        let left_width = width / 2;
+        let scene_matrix = self.scene_matrix();
+        self.draw_to_window(|| unsafe {
+            self.gl.Disable(gl::SCISSOR_TEST);
+            // One clear covers both halves, so they can't leave gaps.
+            self.clear_to(self.clear_color);
+
+            self.gl.Viewport(0, 0, left_width, height);
+            let left_projection = self.projection.with_fov(left.fov);
+            self.draw_skybox(left.view_matrix(), left_projection, (left_width, height));
+            let left_stats = self.draw_scene(
+                left.view_matrix(),
+                left_projection,
+                (left_width, height),
+                scene_matrix,
+            );
+            self.gl.Viewport(left_width, 0, width - left_width, height);
+            let right_projection = self.projection.with_fov(right.fov);
+            let right_size = (width - left_width, height);
+            self.draw_skybox(right.view_matrix(), right_projection, right_size);
+            let right_stats = self.draw_scene(
+                right.view_matrix(),
+                right_projection,
+                right_size,
+                scene_matrix,
+            );
+            self.draw_stats.set(DrawStats {
+                drawn: left_stats.drawn + right_stats.drawn,
+                culled: left_stats.culled + right_stats.culled,
+                triangles: left_stats.triangles + right_stats.triangles,
+            });
+
+            self.gl.Viewport(0, 0, width, height);
+            if self.scissor.is_some() {
+                self.gl.Enable(gl::SCISSOR_TEST);
+            }
+        });
+    }
+
+    /// Draws a `size`x`size` grid of lines on the XZ plane through the origin, split into
+    /// `divisions` cells along each side, over the last frame drawn. It follows the camera but not
+    /// `model_matrix`, and does nothing while hidden with [`Renderer::set_grid_visible`].
+    pub fn draw_grid(&self, size: f32, divisions: u32) -> Result<(), ModelLoadingError> {
+        if !self.show_grid {
+            return Ok(());
+        }
+
+        let mut cache = self.grid.borrow_mut();
+        if let Some(old) = cache.take_if(|grid| grid.size != size || grid.divisions != divisions) {
+            old.mesh.delete(&self.gl);
+        }
+        let grid = match cache.as_ref() {
+            Some(grid) => grid,
+            None => {
+                let (vertices, indices) = grid_lines(size, divisions.max(1));
+                let mesh =
+                    Mesh::with_primitive_mode(&self.gl, &vertices, &indices, PrimitiveMode::Lines)?;
+                cache.insert(Grid {
+                    size,
+                    divisions,
+                    mesh,
+                })
+            }
+        };
+
+        let program = self
+            .programs
+            .get(UNLIT_PROGRAM)
+            .expect("programs are never removed from the library");
+        let view_projection = self.projection.matrix(self.viewport_size) * self.view_matrix;
+        self.update_camera_ubo();
+        program.set_uniform_mat4(&self.gl, "uMatrix", &view_projection);
+        program.set_uniform_mat4(&self.gl, "uModel", &Mat4::IDENTITY);
+        program.set_uniform_f32(&self.gl, "uMaterial.alpha", 1.0);
+        // The grid keeps its own color whatever the base color is set to.
+        program.set_uniform_bool(&self.gl, "uUseVertexColor", true);
+        let shadows = self.apply_shadow_uniforms(program);
+        program.set_uniform_bool(&self.gl, "uReceiveShadows", shadows);
+        self.draw_to_window(|| {
+            unsafe {
+                self.gl.UseProgram(program.id());
+            }
+            self.white_texture.bind(&self.gl, 0);
+            grid.mesh.draw(&self.gl);
+        });
+        // Nothing else drawn unlit is lit by anything to be shadowed from.
+        program.set_uniform_bool(&self.gl, "uReceiveShadows", false);
+        Ok(())
+    }
+
+    /// Draws the edges of the box from `min` to `max` in world space as lines of `color`, over the
+    /// last frame drawn, e.g. to check the bounds used for culling and picking.
+    pub fn draw_aabb(&self, min: Vec3, max: Vec3, color: Vec3) -> Result<(), ModelLoadingError> {
+        let mut cache = self.box_lines.borrow_mut();
+        let box_lines = match cache.as_ref() {
+            Some(mesh) => mesh,
+            None => {
+                let (vertices, indices) = box_edges();
+                let mesh =
+                    Mesh::with_primitive_mode(&self.gl, &vertices, &indices, PrimitiveMode::Lines)?;
+                cache.insert(mesh)
+            }
+        };
+
+        let program = self
+            .programs
+            .get(UNLIT_PROGRAM)
+            .expect("programs are never removed from the library");
+        let model = Mat4::from_translation(min) * Mat4::from_scale(max - min);
+        let view_projection = self.projection.matrix(self.viewport_size) * self.view_matrix;
+        self.update_camera_ubo();
+        program.set_uniform_mat4(&self.gl, "uMatrix", &(view_projection * model));
+        program.set_uniform_mat4(&self.gl, "uModel", &model);
+        program.set_uniform_f32(&self.gl, "uMaterial.alpha", 1.0);
+        program.set_uniform_bool(&self.gl, "uUseVertexColor", false);
+        program.set_uniform_vec3(&self.gl, "uBaseColor", color);
+        self.draw_to_window(|| {
+            unsafe {
+                self.gl.UseProgram(program.id());
+            }
+            self.white_texture.bind(&self.gl, 0);
+            box_lines.draw(&self.gl);
+        });
+        Ok(())
+    }
+
+    /// Draws `texture` on a `size` (width and height) rectangle centered on `position` in world
+    /// space that always faces the camera, over the last frame drawn, e.g. for labels and
+    /// markers. It's lined up with the camera's right and up directions, so it stays upright on
+    /// screen, and scales with distance under perspective like the scene does. The texture's
+    /// alpha blends it over what's behind, and it's hidden behind nearer geometry without hiding
+    /// anything itself.
+    pub fn draw_billboard(
+        &self,
+        position: Vec3,
+        size: Vec2,
+        texture: &Texture,
+    ) -> Result<(), ModelLoadingError> {
+        let mut cache = self.billboard.borrow_mut();
+        let billboard = match cache.as_ref() {
+            Some(billboard) => billboard,
+            None => {
+                let corner = |x: f32, y: f32| Vertex {
+                    position: vec3(x, y, 0.0),
+                    color: Vec3::ONE,
+                    normal: Vec3::Z,
+                    uv: Vec2::new(x + 0.5, 0.5 - y),
+                    ..Default::default()
+                };
+                let vertices = [
+                    corner(-0.5, -0.5),
+                    corner(0.5, -0.5),
+                    corner(0.5, 0.5),
+                    corner(-0.5, 0.5),
+                ];
+                let mesh = Mesh::new(&self.gl, &vertices, &[0, 1, 2, 0, 2, 3])?;
+                let program = ShaderProgram::from_sources(
+                    &self.gl,
+                    BILLBOARD_VERTEX_SHADER_SOURCE,
+                    BILLBOARD_FRAGMENT_SHADER_SOURCE,
+                )
+                .context("failed to build the billboard program")
+                .inspect_err(|_| mesh.delete(&self.gl))?;
+                cache.insert(Billboard { mesh, program })
+            }
+        };
+
+        let program = &billboard.program;
+        self.update_camera_ubo();
+        program.set_uniform_vec3(&self.gl, "uCenter", position);
+        program.set_uniform_vec3(&self.gl, "uSize", size.extend(0.0));
+        self.draw_to_window(|| {
+            texture.bind(&self.gl, 0);
+            unsafe {
+                self.gl.UseProgram(program.id());
+                self.gl.PolygonMode(gl::FRONT_AND_BACK, gl::FILL);
+                self.gl.Disable(gl::CULL_FACE);
+                self.gl.Enable(gl::BLEND);
+                self.gl.BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+                self.gl.DepthMask(gl::FALSE);
+            }
+            billboard.mesh.draw(&self.gl);
+            self.set_blending(false);
+            if self.cull_mode != CullMode::None {
+                unsafe { self.gl.Enable(gl::CULL_FACE) };
+            }
+        });
+        Ok(())
+    }
+
+    /// Draws the world space bounding box of every mesh that has one with
+    /// [`Renderer::draw_aabb`], as tested against the view frustum for culling.
+    pub fn draw_mesh_bounds(&self, color: Vec3) -> Result<(), ModelLoadingError> {
+        let scene_matrix = self.scene_matrix();
+        for mesh in &self.scene.meshes {
+            if let Some(bounds) = mesh.bounds() {
+                let bounds = bounds.transformed(&(scene_matrix * mesh.model_matrix));
+                self.draw_aabb(bounds.min, bounds.max, color)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Surrounds the scene with the six images in `faces`, in the order +X, -X, +Y, -Y, +Z, -Z
+    /// (the default camera faces -Z). The faces must all be the same square size. The skybox is
+    /// drawn right after every clear, so models appear inside it, and replaces any set before.
+    pub fn set_skybox(&mut self, faces: [PathBuf; 6]) -> Result<(), ModelLoadingError> {
+        let cube_map = CubeMap::from_files(&self.gl, &faces).context("failed to load skybox")?;
+        self.install_skybox(cube_map)
+    }
+
+    /// Draws `cube_map` as the skybox, building the skybox's program and cube the first time.
+    fn install_skybox(&mut self, cube_map: CubeMap) -> Result<(), ModelLoadingError> {
+        if let Some(skybox) = &mut self.skybox {
+            std::mem::replace(&mut skybox.cube_map, cube_map).delete(&self.gl);
+            return Ok(());
+        }
+
+        let program = ShaderProgram::from_sources(
+            &self.gl,
+            SKYBOX_VERTEX_SHADER_SOURCE,
+            SKYBOX_FRAGMENT_SHADER_SOURCE,
+        )
+        .context("failed to build the skybox program")
+        .inspect_err(|_| cube_map.delete(&self.gl))?;
+        let (vertices, indices) = skybox_cube();
+        let mesh = Mesh::new(&self.gl, &vertices, &indices).inspect_err(|_| {
+            cube_map.delete(&self.gl);
+            program.delete(&self.gl);
+        })?;
+        self.skybox = Some(Skybox {
+            cube_map,
+            mesh,
+            program,
+        });
+        Ok(())
+    }
+
+    /// Goes back to clearing to the clear color alone.
+    pub fn remove_skybox(&mut self) {
+        if let Some(skybox) = self.skybox.take() {
+            skybox.cube_map.delete(&self.gl);
+            skybox.mesh.delete(&self.gl);
+            skybox.program.delete(&self.gl);
+        }
+    }
+
+    /// Shows or hides the grid drawn by [`Renderer::draw_grid`]. It's shown by default.
+    pub fn set_grid_visible(&mut self, visible: bool) {
+        self.show_grid = visible;
+    }
+
+    pub fn grid_visible(&self) -> bool {
+        self.show_grid
+    }
+
+    /// Antialiases frames drawn to the window by rendering them into an offscreen framebuffer with
+    /// `samples` samples per pixel and resolving it to the window, regardless of how the window's
+    /// config was picked. 0 or 1 turn it off. Requests above `GL_MAX_SAMPLES` are capped to it.
+    ///
+    /// The resolve can't write to a multisampled default framebuffer, so the window should be
+    /// built without [`crate::window::GfWindowBuilder::msaa`] when using this.
+    pub fn set_msaa_samples(&mut self, samples: u32) {
+        let mut max_samples = 0;
+        unsafe { self.gl.GetIntegerv(gl::MAX_SAMPLES, &mut max_samples) };
+        let max_samples = max_samples.max(0) as u32;
+        if samples > max_samples {
+            log::warn!("{samples}x MSAA requested, but the driver supports at most {max_samples}x");
+        }
+        let samples = samples.min(max_samples);
+        if samples != self.msaa_samples {
+            self.msaa_samples = samples;
+            self.recreate_msaa_target();
+        }
+    }
+
+    /// Samples per pixel of the offscreen framebuffer frames are drawn into, or 0 without one.
+    pub fn msaa_samples(&self) -> u32 {
+        self.msaa_samples
+    }
+
+    /// Casts shadows from the first directional light set with [`Renderer::set_lights`], drawing
+    /// the depth of the scene as the light sees it into a `resolution`x`resolution` map before
+    /// every frame. The light is blocked where fragments are behind that depth, filtered over 3x3
+    /// texels to soften the edges, and the grid darkens where shadows fall on it. Transparent
+    /// meshes and lines don't cast shadows. Calling it again replaces the map. Off by default.
+    pub fn enable_shadows(&mut self, resolution: u32) -> Result<(), ModelLoadingError> {
+        let max_size = Capabilities::query(&self.gl).max_texture_size.max(1) as u32;
+        ensure!(
+            (1..=max_size).contains(&resolution),
+            InvalidArgument,
+            "shadow map resolution {resolution} is outside 1..={max_size}"
+        );
+        let shadow_map = ShadowMap::new(&self.gl, resolution)?;
+        self.disable_shadows();
+        self.shadow_map = Some(shadow_map);
+        Ok(())
+    }
+
+    /// Stops casting shadows and frees the shadow map.
+    pub fn disable_shadows(&mut self) {
+        if let Some(shadow_map) = self.shadow_map.take() {
+            shadow_map.delete(&self.gl);
+        }
+    }
+
+    /// Resolution of the shadow map along each side, or `None` while shadows are off.
+    pub fn shadow_resolution(&self) -> Option<u32> {
+        self.shadow_map
+            .as_ref()
+            .map(|shadow_map| shadow_map.resolution)
+    }
+
+    /// Turns screen-space ambient occlusion on with `params`, or off with `None`. Each frame the
+    /// view space normals and depth of the opaque triangles are drawn first. From them, the share
+    /// of a hemisphere of samples above each pixel that's hidden behind nearby surfaces is
+    /// measured, blurred without bleeding across depth edges, and scales the lit program's
+    /// ambient light, giving untextured models the creases and contact shadows flat ambient light
+    /// leaves out. Transparent meshes aren't darkened. Off by default.
+    pub fn set_ssao(&mut self, params: Option<SsaoParams>) -> Result<(), ModelLoadingError> {
+        let Some(params) = params else {
+            if let Some(ssao) = self.ssao.take() {
+                ssao.delete(&self.gl);
+            }
+            return self.update_normal_depth_pass();
+        };
+        ensure!(
+            (1..=MAX_SSAO_SAMPLES).contains(&params.samples),
+            InvalidArgument,
+            "{} SSAO samples is outside 1..={MAX_SSAO_SAMPLES}",
+            params.samples
+        );
+        ensure!(
+            params.radius > 0.0,
+            InvalidArgument,
+            "SSAO radius {} isn't positive",
+            params.radius
+        );
+        let ssao = Ssao::new(&self.gl, params)?;
+        if let Some(old) = self.ssao.replace(ssao) {
+            old.delete(&self.gl);
+        }
+        self.update_normal_depth_pass().inspect_err(|_| {
+            if let Some(ssao) = self.ssao.take() {
+                ssao.delete(&self.gl);
+            }
+        })
+    }
+
+    /// The settings SSAO is drawn with, or `None` while it's off.
+    pub fn ssao(&self) -> Option<SsaoParams> {
+        self.ssao.as_ref().map(|ssao| ssao.params)
+    }
+
+    /// Outlines silhouettes and creases in `style`, or stops with `None`. After the opaque meshes
+    /// are drawn, lines are blended over every pixel where the depth or the normals of the
+    /// surfaces around it change by more than the style's thresholds, read from a pass drawing
+    /// the view space normals and depth of the opaque triangles first. It needs nothing from the
+    /// meshes themselves, and pairs with [`ShadingModel::Toon`] for a cartoon look. Transparent
+    /// meshes aren't outlined. Off by default.
+    pub fn set_edge_outline(&mut self, style: Option<EdgeStyle>) -> Result<(), ModelLoadingError> {
+        let Some(style) = style else {
+            if let Some(outline) = self.edge_outline.take() {
+                outline.program.delete(&self.gl);
+            }
+            return self.update_normal_depth_pass();
+        };
+        ensure!(
+            style.depth_threshold >= 0.0 && style.normal_threshold >= 0.0,
+            InvalidArgument,
+            "edge outline thresholds {} and {} must not be negative",
+            style.depth_threshold,
+            style.normal_threshold
+        );
+        ensure!(
+            style.thickness >= 1.0,
+            InvalidArgument,
+            "edge outline thickness {} is less than a pixel",
+            style.thickness
+        );
+        let outline = EdgeOutline::new(&self.gl, style)?;
+        if let Some(old) = self.edge_outline.replace(outline) {
+            old.program.delete(&self.gl);
+        }
+        self.update_normal_depth_pass().inspect_err(|_| {
+            if let Some(outline) = self.edge_outline.take() {
+                outline.program.delete(&self.gl);
+            }
+        })
+    }
+
+    /// The style edges are outlined in, or `None` while they aren't.
+    pub fn edge_outline(&self) -> Option<EdgeStyle> {
+        self.edge_outline.as_ref().map(|outline| outline.style)
+    }
+
+    /// Creates the normal and depth pass when an effect reading it is on, and frees it when none
+    /// is.
+    fn update_normal_depth_pass(&mut self) -> Result<(), ModelLoadingError> {
+        let needed = self.ssao.is_some() || self.edge_outline.is_some();
+        if needed && self.normal_depth.is_none() {
+            self.normal_depth = Some(NormalDepthPass::new(&self.gl)?);
+        } else if !needed {
+            if let Some(pass) = self.normal_depth.take() {
+                pass.delete(&self.gl);
+            }
+        }
+        Ok(())
+    }
+
+    /// Replaces the multisampled framebuffer with one matching `msaa_samples` and the viewport.
+    fn recreate_msaa_target(&mut self) {
+        if let Some(target) = self.msaa_target.take() {
+            unsafe {
+                self.gl.DeleteFramebuffers(1, &target.framebuffer);
+                self.gl
+                    .DeleteRenderbuffers(2, target.renderbuffers.as_ptr());
+            }
+        }
+        if self.msaa_samples <= 1 {
+            return;
+        }
+
+        unsafe {
+            let (framebuffer, renderbuffers, status) =
+                self.create_offscreen_framebuffer(self.viewport_size, self.msaa_samples as i32);
+            if status != gl::FRAMEBUFFER_COMPLETE {
+                log::error!(
+                    "{}x MSAA framebuffer incomplete (status {status:#x}), drawing without it",
+                    self.msaa_samples
+                );
+                self.gl.DeleteFramebuffers(1, &framebuffer);
+                self.gl.DeleteRenderbuffers(2, renderbuffers.as_ptr());
+                self.msaa_samples = 0;
+                return;
+            }
+            self.msaa_target = Some(MsaaTarget {
+                framebuffer,
+                renderbuffers,
+            });
+        }
+    }
+
+    /// Runs `draw` with the framebuffer that ends up in the window bound, resolving the MSAA
+    /// framebuffer to the default one afterwards if there is one.
+    fn draw_to_window(&self, draw: impl FnOnce()) {
+        let Some(target) = &self.msaa_target else {
+            draw();
+            return;
+        };
+        let (width, height) = self.viewport_size;
+        unsafe {
+            self.gl.BindFramebuffer(gl::FRAMEBUFFER, target.framebuffer);
+            draw();
+            if self.gl.direct_state_access() {
+                self.gl.BlitNamedFramebuffer(
+                    target.framebuffer,
+                    0,
+                    0,
+                    0,
+                    width,
+                    height,
+                    0,
+                    0,
+                    width,
+                    height,
+                    gl::COLOR_BUFFER_BIT,
+                    gl::NEAREST,
+                );
+            } else {
+                self.gl
+                    .BindFramebuffer(gl::READ_FRAMEBUFFER, target.framebuffer);
+                self.gl.BindFramebuffer(gl::DRAW_FRAMEBUFFER, 0);
+                self.gl.BlitFramebuffer(
+                    0,
+                    0,
+                    width,
+                    height,
+                    0,
+                    0,
+                    width,
+                    height,
+                    gl::COLOR_BUFFER_BIT,
+                    gl::NEAREST,
+                );
+            }
+            self.gl.BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+    }
+
+    /// Draws the scene offscreen at `width`x`height` and reads it back, with the first row at the
+    /// top. The default framebuffer and viewport are left as they were.
+    pub fn render_to_image(&self, width: u32, height: u32) -> Result<RgbaImage, ModelLoadingError> {
+        self.with_offscreen_framebuffer(width, height, |size| {
+            self.draw_frame(size, self.clear_color, self.scene_matrix());
+            unsafe { self.read_pixels(width, height) }
+        })
+    }
+
+    /// Renders a full turn of the scene about the Y axis, `360 / frames` degrees apart, at the
+    /// window's size, and saves the frames to `out_dir` as `frame_0000.png` onwards, e.g. to be
+    /// put together into a video with ffmpeg. The camera stays where it is.
+    pub fn render_turntable(&self, frames: u32, out_dir: &Path) -> Result<(), ModelLoadingError> {
+        ensure!(
+            frames > 0,
+            InvalidArgument,
+            "a turntable needs at least one frame"
+        );
+        fs::create_dir_all(out_dir).map_err(ModelLoadingError::file_io(out_dir))?;
+        let (width, height) = (self.viewport_size.0.max(1), self.viewport_size.1.max(1));
+        let scene_matrix = self.scene_matrix();
+
+        self.with_offscreen_framebuffer(width as u32, height as u32, |size| {
+            for frame in 0..frames {
+                let angle = TAU * frame as f32 / frames as f32;
+                self.draw_frame(
+                    size,
+                    self.clear_color,
+                    Mat4::from_rotation_y(angle) * scene_matrix,
+                );
+                let image = unsafe { self.read_pixels(width as u32, height as u32) };
+                let path = out_dir.join(format!("frame_{frame:04}.png"));
+                image
+                    .save(&path)
+                    .map_err(ModelLoadingError::image_file(&path))?;
+            }
+            Ok(())
+        })?
+    }
+
+    /// Times `config.frames` frames of a flythrough circling the scene once, drawn offscreen so
+    /// nothing waits on the display. The camera sways above and below the scene and dips in
+    /// towards it, with the model matrix frozen without auto-rotation, so every run draws the same
+    /// frames. Each frame's GPU time is waited for at the end rather than read a frame or two
+    /// late. The camera and the window's contents stay as they were.
+    pub fn run_benchmark(&self, config: &BenchConfig) -> Result<BenchReport, ModelLoadingError> {
+        config.validate()?;
+        let bounds = self
+            .scene
+            .bounds()
+            .map(|bounds| bounds.transformed(&self.model_matrix));
+        let fov = Camera::default().fov;
+        let projection = self.projection.with_fov(fov);
+        let timed_gpu = self.gpu_timer.borrow().is_some();
+
+        self.with_offscreen_framebuffer(config.width, config.height, |size| unsafe {
+            let draw = |frame: u32| {
+                let view_matrix = flythrough_view(bounds, fov, frame as f32 / config.frames as f32);
+                self.clear_to(self.clear_color);
+                self.draw_skybox(view_matrix, projection, size);
+                self.draw_scene(view_matrix, projection, size, self.model_matrix)
+            };
+            for frame in 0..config.warmup_frames {
+                draw(frame % config.frames);
+            }
+            self.gl.Finish();
+
+            let mut queries = vec![0; if timed_gpu { config.frames as usize } else { 0 }];
+            if self.gl.direct_state_access() {
+                self.gl
+                    .CreateQueries(gl::TIME_ELAPSED, queries.len() as i32, queries.as_mut_ptr());
+            } else {
+                self.gl
+                    .GenQueries(queries.len() as i32, queries.as_mut_ptr());
+            }
+            let mut cpu_times = Vec::with_capacity(config.frames as usize);
+            let mut triangles = 0;
+            let start = Instant::now();
+            for frame in 0..config.frames {
+                let frame_start = Instant::now();
+                if let Some(&query) = queries.get(frame as usize) {
+                    self.gl.BeginQuery(gl::TIME_ELAPSED, query);
+                }
+                triangles += draw(frame).triangles as u64;
+                if !queries.is_empty() {
+                    self.gl.EndQuery(gl::TIME_ELAPSED);
+                }
+                cpu_times.push(frame_start.elapsed());
+            }
+            self.gl.Finish();
+            let seconds = start.elapsed().as_secs_f64();
+
+            let gpu_times: Vec<_> = queries
+                .iter()
+                .map(|&query| {
+                    let mut nanoseconds = 0;
+                    self.gl
+                        .GetQueryObjectui64v(query, gl::QUERY_RESULT, &mut nanoseconds);
+                    Duration::from_nanos(nanoseconds)
+                })
+                .collect();
+            self.gl
+                .DeleteQueries(queries.len() as i32, queries.as_ptr());
+
+            BenchReport {
+                frames: config.frames,
+                width: config.width,
+                height: config.height,
+                cpu: FrameTimeStats::from_times(&cpu_times).expect("there's at least one frame"),
+                gpu: FrameTimeStats::from_times(&gpu_times),
+                triangles,
+                triangles_per_second: triangles as f64 / seconds,
+            }
+        })
+    }
+
+    /// Runs `draw` with an offscreen framebuffer of `width`x`height` bound and the viewport set to
+    /// it, passing it the size. The default framebuffer and viewport are restored afterwards.
+    fn with_offscreen_framebuffer<R>(
+        &self,
+        width: u32,
+        height: u32,
+        draw: impl FnOnce((i32, i32)) -> R,
+    ) -> Result<R, ModelLoadingError> {
+        let size = (width as gl::types::GLsizei, height as gl::types::GLsizei);
+        unsafe {
+            let (framebuffer, renderbuffers, status) = self.create_offscreen_framebuffer(size, 0);
+            let result = (status == gl::FRAMEBUFFER_COMPLETE).then(|| {
+                self.gl.BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+                self.gl.Viewport(0, 0, size.0, size.1);
+                // The scissor box is in window coordinates, which don't apply offscreen.
+                self.gl.Disable(gl::SCISSOR_TEST);
+                let result = draw(size);
+
+                self.gl.BindFramebuffer(gl::FRAMEBUFFER, 0);
+                let (viewport_width, viewport_height) = self.viewport_size;
+                self.gl.Viewport(0, 0, viewport_width, viewport_height);
+                if self.scissor.is_some() {
+                    self.gl.Enable(gl::SCISSOR_TEST);
+                }
+                result
+            });
+
+            self.gl.DeleteFramebuffers(1, &framebuffer);
+            self.gl.DeleteRenderbuffers(2, renderbuffers.as_ptr());
+
+            result.ok_or(ModelLoadingError::GlError(status))
+        }
+    }
+
+    /// Saves the last frame drawn to the window as an image, e.g. a PNG, with the format picked
+    /// from the extension of `path`. Call it after [`Renderer::draw`] and before the buffers are
+    /// swapped, as the back buffer's contents are undefined afterwards.
+    pub fn capture_screenshot(&self, path: &Path) -> Result<(), ModelLoadingError> {
+        let (width, height) = self.viewport_size;
+        let image = unsafe {
+            self.gl.BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+            self.read_pixels(width.max(0) as u32, height.max(0) as u32)
+        };
+        image
+            .save(path)
+            .map_err(ModelLoadingError::image_file(path))
+    }
+
+    /// Reads the bottom left `width`x`height` pixels of the bound read framebuffer, top row first.
+    unsafe fn read_pixels(&self, width: u32, height: u32) -> RgbaImage {
+        let mut image = RgbaImage::new(width, height);
+        // Rows are tightly packed, which breaks the default 4 byte alignment for odd widths.
+        self.gl.PixelStorei(gl::PACK_ALIGNMENT, 1);
+        self.gl.ReadPixels(
+            0,
+            0,
+            width as gl::types::GLsizei,
+            height as gl::types::GLsizei,
+            gl::RGBA,
+            gl::UNSIGNED_BYTE,
+            image.as_mut_ptr().cast(),
+        );
+        // GL reads rows bottom up.
+        imageops::flip_vertical_in_place(&mut image);
+        image
+    }
+
+    /// Creates a framebuffer with RGBA8 color and 24 bit depth renderbuffers with `samples`
+    /// samples per pixel (0 for a single-sampled one), returning them with its completeness
+    /// status.
+    unsafe fn create_offscreen_framebuffer(
+        &self,
+        (width, height): (i32, i32),
+        samples: gl::types::GLsizei,
+    ) -> (gl::types::GLuint, [gl::types::GLuint; 2], gl::types::GLenum) {
+        let mut framebuffer = 0;
+        let mut renderbuffers = [0; 2];
+        let attachments = [
+            (gl::COLOR_ATTACHMENT0, gl::RGBA8),
+            (gl::DEPTH_STENCIL_ATTACHMENT, gl::DEPTH24_STENCIL8),
+        ];
+        if self.gl.direct_state_access() {
+            self.gl.CreateFramebuffers(1, &mut framebuffer);
+            self.gl.CreateRenderbuffers(2, renderbuffers.as_mut_ptr());
+            for (renderbuffer, (attachment, format)) in renderbuffers.into_iter().zip(attachments) {
+                self.gl.NamedRenderbufferStorageMultisample(
+                    renderbuffer,
+                    samples,
+                    format,
+                    width,
+                    height,
+                );
+                self.gl.NamedFramebufferRenderbuffer(
+                    framebuffer,
+                    attachment,
+                    gl::RENDERBUFFER,
+                    renderbuffer,
+                );
+            }
+            let status = self
+                .gl
+                .CheckNamedFramebufferStatus(framebuffer, gl::FRAMEBUFFER);
+            (framebuffer, renderbuffers, status)
+        } else {
+            self.gl.GenFramebuffers(1, &mut framebuffer);
+            self.gl.GenRenderbuffers(2, renderbuffers.as_mut_ptr());
+            self.gl.BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+            for (renderbuffer, (attachment, format)) in renderbuffers.into_iter().zip(attachments) {
+                self.gl.BindRenderbuffer(gl::RENDERBUFFER, renderbuffer);
+                self.gl.RenderbufferStorageMultisample(
+                    gl::RENDERBUFFER,
+                    samples,
+                    format,
+                    width,
+                    height,
+                );
+                self.gl.FramebufferRenderbuffer(
+                    gl::FRAMEBUFFER,
+                    attachment,
+                    gl::RENDERBUFFER,
+                    renderbuffer,
+                );
+            }
+            let status = self.gl.CheckFramebufferStatus(gl::FRAMEBUFFER);
+            self.gl.BindRenderbuffer(gl::RENDERBUFFER, 0);
+            self.gl.BindFramebuffer(gl::FRAMEBUFFER, 0);
+            (framebuffer, renderbuffers, status)
+        }
+    }
+
+    fn draw_frame(&self, size: (i32, i32), clear_color: [GLfloat; 4], scene_matrix: Mat4) {
+        self.clear_to(clear_color);
+        self.draw_skybox(self.view_matrix, self.projection, size);
+        let stats = self.draw_scene(self.view_matrix, self.projection, size, scene_matrix);
+        self.draw_stats.set(stats);
+    }
+
+    fn clear_to(&self, [red, green, blue, alpha]: [GLfloat; 4]) {
+        unsafe {
+            self.gl.ClearColor(red, green, blue, alpha);
+            self.gl.Clear(self.clear_flags.bits());
+        }
+    }
+
+    /// Draws the skybox, if any, behind everything a camera at `view_matrix` sees. Called right
+    /// after clearing, as nothing drawn before it would stay visible. It's left out while the
+    /// color buffer is kept, so it doesn't cover what's been drawn into it.
+    fn draw_skybox(&self, view_matrix: Mat4, projection: Projection, size: (i32, i32)) {
+        let Some(skybox) = &self.skybox else {
+            return;
+        };
+        if !self.clear_flags.contains(ClearFlags::COLOR) {
+            return;
+        }
+        // Only the camera's rotation applies, so the sky stays infinitely far away.
+        let rotation = Mat4::from_mat3(Mat3::from_mat4(view_matrix));
+        skybox.program.set_uniform_mat4(
+            &self.gl,
+            "uSkyMatrix",
+            &(projection.matrix(size) * rotation),
+        );
+        unsafe {
+            self.gl.UseProgram(skybox.program.id());
+            // Without a depth test nothing is written to the depth buffer either, so the scene
+            // draws over the sky wherever it is.
+            self.gl.Disable(gl::DEPTH_TEST);
+            self.gl.Disable(gl::CULL_FACE);
+            self.gl.PolygonMode(gl::FRONT_AND_BACK, gl::FILL);
+        }
+        skybox.cube_map.bind(&self.gl, 0);
+        skybox.mesh.draw(&self.gl);
+        // The scene's own cull and polygon modes are set before it's drawn.
+        self.apply_depth_state();
+    }
+
+    /// Draws every mesh in view of a camera at `view_matrix` into a viewport of `width`x`height`,
+    /// with `scene_matrix` applied before each mesh's own transform, without clearing first.
+    fn draw_scene(
+        &self,
+        view_matrix: Mat4,
+        projection: Projection,
+        size: (i32, i32),
+        scene_matrix: Mat4,
+    ) -> DrawStats {
+        let view_projection = projection.matrix(size) * view_matrix;
+        let frustum = Frustum::from_view_projection(&view_projection);
+
+        self.render_shadow_map(scene_matrix);
+        self.update_matrices(view_matrix, projection.matrix(size));
+        let normal_depth = self.render_normal_depth(scene_matrix);
+        if let Some(normal_depth) = normal_depth {
+            self.render_ssao(projection.matrix(size), normal_depth);
+        }
+        let program = self.prepare_program(view_matrix);
+        let mut stats = DrawStats::default();
+        let mut transparent = Vec::new();
+        let mut overlaid = Vec::new();
+        for mesh in &self.scene.meshes {
+            let model = scene_matrix * mesh.model_matrix;
+            if let Some(bounds) = mesh.bounds() {
+                if !frustum.intersects_aabb(&self.array_bounds(bounds.transformed(&model))) {
+                    stats.culled += 1;
+                    continue;
+                }
+            }
+            stats.drawn += 1;
+            stats.triangles += mesh.triangle_count() * self.copies();
+            if self.blends(mesh) {
+                transparent.push((mesh, model));
+                continue;
+            }
+            self.prepare_mesh(program, mesh, view_projection, model);
+            self.draw_copies(mesh, model);
+            if self.wireframe_overlay.is_some() {
+                overlaid.push((mesh, model));
+            }
+        }
+        if let Some(color) = self.wireframe_overlay {
+            self.draw_wireframe_overlay(&overlaid, view_projection, color);
+            self.prepare_program(view_matrix);
+        }
+        // Under the transparent meshes, which aren't outlined.
+        if let Some(normal_depth) = normal_depth.filter(|_| self.edge_outline.is_some()) {
+            self.draw_edge_outline(projection.matrix(size), normal_depth);
+            self.prepare_program(view_matrix);
+        }
+
+        if !transparent.is_empty() {
+            // Furthest first, so each mesh blends over everything behind it. Depth is negative in
+            // front of the camera, so that's the most negative.
+            let view_depth = |(mesh, model): &(&Mesh, Mat4)| {
+                let center = mesh.bounds().map_or(Vec3::ZERO, |bounds| bounds.center());
+                (view_matrix * *model).transform_point3(center).z
+            };
+            transparent.sort_by(|a, b| view_depth(a).total_cmp(&view_depth(b)));
+            // The occlusion is of the opaque surfaces behind them.
+            program.set_uniform_bool(&self.gl, "uUseOcclusion", false);
+            self.set_blending(true);
+            for (mesh, model) in transparent {
+                self.prepare_mesh(program, mesh, view_projection, model);
+                self.draw_copies(mesh, model);
+            }
+            self.set_blending(false);
+        }
+        if let Some(ssao) = &self.ssao {
+            ssao.origin.set(None);
+        }
+        stats
+    }
+
+    /// Index and direction of the light casting shadows, the first directional one.
+    fn shadow_light(&self) -> Option<(usize, Vec3)> {
+        self.lights
+            .iter()
+            .enumerate()
+            .find_map(|(index, light)| match light {
+                Light::Directional { dir, .. } => Some((index, dir.normalize_or_zero())),
+                Light::Point { .. } => None,
+            })
+            .filter(|(_, dir)| *dir != Vec3::ZERO)
+    }
+
+    /// Draws the depth of the scene's opaque triangles, moved by `scene_matrix`, from the shadow
+    /// light into the shadow map, if shadows are on. The framebuffer, viewport and scissor test are
+    /// restored afterwards, the rest of the raster state is left to [`Renderer::prepare_program`].
+    fn render_shadow_map(&self, scene_matrix: Mat4) {
+        let Some(shadow_map) = &self.shadow_map else {
+            return;
+        };
+        let bounds = self
+            .scene
+            .bounds()
+            .map(|bounds| self.array_bounds(bounds.transformed(&scene_matrix)));
+        let Some(((_, direction), bounds)) = self.shadow_light().zip(bounds) else {
+            shadow_map.light_matrix.set(None);
+            return;
+        };
+        let light_matrix = light_matrix(direction, bounds);
+        shadow_map.light_matrix.set(Some(light_matrix));
+
+        let program = &shadow_map.program;
+        program.set_uniform_mat4(&self.gl, "uLightMatrix", &light_matrix);
+        let size = shadow_map.resolution as gl::types::GLsizei;
+        unsafe {
+            let mut framebuffer = 0;
+            self.gl
+                .GetIntegerv(gl::DRAW_FRAMEBUFFER_BINDING, &mut framebuffer);
+            let mut viewport = [0; 4];
+            self.gl.GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+            let scissor = self.gl.IsEnabled(gl::SCISSOR_TEST) == gl::TRUE;
+
+            self.gl
+                .BindFramebuffer(gl::DRAW_FRAMEBUFFER, shadow_map.framebuffer);
+            self.gl.Viewport(0, 0, size, size);
+            self.gl.Disable(gl::SCISSOR_TEST);
+            self.gl.Enable(gl::DEPTH_TEST);
+            self.gl.DepthFunc(gl::LESS);
+            self.gl.DepthMask(gl::TRUE);
+            self.gl.Clear(gl::DEPTH_BUFFER_BIT);
+            self.gl.PolygonMode(gl::FRONT_AND_BACK, gl::FILL);
+            // Both faces, so open meshes cast shadows too.
+            self.gl.Disable(gl::CULL_FACE);
+            // Pushes the depth back along slopes, where the map's texels cover the most depth.
+            self.gl.Enable(gl::POLYGON_OFFSET_FILL);
+            self.gl.PolygonOffset(2.0, 4.0);
+            self.gl.UseProgram(program.id());
+            for mesh in &self.scene.meshes {
+                if mesh.primitive_mode() != PrimitiveMode::Triangles || self.blends(mesh) {
+                    continue;
+                }
+                let model = scene_matrix * mesh.model_matrix;
+                program.set_uniform_mat4(&self.gl, "uModel", &model);
+                self.draw_copies(mesh, model);
+            }
+
+            self.gl
+                .BindFramebuffer(gl::DRAW_FRAMEBUFFER, framebuffer as gl::types::GLuint);
+            let [x, y, width, height] = viewport;
+            self.gl.Viewport(x, y, width, height);
+            if scissor {
+                self.gl.Enable(gl::SCISSOR_TEST);
+            }
+        }
+    }
+
+    /// Sets the uniforms reading the shadow map on `program` and binds it, returning whether
+    /// there's a shadow to receive.
+    fn apply_shadow_uniforms(&self, program: &ShaderProgram) -> bool {
+        let light_matrix = self
+            .shadow_map
+            .as_ref()
+            .and_then(|shadow_map| Some((shadow_map, shadow_map.light_matrix.get()?)));
+        program.set_uniform_i32(&self.gl, "uShadowMap", SHADOW_MAP_TEXTURE_UNIT as i32);
+        let Some((shadow_map, light_matrix)) = light_matrix else {
+            return false;
+        };
+        program.set_uniform_mat4(&self.gl, "uLightMatrix", &light_matrix);
+        shadow_map.bind(&self.gl);
+        true
+    }
+
+    /// Draws the view space normals and depth of the scene's opaque triangles, moved by
+    /// `scene_matrix`, into textures the size of the viewport, if an effect reads them. Expects
+    /// the camera's matrices in the `Matrices` block. The framebuffers, viewport and scissor test
+    /// are restored afterwards, the rest of the raster state is left to
+    /// [`Renderer::prepare_program`].
+    fn render_normal_depth(&self, scene_matrix: Mat4) -> Option<NormalDepth> {
+        let pass = self.normal_depth.as_ref()?;
+        unsafe {
+            let target = DrawTarget::current(&self.gl);
+            let (width, height) = target.size();
+            let mut targets = pass.targets.borrow_mut();
+            let targets = RenderTargets::sized(
+                &mut targets,
+                &self.gl,
+                target.size(),
+                &[gl::DEPTH_COMPONENT24, gl::RGBA8],
+                &[&[(gl::DEPTH_ATTACHMENT, 0), (gl::COLOR_ATTACHMENT0, 1)]],
+            )
+            .inspect_err(|err| {
+                log::error!("failed to create {width}x{height} normal and depth targets: {err:#}")
+            })
+            .ok()?;
+
+            self.gl
+                .BindFramebuffer(gl::DRAW_FRAMEBUFFER, targets.framebuffers[0]);
+            self.gl.Viewport(0, 0, width, height);
+            self.gl.Disable(gl::SCISSOR_TEST);
+            self.gl.Enable(gl::DEPTH_TEST);
+            self.gl.DepthFunc(gl::LESS);
+            self.gl.DepthMask(gl::TRUE);
+            // Pixels nothing is drawn at are left with a zero normal.
+            self.gl.ClearBufferfv(gl::COLOR, 0, [0.0; 4].as_ptr());
+            self.gl.ClearBufferfv(gl::DEPTH, 0, &1.0);
+            self.gl.PolygonMode(gl::FRONT_AND_BACK, gl::FILL);
+            // Both faces, with the normals of back faces turned towards the camera, so the insides
+            // of open meshes are shaded too.
+            self.gl.Disable(gl::CULL_FACE);
+            self.gl.Disable(gl::POLYGON_OFFSET_FILL);
+            let program = &pass.program;
+            self.gl.UseProgram(program.id());
+            for mesh in &self.scene.meshes {
+                if mesh.primitive_mode() != PrimitiveMode::Triangles || self.blends(mesh) {
+                    continue;
+                }
+                let model = scene_matrix * mesh.model_matrix;
+                let normal_matrix = Mat3::from_mat4(model).inverse().transpose();
+                program.set_uniform_mat4(&self.gl, "uModel", &model);
+                program.set_uniform_mat3(&self.gl, "uNormalMatrix", &normal_matrix);
+                self.draw_copies(mesh, model);
+            }
+            target.restore(&self.gl);
+
+            let [x, y, ..] = target.viewport;
+            Some(NormalDepth {
+                depth: targets.textures[0],
+                normals: targets.textures[1],
+                size: targets.size,
+                origin: vec2(x as f32, y as f32),
+            })
+        }
+    }
+
+    /// Measures the occlusion of every pixel of `normal_depth` and blurs it into a texture bound
+    /// to [`OCCLUSION_TEXTURE_UNIT`], if SSAO is on. The camera's projection is `projection`. The
+    /// framebuffers, viewport and scissor test are restored afterwards, the rest of the raster
+    /// state is left to [`Renderer::prepare_program`].
+    fn render_ssao(&self, projection: Mat4, normal_depth: NormalDepth) {
+        let Some(ssao) = &self.ssao else {
+            return;
+        };
+        let (width, height) = normal_depth.size;
+        unsafe {
+            let target = DrawTarget::current(&self.gl);
+            let mut targets = ssao.targets.borrow_mut();
+            let Ok(targets) = RenderTargets::sized(
+                &mut targets,
+                &self.gl,
+                normal_depth.size,
+                &[gl::R8, gl::R8],
+                &[&[(gl::COLOR_ATTACHMENT0, 0)], &[(gl::COLOR_ATTACHMENT0, 1)]],
+            )
+            .inspect_err(|err| {
+                log::error!("failed to create {width}x{height} SSAO targets: {err:#}")
+            }) else {
+                return;
+            };
+
+            self.gl.Viewport(0, 0, width, height);
+            self.gl.Disable(gl::SCISSOR_TEST);
+            self.gl.Disable(gl::DEPTH_TEST);
+            self.gl.BindVertexArray(self.full_screen_vertex_array);
+
+            let program = &ssao.occlusion_program;
+            program.set_uniform_mat4(&self.gl, "uInverseProjection", &projection.inverse());
+            self.gl
+                .BindFramebuffer(gl::DRAW_FRAMEBUFFER, targets.framebuffers[0]);
+            bind_texture_unit(&self.gl, 0, normal_depth.depth);
+            bind_texture_unit(&self.gl, 1, normal_depth.normals);
+            ssao.noise.bind(&self.gl, 2);
+            self.gl.UseProgram(program.id());
+            self.gl.DrawArrays(gl::TRIANGLES, 0, 3);
+
+            let program = &ssao.blur_program;
+            program.set_uniform_mat4(&self.gl, "uInverseProjection", &projection.inverse());
+            self.gl
+                .BindFramebuffer(gl::DRAW_FRAMEBUFFER, targets.framebuffers[1]);
+            bind_texture_unit(&self.gl, 0, targets.textures[0]);
+            bind_texture_unit(&self.gl, 1, normal_depth.depth);
+            self.gl.UseProgram(program.id());
+            self.gl.DrawArrays(gl::TRIANGLES, 0, 3);
+            self.gl.BindVertexArray(0);
+
+            bind_texture_unit(&self.gl, OCCLUSION_TEXTURE_UNIT, targets.textures[1]);
+            target.restore(&self.gl);
+        }
+        ssao.origin.set(Some(normal_depth.origin));
+    }
+
+    /// Blends the lines of the edge outline, if there is one, over the viewport, from the normals
+    /// and depth in `normal_depth`. The camera's projection is `projection`. Leaves the raster
+    /// state and bound program to [`Renderer::prepare_program`].
+    fn draw_edge_outline(&self, projection: Mat4, normal_depth: NormalDepth) {
+        let Some(outline) = &self.edge_outline else {
+            return;
+        };
+        let program = &outline.program;
+        program.set_uniform_mat4(&self.gl, "uInverseProjection", &projection.inverse());
+        program.set_uniform_vec2(&self.gl, "uOrigin", normal_depth.origin);
+        unsafe {
+            self.gl.Disable(gl::DEPTH_TEST);
+            self.gl.Disable(gl::STENCIL_TEST);
+            self.gl.Disable(gl::CULL_FACE);
+            self.gl.PolygonMode(gl::FRONT_AND_BACK, gl::FILL);
+            self.gl.Enable(gl::BLEND);
+            self.gl.BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+            bind_texture_unit(&self.gl, 0, normal_depth.depth);
+            bind_texture_unit(&self.gl, 1, normal_depth.normals);
+            self.gl.UseProgram(program.id());
+            self.gl.BindVertexArray(self.full_screen_vertex_array);
+            self.gl.DrawArrays(gl::TRIANGLES, 0, 3);
+            self.gl.BindVertexArray(0);
+        }
+        self.set_blending(false);
+    }
+
+    /// Sets the uniforms reading the occlusion SSAO measured for the draw under way on `program`,
+    /// returning whether there is one.
+    fn apply_occlusion_uniforms(&self, program: &ShaderProgram) -> bool {
+        program.set_uniform_i32(&self.gl, "uOcclusion", OCCLUSION_TEXTURE_UNIT as i32);
+        let Some(origin) = self.ssao.as_ref().and_then(|ssao| ssao.origin.get()) else {
+            return false;
+        };
+        program.set_uniform_vec2(&self.gl, "uOcclusionOrigin", origin);
+        true
+    }
+
+    /// Draws the edges of `meshes` in `color` over their filled triangles, without polygon offset
+    /// so they win against the faces pushed back by it. Leaves the unlit program bound.
+    fn draw_wireframe_overlay(&self, meshes: &[(&Mesh, Mat4)], view_projection: Mat4, color: Vec3) {
+        let program = self
+            .programs
+            .get(UNLIT_PROGRAM)
+            .expect("programs are never removed from the library");
+        program.set_uniform_f32(&self.gl, "uMaterial.alpha", 1.0);
+        program.set_uniform_bool(&self.gl, "uUseVertexColor", false);
+        program.set_uniform_vec3(&self.gl, "uBaseColor", color);
+        self.white_texture.bind(&self.gl, 0);
+        unsafe {
+            self.gl.UseProgram(program.id());
+            self.gl.PolygonMode(gl::FRONT_AND_BACK, gl::LINE);
+            self.gl.Disable(gl::POLYGON_OFFSET_FILL);
+            self.gl.Disable(gl::POLYGON_OFFSET_LINE);
+            self.gl.DepthFunc(gl::LEQUAL);
+        }
+        for (mesh, model) in meshes {
+            program.set_uniform_mat4(&self.gl, "uMatrix", &(view_projection * *model));
+            program.set_uniform_mat4(&self.gl, "uModel", model);
+            self.draw_copies(mesh, *model);
+        }
+    }
+
+    /// Sets the per-frame uniforms and raster state, returning the bound active program.
+    fn prepare_program(&self, view_matrix: Mat4) -> &ShaderProgram {
+        let program = self.program();
+        let light_direction = self.lights.iter().find_map(|light| match light {
+            Light::Directional { dir, .. } => Some(dir.normalize_or_zero()),
+            Light::Point { .. } => None,
+        });
+        program.set_uniform_vec3(&self.gl, "uLightDir", light_direction.unwrap_or(Vec3::ZERO));
+        program.set_uniform_i32(&self.gl, "uLightCount", self.lights.len() as i32);
+        for (index, light) in self.lights.iter().enumerate() {
+            light.apply(&self.gl, program, index);
+        }
+        let view_position = view_matrix.inverse().w_axis.truncate();
+        program.set_uniform_vec3(&self.gl, "uViewPos", view_position);
+        program.set_uniform_f32(&self.gl, "uTime", self.start_time.elapsed().as_secs_f32());
+        program.set_uniform_bool(&self.gl, "uUseVertexColor", self.base_color.is_none());
+        program.set_uniform_vec3(&self.gl, "uBaseColor", self.base_color.unwrap_or(Vec3::ONE));
+        // Set even without a skybox, as samplers of different types can't share unit 0.
+        program.set_uniform_i32(&self.gl, "uEnvironment", ENVIRONMENT_TEXTURE_UNIT as i32);
+        program.set_uniform_bool(&self.gl, "uUseEnvironment", self.skybox.is_some());
+        program.set_uniform_i32(&self.gl, "uNormalMap", NORMAL_MAP_TEXTURE_UNIT as i32);
+        let shadows = self.apply_shadow_uniforms(program);
+        program.set_uniform_bool(&self.gl, "uUseShadows", shadows);
+        let shadow_light = self.shadow_light().map_or(-1, |(index, _)| index as i32);
+        program.set_uniform_i32(&self.gl, "uShadowLight", shadow_light);
+        let occlusion = self.apply_occlusion_uniforms(program);
+        program.set_uniform_bool(&self.gl, "uUseOcclusion", occlusion);
+        if let Some(skybox) = &self.skybox {
+            skybox.cube_map.bind(&self.gl, ENVIRONMENT_TEXTURE_UNIT);
+        }
+
+        unsafe {
+            self.gl.UseProgram(program.id());
+
+            let polygon_mode = if self.wireframe { gl::LINE } else { gl::FILL };
+            self.gl.PolygonMode(gl::FRONT_AND_BACK, polygon_mode);
+            match self.polygon_offset {
+                Some((factor, units)) => {
+                    self.gl.Enable(gl::POLYGON_OFFSET_FILL);
+                    self.gl.Enable(gl::POLYGON_OFFSET_LINE);
+                    self.gl.PolygonOffset(factor, units);
+                }
+                None => {
+                    self.gl.Disable(gl::POLYGON_OFFSET_FILL);
+                    self.gl.Disable(gl::POLYGON_OFFSET_LINE);
+                }
+            }
+
+            self.apply_depth_state();
+            self.apply_stencil_state();
+            self.gl.FrontFace(gl::CCW);
+            match self.cull_mode.gl_face() {
+                Some(face) => {
+                    self.gl.Enable(gl::CULL_FACE);
+                    self.gl.CullFace(face);
+                }
+                None => self.gl.Disable(gl::CULL_FACE),
+            }
+        }
+        program
+    }
+
+    /// Sets the uniforms and texture for drawing `mesh` with the `model` matrix.
+    fn prepare_mesh(
+        &self,
+        program: &ShaderProgram,
+        mesh: &Mesh,
+        view_projection: Mat4,
+        model: Mat4,
+    ) {
+        let normal_matrix = Mat3::from_mat4(model).inverse().transpose();
+        // The built-in programs read the view-projection matrix from the `Matrices` block instead.
+        if program.uniform_location("uMatrix") != -1 {
+            program.set_uniform_mat4(&self.gl, "uMatrix", &(view_projection * model));
+        }
+        program.set_uniform_mat4(&self.gl, "uModel", &model);
+        program.set_uniform_mat3(&self.gl, "uNormalMatrix", &normal_matrix);
+        program.set_uniform_bool(&self.gl, "uFlipV", mesh.flip_uv_v());
+        let curvature_range = mesh.curvature_range().unwrap_or(1.0);
+        program.set_uniform_f32(&self.gl, "uCurvatureRange", curvature_range);
+        mesh.material().apply(&self.gl, program);
+        if !self.blends(mesh) {
+            // Meshes drawn solid keep the framebuffer opaque, e.g. for screenshots.
+            program.set_uniform_f32(&self.gl, "uMaterial.alpha", 1.0);
+        }
+        mesh.texture()
+            .unwrap_or(&self.white_texture)
+            .bind(&self.gl, 0);
+        let normal_map = mesh.material().normal_map.as_deref();
+        let normal_mapped =
+            self.normal_mapping && normal_map.is_some_and(|path| self.bind_normal_map(path));
+        program.set_uniform_bool(&self.gl, "uUseNormalMap", normal_mapped);
+    }
+
+    /// Binds the normal map at `path` to [`NORMAL_MAP_TEXTURE_UNIT`], loading it first if it's
+    /// new. Returns false without binding anything if it can't be loaded.
+    fn bind_normal_map(&self, path: &Path) -> bool {
+        let mut normal_maps = self.normal_maps.borrow_mut();
+        let texture = normal_maps.entry(path.to_owned()).or_insert_with(|| {
+            Texture::from_file(&self.gl, path)
+                .inspect_err(|err| log::warn!("{err:#}, drawing without the normal map"))
+                .ok()
+        });
+        match texture {
+            Some(texture) => {
+                texture.bind(&self.gl, NORMAL_MAP_TEXTURE_UNIT);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Draws `mesh` once per matrix in `transforms` over the last frame, in a single draw call.
+    /// Each copy is drawn as if the matrix were applied before the mesh's own `model_matrix`.
+    pub fn draw_instanced(&self, mesh: &Mesh, transforms: &[Mat4]) {
+        if transforms.is_empty() {
+            return;
+        }
+        let buffer = self.upload_instances(transforms);
+        let view_projection = self.projection.matrix(self.viewport_size) * self.view_matrix;
+        self.update_camera_ubo();
+        self.draw_to_window(|| {
+            let program = self.prepare_program(self.view_matrix);
+            let model = self.scene_matrix() * mesh.model_matrix;
+            self.prepare_mesh(program, mesh, view_projection, model);
+            mesh.draw_instanced(&self.gl, buffer, transforms.len() as gl::types::GLsizei);
+        });
+    }
+
+    /// Draws `nx`x`ny`x`nz` copies of every mesh, `spacing` apart along the world X, Y and Z axes
+    /// starting from the original, like a CAD pattern. Each copy is a single instanced draw call.
+    /// Counts below 1 are raised to 1, and if there'd be more than 4096 copies in total the largest
+    /// count is lowered until there aren't, with a warning. 1x1x1, the default, draws every mesh
+    /// once.
+    pub fn set_array(&mut self, nx: u32, ny: u32, nz: u32, spacing: Vec3) {
+        let requested = [nx, ny, nz].map(|count| count.max(1));
+        let counts = InstanceArray::capped_counts(requested);
+        if counts != requested {
+            let requested: u64 = requested.iter().map(|&count| u64::from(count)).product();
+            let [nx, ny, nz] = counts;
+            log::warn!(
+                "{requested} copies requested, drawing {nx}x{ny}x{nz} as at most \
+                 {MAX_ARRAY_INSTANCES} are allowed"
+            );
+        }
+        self.array = (counts != [1; 3]).then_some(InstanceArray { counts, spacing });
+    }
+
+    /// Counts and spacing of the copies set with [`Renderer::set_array`], or `None` if every mesh
+    /// is drawn once.
+    pub fn array(&self) -> Option<([u32; 3], Vec3)> {
+        self.array.map(|array| (array.counts, array.spacing))
+    }
+
+    /// `bounds` of a mesh in world space, grown to take in all of its copies.
+    fn array_bounds(&self, bounds: Aabb) -> Aabb {
+        match &self.array {
+            Some(array) => {
+                bounds.union(&bounds.transformed(&Mat4::from_translation(array.extent())))
+            }
+            None => bounds,
+        }
+    }
+
+    /// Copies every mesh is drawn as, see [`Renderer::set_array`].
+    fn copies(&self) -> usize {
+        self.array.map_or(1, |array| {
+            array.counts.iter().map(|&count| count as usize).product()
+        })
+    }
+
+    /// Draws `mesh` with the bound program, as every copy of the array if one is set. `model` is
+    /// the matrix the program's `uModel` is set to.
+    fn draw_copies(&self, mesh: &Mesh, model: Mat4) {
+        let Some(array) = &self.array else {
+            mesh.draw(&self.gl);
+            return;
+        };
+        // Instance matrices are applied before `model`, so the offsets are moved back into the
+        // mesh's space, where they're still translations.
+        let to_mesh = Mat3::from_mat4(model).inverse();
+        let transforms: Vec<Mat4> = array
+            .offsets()
+            .map(|offset| Mat4::from_translation(to_mesh * offset))
+            .collect();
+        let buffer = self.upload_instances(&transforms);
+        mesh.draw_instanced(&self.gl, buffer, transforms.len() as gl::types::GLsizei);
+    }
+
+    /// Replaces the contents of the instance buffer with `transforms`, returning the buffer.
+    fn upload_instances(&self, transforms: &[Mat4]) -> gl::types::GLuint {
+        let data = cast_slice::<Mat4, u8>(transforms);
+        let mut instances = self.instance_buffer.borrow_mut();
+        unsafe {
+            if instances.buffer == 0 {
+                if self.gl.direct_state_access() {
+                    self.gl.CreateBuffers(1, &mut instances.buffer);
+                } else {
+                    self.gl.GenBuffers(1, &mut instances.buffer);
+                }
+            }
+            // Reallocating every call lets the driver hand over a fresh buffer instead of
+            // waiting for the previous draw to finish reading the old one.
+            let size = data.len() as isize;
+            if self.gl.direct_state_access() {
+                self.gl.NamedBufferData(
+                    instances.buffer,
+                    size,
+                    data.as_ptr().cast(),
+                    gl::STREAM_DRAW,
+                );
+            } else {
+                self.gl.BindBuffer(gl::COPY_WRITE_BUFFER, instances.buffer);
+                self.gl.BufferData(
+                    gl::COPY_WRITE_BUFFER,
+                    size,
+                    data.as_ptr().cast(),
+                    gl::STREAM_DRAW,
+                );
+                self.gl.BindBuffer(gl::COPY_WRITE_BUFFER, 0);
+            }
+        }
+        instances.buffer
+    }
+
+    /// Writes the camera's view, projection and view-projection matrices to the uniform buffer
+    /// behind every program's `Matrices` block. Drawing keeps it up to date, so this is only
+    /// needed before drawing with programs of your own outside the renderer.
+    pub fn update_camera_ubo(&self) {
+        self.update_matrices(self.view_matrix, self.projection.matrix(self.viewport_size));
+    }
+
+    fn update_matrices(&self, view: Mat4, projection: Mat4) {
+        let matrices = [view, projection, projection * view];
+        let data = cast_slice::<Mat4, u8>(&matrices);
+        unsafe {
+            if self.gl.direct_state_access() {
+                self.gl.NamedBufferSubData(
+                    self.matrices_buffer,
+                    0,
+                    data.len() as isize,
+                    data.as_ptr().cast(),
+                );
+            } else {
+                self.gl
+                    .BindBuffer(gl::COPY_WRITE_BUFFER, self.matrices_buffer);
+                self.gl.BufferSubData(
+                    gl::COPY_WRITE_BUFFER,
+                    0,
+                    data.len() as isize,
+                    data.as_ptr().cast(),
+                );
+                self.gl.BindBuffer(gl::COPY_WRITE_BUFFER, 0);
+            }
+        }
+    }
+
+    /// `model_matrix` with the auto-rotation applied, which every mesh is drawn with.
+    fn scene_matrix(&self) -> Mat4 {
+        self.scene_matrix_for(&self.model_matrix)
+    }
+
+    /// `model` with the auto-rotation applied.
+    fn scene_matrix_for(&self, model: &Mat4) -> Mat4 {
+        Mat4::from_rotation_y(self.current_auto_rotate_angle()) * *model
+    }
+
+    /// World space ray from the camera through the pixel at `(x, y)`, measured from the top left
+    /// of the viewport. Returns its origin on the near plane and its unit direction.
+    pub fn cursor_ray(&self, x: f32, y: f32) -> (Vec3, Vec3) {
+        let (width, height) = self.viewport_size;
+        let ndc_x = 2.0 * x / width.max(1) as f32 - 1.0;
+        let ndc_y = 1.0 - 2.0 * y / height.max(1) as f32;
+        let inverse_view_projection =
+            (self.projection.matrix(self.viewport_size) * self.view_matrix).inverse();
+        let near = inverse_view_projection.project_point3(vec3(ndc_x, ndc_y, -1.0));
+        let far = inverse_view_projection.project_point3(vec3(ndc_x, ndc_y, 1.0));
+        (near, (far - near).normalize())
+    }
+
+    /// The mesh under the pixel at `(x, y)`, measured from the top left of the viewport, as drawn
+    /// by the last frame. The id indexes [`Renderer::meshes`].
+    pub fn pick(&self, x: f32, y: f32) -> Option<MeshId> {
+        let (origin, dir) = self.cursor_ray(x, y);
+        let scene_matrix = self.scene_matrix();
+        pick_closest(
+            self.scene
+                .meshes
+                .iter()
+                .map(|mesh| (mesh, scene_matrix * mesh.model_matrix)),
+            origin,
+            dir,
+        )
+    }
+
+    /// Meshes drawn and culled by the last frame.
+    pub fn draw_stats(&self) -> DrawStats {
+        self.draw_stats.get()
+    }
+
+    /// Registers a shader program that can be switched to with [`Renderer::use_program`],
+    /// replacing (and deleting) any program already registered as `name`. The built-in programs
+    /// are `"lit"`, `"unlit"` and `"toon"`, see [`Renderer::set_shading`].
+    ///
+    /// The program can declare any of the uniforms the renderer sets: `uMatrix`, `uModel`,
+    /// `uNormalMatrix`, `uLights`, `uLightCount`, `uLightDir`, the first directional light's
+    /// direction, `uViewPos`, `uMaterial`, `uTexture`, `uBaseColor`,
+    /// `uUseVertexColor` (see [`Renderer::set_base_color`]), `uFlipV` (see
+    /// [`Mesh::set_flip_uv_v`]), `uEnvironment`, the skybox's
+    /// `samplerCube`, with `uUseEnvironment` telling whether there is one, and `uTime`, the
+    /// seconds since the renderer was created or [`Renderer::reset_time`]. It can also declare
+    /// `layout(std140) uniform Matrices { mat4 uView; mat4 uProjection; mat4 uViewProjection; };`
+    /// to read the camera from the buffer shared by every program, see
+    /// [`Renderer::update_camera_ubo`].
+    pub fn add_program(&mut self, name: &str, program: ShaderProgram) {
+        if let Some(old) = self.programs.insert(name, program) {
+            old.delete(&self.gl);
+        }
+    }
+
+    /// Draws meshes with the program registered as `name`. Uniforms are looked up by name on the
+    /// new program, so it only needs to declare the ones it uses.
+    pub fn use_program(&mut self, name: &str) -> Result<(), ModelLoadingError> {
+        if self.programs.get(name).is_none() {
+            bail!(InvalidArgument, "no shader program named {name:?}");
+        }
+        self.active_program = name.to_owned();
+        Ok(())
+    }
+
+    pub fn active_program(&self) -> &str {
+        &self.active_program
+    }
+
+    /// Draws meshes with the built-in program for `model`, with [`ShadingModel::Lit`] the
+    /// default. The toon program is the lit one with an `int uToonBands` uniform set to the
+    /// number of bands, which is 0, smooth shading, in the lit program itself.
+    pub fn set_shading(&mut self, model: ShadingModel) -> Result<(), ModelLoadingError> {
+        let name = match model {
+            ShadingModel::Lit => LIT_PROGRAM,
+            ShadingModel::Unlit => UNLIT_PROGRAM,
+            ShadingModel::Toon { bands } => {
+                ensure!(
+                    bands > 0,
+                    InvalidArgument,
+                    "toon shading needs at least one band"
+                );
+                let program = self
+                    .programs
+                    .get(TOON_PROGRAM)
+                    .expect("programs are never removed from the library");
+                program.set_uniform_i32(&self.gl, "uToonBands", bands as i32);
+                self.toon_bands = bands;
+                TOON_PROGRAM
+            }
+        };
+        self.use_program(name)
+    }
+
+    /// The shading model of the active program, or `None` while it isn't a built-in one.
+    pub fn shading(&self) -> Option<ShadingModel> {
+        match self.active_program.as_str() {
+            LIT_PROGRAM => Some(ShadingModel::Lit),
+            UNLIT_PROGRAM => Some(ShadingModel::Unlit),
+            TOON_PROGRAM => Some(ShadingModel::Toon {
+                bands: self.toon_bands,
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn programs(&self) -> &ShaderLibrary {
+        &self.programs
+    }
+
+    fn program(&self) -> &ShaderProgram {
+        if let (DebugView::Curvature, Some(program)) = (self.debug_view, &self.curvature_program) {
+            return program;
+        }
+        self.programs
+            .get(&self.active_program)
+            .expect("programs are never removed from the library")
+    }
+
+    /// Adds a mesh to be drawn every frame. The renderer takes care of deleting it.
+    pub fn add_mesh(&mut self, mesh: Mesh) {
+        self.scene.meshes.push(mesh);
+    }
+
+    pub fn meshes(&self) -> &[Mesh] {
+        &self.scene.meshes
+    }
+
+    pub fn meshes_mut(&mut self) -> &mut [Mesh] {
+        &mut self.scene.meshes
+    }
+
+    /// The meshes drawn and any node hierarchy positioning them.
+    pub fn scene(&self) -> &Scene {
+        &self.scene
+    }
+
+    /// Call [`Scene::update_world_transforms`] after moving nodes for the meshes to follow.
+    pub fn scene_mut(&mut self) -> &mut Scene {
+        &mut self.scene
+    }
+
+    /// Batches the static meshes with [`Scene::merge_static`], returning how many draw calls
+    /// that saves.
+    pub fn merge_static_meshes(
+        &mut self,
+        is_animated: impl Fn(MeshId, &Mesh) -> bool,
+    ) -> Result<usize, ModelLoadingError> {
+        self.scene.merge_static(&self.gl, is_animated)
+    }
+
+    pub fn resize(&mut self, width: i32, height: i32) {
+        self.viewport_size = (width, height);
+        unsafe {
+            self.gl.Viewport(0, 0, width, height);
+        }
+        if self.msaa_target.is_some() {
+            self.recreate_msaa_target();
+        }
+    }
+
+    /// Sets the transform applied to the whole scene, replacing `model_matrix`.
+    pub fn set_transform(&mut self, transform: &Transform) {
+        self.model_matrix = transform.matrix();
+    }
+
+    /// Lights the scene with a single white directional light travelling in `dir`, replacing any
+    /// lights set before. It doesn't need to be normalized.
+    pub fn set_light_direction(&mut self, dir: Vec3) {
+        self.set_lights(&[Light::Directional {
+            dir,
+            color: Vec3::ONE,
+        }]);
+    }
+
+    /// Replaces the lights shining on the scene. Only the first [`MAX_LIGHTS`] are used. With no
+    /// lights, meshes are lit by their material's ambient term alone.
+    pub fn set_lights(&mut self, lights: &[Light]) {
+        if lights.len() > MAX_LIGHTS {
+            log::warn!(
+                "{} lights given, only the first {MAX_LIGHTS} are used",
+                lights.len()
+            );
+        }
+        self.lights = lights[..lights.len().min(MAX_LIGHTS)].to_vec();
+    }
+
+    pub fn lights(&self) -> &[Light] {
+        &self.lights
+    }
+
+    /// Spins the scene about the Y axis at `speed_deg_per_sec`, or stops it where it is with
+    /// `None`. The angle follows elapsed time, so the speed doesn't depend on the frame rate.
+    pub fn set_auto_rotate(&mut self, speed_deg_per_sec: Option<f32>) {
+        self.auto_rotate_angle = self.current_auto_rotate_angle();
+        self.auto_rotate_start = Instant::now();
+        self.auto_rotate_speed = speed_deg_per_sec.map(f32::to_radians);
+    }
+
+    fn current_auto_rotate_angle(&self) -> f32 {
+        let elapsed = self.auto_rotate_start.elapsed().as_secs_f32();
+        // Wrapped so the stored angle stays small across repeated speed changes.
+        (self.auto_rotate_angle + self.auto_rotate_speed.unwrap_or(0.0) * elapsed) % TAU
+    }
+
+    /// Restarts the clock behind the `uTime` uniform from zero.
+    pub fn reset_time(&mut self) {
+        self.start_time = Instant::now();
+    }
+
+    /// Sets the color [`Renderer::draw`] clears the frame to. It's a dark gray by default.
+    pub fn set_clear_color(&mut self, red: GLfloat, green: GLfloat, blue: GLfloat, alpha: GLfloat) {
+        self.clear_color = [red, green, blue, alpha];
+    }
+
+    pub fn clear_color(&self) -> [GLfloat; 4] {
+        self.clear_color
+    }
+
+    /// Picks the buffers cleared at the start of each frame, color and depth by default. Keeping
+    /// the color buffer lets frames accumulate into it, e.g. for motion trails.
+    pub fn set_clear_flags(&mut self, flags: ClearFlags) {
+        self.clear_flags = flags;
+    }
+
+    pub fn clear_flags(&self) -> ClearFlags {
+        self.clear_flags
+    }
+
+    /// Sets the distances to the near and far clipping planes, 0.1 and 100 by default. Keeping
+    /// `far / near` small spreads the depth buffer's precision over less distance, so tiny models
+    /// want a closer near plane and huge scenes a further far plane.
+    pub fn set_clip_planes(&mut self, near: f32, far: f32) -> Result<(), ModelLoadingError> {
+        self.set_projection(match self.projection {
+            Projection::Perspective { fov, .. } => Projection::Perspective { fov, near, far },
+            Projection::Orthographic { height, .. } => {
+                Projection::Orthographic { height, near, far }
+            }
+        })
+    }
+
+    pub fn clip_planes(&self) -> (f32, f32) {
+        self.projection.clip_planes()
+    }
+
+    /// Switches between perspective and orthographic projection. A perspective projection by
+    /// default, which [`Renderer::set_camera`] keeps the field of view of up to date.
+    pub fn set_projection(&mut self, projection: Projection) -> Result<(), ModelLoadingError> {
+        let (near, far) = projection.clip_planes();
+        ensure!(
+            near > 0.0,
+            InvalidArgument,
+            "near plane distance {near} must be positive"
+        );
+        ensure!(
+            far > near,
+            InvalidArgument,
+            "far plane distance {far} must be beyond the near plane {near}"
+        );
+        match projection {
+            Projection::Perspective { fov, .. } => {
+                ensure!(
+                    fov > 0.0,
+                    InvalidArgument,
+                    "field of view {fov} must be positive"
+                )
+            }
+            Projection::Orthographic { height, .. } => {
+                ensure!(
+                    height > 0.0,
+                    InvalidArgument,
+                    "orthographic height {height} must be positive"
+                )
+            }
+        }
+        self.projection = projection;
+        Ok(())
+    }
+
+    pub fn projection(&self) -> Projection {
+        self.projection
+    }
+
+    /// Draws every mesh in `color` instead of its vertex colors, e.g. for models whose colors are
+    /// missing or misleading, or goes back to the vertex colors with `None`.
+    pub fn set_base_color(&mut self, color: Option<Vec3>) {
+        self.base_color = color;
+    }
+
+    pub fn base_color(&self) -> Option<Vec3> {
+        self.base_color
+    }
+
+    /// Has the GPU convert the shaders' linear colors to sRGB when writing them to an sRGB
+    /// framebuffer, so they look the same on every platform. Without it colors are written as
+    /// is, which an sRGB surface displays too dark. Has no effect on framebuffers that aren't
+    /// sRGB. Off by default; [`crate::window::GfWindowBuilder::srgb`] turns it on.
+    pub fn set_framebuffer_srgb(&mut self, enabled: bool) {
+        self.framebuffer_srgb = enabled;
+        unsafe {
+            if enabled {
+                self.gl.Enable(gl::FRAMEBUFFER_SRGB);
+            } else {
+                self.gl.Disable(gl::FRAMEBUFFER_SRGB);
+            }
+        }
+    }
+
+    pub fn framebuffer_srgb(&self) -> bool {
+        self.framebuffer_srgb
+    }
+
+    /// Confines clears and draws to the `(x, y, width, height)` box of the window, measured in
+    /// pixels from the bottom left, e.g. for a preview pane. The rest of the window keeps whatever
+    /// was last drawn there. `None`, the default, draws to the whole viewport. Fails, keeping the
+    /// current box, if the size is negative.
+    pub fn set_scissor(
+        &mut self,
+        scissor: Option<(i32, i32, i32, i32)>,
+    ) -> Result<(), ModelLoadingError> {
+        unsafe {
+            match scissor {
+                Some((x, y, width, height)) => {
+                    ensure!(
+                        width >= 0 && height >= 0,
+                        InvalidArgument,
+                        "scissor box {width}x{height} has a negative size"
+                    );
+                    self.gl.Scissor(x, y, width, height);
+                    self.gl.Enable(gl::SCISSOR_TEST);
+                }
+                None => self.gl.Disable(gl::SCISSOR_TEST),
+            }
+        }
+        self.scissor = scissor;
+        Ok(())
+    }
+
+    pub fn scissor(&self) -> Option<(i32, i32, i32, i32)> {
+        self.scissor
+    }
+
+    /// Sets how many pixels across points are drawn, 1 by default.
+    pub fn set_point_size(&mut self, size: f32) {
+        let size = self.clamp_to_range(gl::POINT_SIZE_RANGE, "point size", size);
+        self.point_size = size;
+        unsafe { self.gl.PointSize(size) };
+    }
+
+    pub fn point_size(&self) -> f32 {
+        self.point_size
+    }
+
+    /// Sets how many pixels wide lines are drawn, including the grid and wireframe edges, 1 by
+    /// default. Core profile drivers often only support a width of 1.
+    pub fn set_line_width(&mut self, width: f32) {
+        let width = self.clamp_to_range(gl::ALIASED_LINE_WIDTH_RANGE, "line width", width);
+        self.line_width = width;
+        unsafe { self.gl.LineWidth(width) };
+    }
+
+    pub fn line_width(&self) -> f32 {
+        self.line_width
+    }
+
+    /// Caps `value` to the `[min, max]` the driver reports for `range`, warning if it's outside.
+    fn clamp_to_range(&self, range: gl::types::GLenum, what: &str, value: f32) -> f32 {
+        let mut bounds = [0.0; 2];
+        unsafe { self.gl.GetFloatv(range, bounds.as_mut_ptr()) };
+        let [min, max] = bounds;
+        if !(min..=max).contains(&value) {
+            log::warn!("{what} {value} requested, but the driver supports {min} to {max}");
+        }
+        value.clamp(min, max.max(min))
+    }
+
+    /// Draws only the edges of each triangle when `enabled`.
+    pub fn set_wireframe(&mut self, enabled: bool) {
+        self.wireframe = enabled;
+    }
+
+    pub fn wireframe(&self) -> bool {
+        self.wireframe
+    }
+
+    /// Applies the normal maps of the meshes' materials, or lights the meshes with their vertex
+    /// normals alone, e.g. to compare the two. On by default.
+    pub fn set_normal_mapping(&mut self, enabled: bool) {
+        self.normal_mapping = enabled;
+    }
+
+    pub fn normal_mapping(&self) -> bool {
+        self.normal_mapping
+    }
+
+    /// Draws every mesh as `view` visualizes it instead of with the active program, or normally
+    /// with [`DebugView::None`], the default. Switching to [`DebugView::Curvature`] computes the
+    /// curvature of the meshes that don't have it yet, drawing the ones it fails for (those not
+    /// using the [`Vertex`] layout or not drawn as triangles) as flat. Meshes added afterwards need
+    /// [`Mesh::compute_curvature`] called on them.
+    pub fn set_debug_view(&mut self, view: DebugView) -> Result<(), ModelLoadingError> {
+        if view == DebugView::Curvature {
+            if self.curvature_program.is_none() {
+                let program = ShaderProgram::from_sources(
+                    &self.gl,
+                    CURVATURE_VERTEX_SHADER_SOURCE,
+                    CURVATURE_FRAGMENT_SHADER_SOURCE,
+                )
+                .context("failed to build the curvature program")?;
+                self.curvature_program = Some(program);
+            }
+            for mesh in &mut self.scene.meshes {
+                if mesh.curvature_range().is_none() {
+                    if let Err(err) = mesh.compute_curvature(&self.gl) {
+                        log::warn!("{err:#}, drawing the mesh as flat");
+                    }
+                }
+            }
+        }
+        self.debug_view = view;
+        Ok(())
+    }
+
+    pub fn debug_view(&self) -> DebugView {
+        self.debug_view
+    }
+
+    /// Uses `texture` for materials whose normal map is `path` instead of loading the file, e.g.
+    /// for a generated normal map. Replaces (and deletes) the texture used for `path` until now.
+    pub fn insert_normal_map(&mut self, path: impl Into<PathBuf>, texture: Texture) {
+        if let Some(Some(old)) = self
+            .normal_maps
+            .get_mut()
+            .insert(path.into(), Some(texture))
+        {
+            old.delete(&self.gl);
+        }
+    }
+
+    /// Draws the edges of the opaque meshes in `color` over their faces, or stops with `None`,
+    /// the default. Set a polygon offset with [`Renderer::set_polygon_offset`] too, or the edges
+    /// z-fight with the faces they lie on.
+    pub fn set_wireframe_overlay(&mut self, color: Option<Vec3>) {
+        self.wireframe_overlay = color;
+    }
+
+    pub fn wireframe_overlay(&self) -> Option<Vec3> {
+        self.wireframe_overlay
+    }
+
+    /// Pushes the meshes' triangles and wireframe edges back in the depth buffer by `factor` times
+    /// their depth slope plus `units` of the smallest depth step, as `Some((factor, units))`, so
+    /// coplanar geometry drawn without it wins the depth test. `Some((1.0, 1.0))` is usually
+    /// enough for a wireframe overlay, steep faces may want a larger factor, and negative values
+    /// pull decals forward instead. Off (`None`) by default.
+    pub fn set_polygon_offset(&mut self, offset: Option<(f32, f32)>) {
+        self.polygon_offset = offset;
+    }
+
+    pub fn polygon_offset(&self) -> Option<(f32, f32)> {
+        self.polygon_offset
+    }
+
+    /// Clears only the depth buffer, so whatever is drawn next isn't hidden by what's already in
+    /// the frame, e.g. a HUD over the scene.
+    pub fn clear_depth(&self) {
+        unsafe { self.gl.Clear(gl::DEPTH_BUFFER_BIT) };
+    }
+
+    /// Turns depth testing on or off for the following draws. Without it, meshes cover whatever
+    /// was drawn before them and leave the depth buffer untouched. It's on by default.
+    pub fn set_depth_test(&mut self, enabled: bool) {
+        self.depth_test = enabled;
+        self.apply_depth_state();
+    }
+
+    pub fn depth_test(&self) -> bool {
+        self.depth_test
+    }
+
+    /// [`CompareFunc::Less`] by default.
+    pub fn set_depth_func(&mut self, func: CompareFunc) {
+        self.depth_func = func;
+        self.apply_depth_state();
+    }
+
+    pub fn depth_func(&self) -> CompareFunc {
+        self.depth_func
+    }
+
+    fn apply_depth_state(&self) {
+        unsafe {
+            if self.depth_test {
+                self.gl.Enable(gl::DEPTH_TEST);
+            } else {
+                self.gl.Disable(gl::DEPTH_TEST);
+            }
+            self.gl.DepthFunc(self.depth_func.gl_enum());
+        }
+    }
+
+    /// Turns the stencil test on or off for the following draws. Off by default. The window asks
+    /// for an 8 bit stencil buffer, as do offscreen renders.
+    pub fn set_stencil_test(&mut self, enabled: bool) {
+        self.stencil_test = enabled;
+        self.apply_stencil_state();
+    }
+
+    pub fn stencil_test(&self) -> bool {
+        self.stencil_test
+    }
+
+    /// Sets how `reference` is compared against the stencil buffer, with both masked by `mask`
+    /// first. Always passes, with a reference of 0 and every bit in the mask, by default.
+    pub fn set_stencil_func(&mut self, func: CompareFunc, reference: u8, mask: u8) {
+        self.stencil_func = (func, reference, mask);
+        self.apply_stencil_state();
+    }
+
+    pub fn stencil_func(&self) -> (CompareFunc, u8, u8) {
+        self.stencil_func
+    }
+
+    /// Sets what happens to the stencil buffer when a fragment fails the stencil test
+    /// (`stencil_fail`), passes it but fails the depth test (`depth_fail`), or passes both
+    /// (`pass`). [`StencilOp::Keep`] for all three by default.
+    pub fn set_stencil_op(
+        &mut self,
+        stencil_fail: StencilOp,
+        depth_fail: StencilOp,
+        pass: StencilOp,
+    ) {
+        self.stencil_op = [stencil_fail, depth_fail, pass];
+        self.apply_stencil_state();
+    }
+
+    pub fn stencil_op(&self) -> (StencilOp, StencilOp, StencilOp) {
+        let [stencil_fail, depth_fail, pass] = self.stencil_op;
+        (stencil_fail, depth_fail, pass)
+    }
+
+    fn apply_stencil_state(&self) {
+        let (func, reference, mask) = self.stencil_func;
+        let [stencil_fail, depth_fail, pass] = self.stencil_op.map(StencilOp::gl_enum);
+        unsafe {
+            if self.stencil_test {
+                self.gl.Enable(gl::STENCIL_TEST);
+            } else {
+                self.gl.Disable(gl::STENCIL_TEST);
+            }
+            self.gl
+                .StencilFunc(func.gl_enum(), reference.into(), mask.into());
+            self.gl.StencilOp(stencil_fail, depth_fail, pass);
+            self.gl.StencilMask(u8::MAX.into());
+        }
+    }
+
+    /// [`BlendMode::Opaque`] by default, which draws transparent meshes solid.
+    pub fn set_blend_mode(&mut self, mode: BlendMode) {
+        self.blend_mode = mode;
+    }
+
+    pub fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
+    fn blends(&self, mesh: &Mesh) -> bool {
+        self.blend_mode != BlendMode::Opaque && mesh.material().is_transparent()
+    }
+
+    /// Turns blending with the blend mode's factors on, with depth writes off so transparent
+    /// meshes don't hide each other, or back to drawing solid.
+    fn set_blending(&self, enabled: bool) {
+        unsafe {
+            match self.blend_mode.gl_factors().filter(|_| enabled) {
+                Some((source, destination)) => {
+                    self.gl.Enable(gl::BLEND);
+                    self.gl.BlendFunc(source, destination);
+                    self.gl.DepthMask(gl::FALSE);
+                }
+                None => {
+                    self.gl.Disable(gl::BLEND);
+                    self.gl.DepthMask(gl::TRUE);
+                }
+            }
+        }
+    }
+
+    /// Culling is off by default.
+    pub fn set_cull_mode(&mut self, mode: CullMode) {
+        self.cull_mode = mode;
+    }
+
+    pub fn cull_mode(&self) -> CullMode {
+        self.cull_mode
+    }
+
+    /// Starts timing the GPU work of the following draws, up to [`Renderer::end_gpu_timing`].
+    /// Does nothing if the context lacks `GL_ARB_timer_query`.
+    pub fn begin_gpu_timing(&self) {
+        if let Some(timer) = self.gpu_timer.borrow_mut().as_mut() {
+            timer.begin(&self.gl);
+        }
+    }
+
+    /// Stops timing, returning how long the GPU took over the latest timed span it has finished,
+    /// usually from a frame or two before. `None` until the first result arrives, and always
+    /// without timer query support.
+    pub fn end_gpu_timing(&self) -> Option<Duration> {
+        self.gpu_timer.borrow_mut().as_mut()?.end(&self.gl)
+    }
+
+    /// The GL functions the renderer draws with, for creating meshes and textures to hand to it.
+    /// Whatever is created with them has to be deleted before the renderer is dropped.
+    pub fn gl(&self) -> &TracedGl {
+        &self.gl
+    }
+
+    /// Queries the context's limits and extensions. Each call asks the driver again.
+    pub fn capabilities(&self) -> Capabilities {
+        Capabilities::query(&self.gl)
+    }
+
+    /// Size of the viewport in pixels, which the projection's aspect ratio is derived from.
+    pub fn viewport_size(&self) -> (i32, i32) {
+        self.viewport_size
+    }
+
+    /// Views the scene through `camera`, taking both its view matrix and, for a perspective
+    /// projection, its field of view.
+    pub fn set_camera(&mut self, camera: &CameraMode) {
+        self.view_matrix = camera.view_matrix();
+        self.projection = self.projection.with_fov(camera.fov());
+    }
+
+    /// Routes driver debug messages (errors, undefined behavior, performance warnings) to the
+    /// `log` crate.
+    pub fn enable_debug_output(&self) {
+        enable_debug_output(&self.gl);
+    }
+
+    /// Starts recording every GL call the renderer issues until [`Renderer::end_trace`].
+    pub fn begin_trace(&self) {
+        self.gl.begin_trace();
+    }
+
+    /// Stops the current trace and writes it to `path`.
+    pub fn end_trace(&self, path: &Path) -> Result<(), ModelLoadingError> {
+        let calls = self.gl.end_trace().ok_or_else(|| {
+            ModelLoadingError::InvalidArgument("end_trace called without begin_trace".into())
+        })?;
+        write_trace(path, &calls)
+    }
+}
+
+impl Deref for Renderer {
+    type Target = TracedGl;
+
+    fn deref(&self) -> &Self::Target {
+        &self.gl
+    }
+}
+
+impl Drop for Renderer {
+    fn drop(&mut self) {
+        self.programs.delete(&self.gl);
+        if let Some(program) = &self.curvature_program {
+            program.delete(&self.gl);
+        }
+        self.white_texture.delete(&self.gl);
+        for texture in self.normal_maps.get_mut().values().flatten() {
+            texture.delete(&self.gl);
+        }
+        unsafe { self.gl.DeleteBuffers(1, &self.matrices_buffer) };
+        for mesh in &self.scene.meshes {
+            mesh.delete(&self.gl);
+        }
+        self.remove_skybox();
+        if let Some(grid) = self.grid.get_mut() {
+            grid.mesh.delete(&self.gl);
+        }
+        if let Some(box_lines) = self.box_lines.get_mut() {
+            box_lines.delete(&self.gl);
+        }
+        if let Some(billboard) = self.billboard.get_mut() {
+            billboard.mesh.delete(&self.gl);
+            billboard.program.delete(&self.gl);
+        }
+        if let Some(timer) = self.gpu_timer.get_mut() {
+            timer.delete(&self.gl);
+        }
+        self.disable_shadows();
+        if let Some(pass) = &self.normal_depth {
+            pass.delete(&self.gl);
+        }
+        if let Some(ssao) = &self.ssao {
+            ssao.delete(&self.gl);
+        }
+        if let Some(outline) = &self.edge_outline {
+            outline.program.delete(&self.gl);
+        }
+        unsafe {
+            self.gl
+                .DeleteVertexArrays(1, &self.full_screen_vertex_array);
+        }
+        if let Some(target) = &self.msaa_target {
+            unsafe {
+                self.gl.DeleteFramebuffers(1, &target.framebuffer);
+                self.gl
+                    .DeleteRenderbuffers(2, target.renderbuffers.as_ptr());
+            }
+        }
+        unsafe {
+            self.gl
+                .DeleteBuffers(1, &self.instance_buffer.get_mut().buffer);
+        }
+    }
+}
+
+/// Pairs of line vertices covering a grid of `divisions`x`divisions` cells, centered on the
+/// origin.
+fn grid_lines(size: f32, divisions: u32) -> (Vec<Vertex>, Vec<u32>) {
+    let half = size / 2.0;
+    let mut vertices = Vec::new();
+    for i in 0..=divisions {
+        let offset = -half + size * i as f32 / divisions as f32;
+        for position in [
+            vec3(offset, 0.0, -half),
+            vec3(offset, 0.0, half),
+            vec3(-half, 0.0, offset),
+            vec3(half, 0.0, offset),
+        ] {
+            vertices.push(Vertex {
+                position,
+                color: GRID_COLOR,
+                ..Default::default()
+            });
+        }
+    }
+    let indices = (0..vertices.len() as u32).collect();
+    (vertices, indices)
+}
+
+/// Pairs of line vertices along the 12 edges of the cube from 0 to 1.
+fn box_edges() -> (Vec<Vertex>, Vec<u32>) {
+    let vertices = (0..8)
+        .map(|corner| Vertex {
+            position: vec3(
+                (corner & 1) as f32,
+                ((corner >> 1) & 1) as f32,
+                ((corner >> 2) & 1) as f32,
+            ),
+            ..Default::default()
+        })
+        .collect();
+    // Each edge joins two corners differing along one axis.
+    let indices = (0..8u32)
+        .flat_map(|corner| {
+            [1, 2, 4]
+                .into_iter()
+                .filter(move |axis| corner & axis == 0)
+                .flat_map(move |axis| [corner, corner | axis])
+        })
+        .collect();
+    (vertices, indices)
+}
+
+/// The corners of a cube from -1 to 1, with two triangles per face.
+fn skybox_cube() -> (Vec<Vertex>, Vec<u32>) {
+    let vertices = (0..8)
+        .map(|corner| Vertex {
+            position: vec3(
+                if corner & 1 == 0 { -1.0 } else { 1.0 },
+                if corner & 2 == 0 { -1.0 } else { 1.0 },
+                if corner & 4 == 0 { -1.0 } else { 1.0 },
+            ),
+            color: Vec3::ONE,
+            ..Default::default()
+        })
+        .collect();
+    // Culling is off while the sky is drawn, so the winding doesn't matter.
+    let faces = [
+        [0, 2, 6, 4],
+        [1, 5, 7, 3],
+        [0, 4, 5, 1],
+        [2, 3, 7, 6],
+        [0, 1, 3, 2],
+        [4, 6, 7, 5],
+    ];
+    let indices = faces
+        .iter()
+        .flat_map(|[a, b, c, d]| [*a, *b, *c, *a, *c, *d])
+        .collect();
+    (vertices, indices)
+}
+
+static VERTEX_DATA: [Vertex; 3] = [
+    Vertex {
+        position: vec3(-0.5, -0.5, 0.0),
+        color: vec3(1.0, 0.0, 0.0),
+        normal: Vec3::Z,
+        uv: Vec2::ZERO,
+        tangent: Vec3::ZERO,
+        bitangent_sign: 0.0,
+    },
+    Vertex {
+        position: vec3(0.0, 0.5, 0.0),
+        color: vec3(0.0, 1.0, 0.0),
+        normal: Vec3::Z,
+        uv: Vec2::ZERO,
+        tangent: Vec3::ZERO,
+        bitangent_sign: 0.0,
+    },
+    Vertex {
+        position: vec3(0.5, -0.5, 0.0),
+        color: vec3(0.0, 0.0, 1.0),
+        normal: Vec3::Z,
+        uv: Vec2::ZERO,
+        tangent: Vec3::ZERO,
+        bitangent_sign: 0.0,
+    },
+];
+
+const DEFAULT_LIGHT_DIRECTION: Vec3 = vec3(-0.2, -0.5, -1.0);
+/// Distances to the near and far clipping planes, which fit a unit cube model seen from a few
+/// units away with room to move around.
+const DEFAULT_CLIP_PLANES: (f32, f32) = (0.1, 100.0);
+/// Most copies [`Renderer::set_array`] draws of each mesh, keeping the instance data uploaded per
+/// mesh and frame around a quarter of a megabyte.
+const MAX_ARRAY_INSTANCES: u64 = 4096;
+/// Opaque, as a translucent clear lets the desktop show through on compositors that blend windows.
+const DEFAULT_CLEAR_COLOR: [GLfloat; 4] = [0.1, 0.1, 0.1, 1.0];
+/// Dim enough for the grid to stay out of the way of the model.
+const GRID_COLOR: Vec3 = vec3(0.35, 0.35, 0.35);
+
+static INDEX_DATA: [u32; 3] = [0, 1, 2];
+
+const LIT_PROGRAM: &str = "lit";
+const UNLIT_PROGRAM: &str = "unlit";
+const TOON_PROGRAM: &str = "toon";
+/// Few enough to read as flat shades, enough to still show the shape of curved surfaces.
+const DEFAULT_TOON_BANDS: u32 = 4;
+
+const VERTEX_SHADER_SOURCE: &str = "
+#version 330 core
+
+in vec3 position;
+in vec3 color;
+in vec3 normal;
+in vec2 uv;
+// The sign of the bitangent is in w.
+in vec4 tangent;
+// The identity outside of instanced draws.
+in mat4 instance_matrix;
+
+layout(std140) uniform Matrices {
+    mat4 uView;
+    mat4 uProjection;
+    mat4 uViewProjection;
+};
+uniform mat4 uModel;
+uniform mat3 uNormalMatrix;
+uniform bool uFlipV;
+
+out vec3 v_position;
+out vec3 v_normal;
+out vec3 v_color;
+out vec2 v_uv;
+// World space tangent, bitangent and normal, for normal mapping.
+out mat3 v_tbn;
+
+void main() {
+    vec4 world_position = uModel * instance_matrix * vec4(position, 1.0);
+    gl_Position = uViewProjection * world_position;
+    v_position = world_position.xyz;
+    mat3 instance_rotation = mat3(instance_matrix[0].xyz, instance_matrix[1].xyz,
+        instance_matrix[2].xyz);
+    // Exact for rotations and uniform scales, which is what instances are expected to use.
+    v_normal = uNormalMatrix * instance_rotation * normal;
+    v_color = color;
+    v_uv = uFlipV ? vec2(uv.x, 1.0 - uv.y) : uv;
+
+    // Tangents lie along the surface, so they transform like positions rather than normals.
+    vec3 world_tangent = mat3(uModel) * instance_rotation * tangent.xyz;
+    // Flipping V reverses the direction it increases in.
+    float bitangent_sign = uFlipV ? -tangent.w : tangent.w;
+    v_tbn = mat3(world_tangent, cross(v_normal, world_tangent) * bitangent_sign, v_normal);
+}
+";
+
+/// Passes the vertex color through without lighting, e.g. to check the colors a model was
+/// exported with.
+const UNLIT_VERTEX_SHADER_SOURCE: &str = "
+#version 330 core
+
+in vec3 position;
+in vec3 color;
+in vec2 uv;
+in mat4 instance_matrix;
+
+layout(std140) uniform Matrices {
+    mat4 uView;
+    mat4 uProjection;
+    mat4 uViewProjection;
+};
+uniform mat4 uModel;
+uniform bool uFlipV;
+
+out vec3 v_position;
+out vec3 v_color;
+out vec2 v_uv;
+
+void main() {
+    vec4 world_position = uModel * instance_matrix * vec4(position, 1.0);
+    gl_Position = uViewProjection * world_position;
+    v_position = world_position.xyz;
+    v_color = color;
+    v_uv = uFlipV ? vec2(uv.x, 1.0 - uv.y) : uv;
+}
+";
+
+/// Texture unit the skybox is bound to for reflective materials, leaving unit 0 to `uTexture`.
+const ENVIRONMENT_TEXTURE_UNIT: u32 = 1;
+/// Texture unit materials' normal maps are bound to.
+const NORMAL_MAP_TEXTURE_UNIT: u32 = 2;
+/// Texture unit the shadow map is bound to, which every built-in program declares.
+const SHADOW_MAP_TEXTURE_UNIT: u32 = 3;
+/// Texture unit the blurred occlusion of [`Renderer::set_ssao`] is bound to for the lit program.
+const OCCLUSION_TEXTURE_UNIT: u32 = 4;
+
+/// Spans the quad along the camera's right and up directions, which are the first two rows of
+/// the view matrix's rotation.
+const BILLBOARD_VERTEX_SHADER_SOURCE: &str = "
+#version 330 core
+
+in vec3 position;
+in vec2 uv;
+
+layout(std140) uniform Matrices {
+    mat4 uView;
+    mat4 uProjection;
+    mat4 uViewProjection;
+};
+uniform vec3 uCenter;
+// Width and height, with z unused.
+uniform vec3 uSize;
+
+out vec2 v_uv;
+
+void main() {
+    vec3 right = vec3(uView[0][0], uView[1][0], uView[2][0]);
+    vec3 up = vec3(uView[0][1], uView[1][1], uView[2][1]);
+    vec3 world_position = uCenter + (right * position.x * uSize.x + up * position.y * uSize.y);
+    gl_Position = uViewProjection * vec4(world_position, 1.0);
+    v_uv = uv;
+}
+";
+
+const BILLBOARD_FRAGMENT_SHADER_SOURCE: &str = "
+#version 330 core
+
+uniform sampler2D uTexture;
+
+in vec2 v_uv;
+
+layout(location = 0) out vec4 frag_color;
+
+void main() {
+    frag_color = texture(uTexture, v_uv);
+}
+";
+
+/// Passes each vertex's curvature on for [`DebugView::Curvature`].
+const CURVATURE_VERTEX_SHADER_SOURCE: &str = "
+#version 330 core
+
+in vec3 position;
+in vec3 normal;
+in mat4 instance_matrix;
+in float curvature;
+
+layout(std140) uniform Matrices {
+    mat4 uView;
+    mat4 uProjection;
+    mat4 uViewProjection;
+};
+uniform mat4 uModel;
+uniform mat3 uNormalMatrix;
+
+out vec3 v_position;
+out vec3 v_normal;
+out float v_curvature;
+
+void main() {
+    vec4 world_position = uModel * instance_matrix * vec4(position, 1.0);
+    gl_Position = uViewProjection * world_position;
+    v_position = world_position.xyz;
+    v_normal = uNormalMatrix * mat3(instance_matrix) * normal;
+    v_curvature = curvature;
+}
+";
+
+/// Maps curvature from -uCurvatureRange to uCurvatureRange over the turbo color map, shaded by
+/// how directly each fragment faces the camera so the shape still reads.
+const CURVATURE_FRAGMENT_SHADER_SOURCE: &str = "
+#version 330 core
+
+uniform float uCurvatureRange;
+uniform vec3 uViewPos;
+
+in vec3 v_position;
+in vec3 v_normal;
+in float v_curvature;
+
+layout(location = 0) out vec4 frag_color;
+
+// Polynomial fit of the turbo color map, from Google's published approximation.
+vec3 turbo(float x) {
+    const vec4 red4 = vec4(0.13572138, 4.61539260, -42.66032258, 132.13108234);
+    const vec4 green4 = vec4(0.09140261, 2.19418839, 4.84296658, -14.18503333);
+    const vec4 blue4 = vec4(0.10667330, 12.64194608, -60.58204836, 110.36276771);
+    const vec2 red2 = vec2(-152.94239396, 59.28637943);
+    const vec2 green2 = vec2(4.27729857, 2.82956604);
+    const vec2 blue2 = vec2(-89.90310912, 27.34824973);
+    x = clamp(x, 0.0, 1.0);
+    vec4 v4 = vec4(1.0, x, x * x, x * x * x);
+    vec2 v2 = v4.zw * v4.z;
+    return vec3(dot(v4, red4) + dot(v2, red2), dot(v4, green4) + dot(v2, green2),
+        dot(v4, blue4) + dot(v2, blue2));
+}
+
+void main() {
+    vec3 color = turbo(v_curvature / uCurvatureRange * 0.5 + 0.5);
+    float facing = 1.0;
+    if (dot(v_normal, v_normal) > 0.0) {
+        facing = abs(dot(normalize(v_normal), normalize(uViewPos - v_position)));
+    }
+    frag_color = vec4(color * mix(0.4, 1.0, facing), 1.0);
+}
+";
+
+/// Draws meshes' depth from the shadow light, with the light's view-projection in `uLightMatrix`.
+const SHADOW_VERTEX_SHADER_SOURCE: &str = "
+#version 330 core
+
+in vec3 position;
+in mat4 instance_matrix;
+
+uniform mat4 uLightMatrix;
+uniform mat4 uModel;
+
+void main() {
+    gl_Position = uLightMatrix * uModel * instance_matrix * vec4(position, 1.0);
+}
+";
+
+/// Only the depth is written.
+const SHADOW_FRAGMENT_SHADER_SOURCE: &str = "
+#version 330 core
+
+void main() {}
+";
+
+/// Draws meshes' view space normals, mapped from `-1..1` to the color's `0..1` with an alpha of 1,
+/// for [`NormalDepthPass`].
+const NORMAL_DEPTH_VERTEX_SHADER_SOURCE: &str = "
+#version 330 core
+
+in vec3 position;
+in vec3 normal;
+in mat4 instance_matrix;
+
+layout(std140) uniform Matrices {
+    mat4 uView;
+    mat4 uProjection;
+    mat4 uViewProjection;
+};
+uniform mat4 uModel;
+uniform mat3 uNormalMatrix;
+
+out vec3 v_normal;
+
+void main() {
+    gl_Position = uViewProjection * uModel * instance_matrix * vec4(position, 1.0);
+    v_normal = mat3(uView) * uNormalMatrix * mat3(instance_matrix) * normal;
+}
+";
+
+const NORMAL_DEPTH_FRAGMENT_SHADER_SOURCE: &str = "
+#version 330 core
+
+in vec3 v_normal;
+
+layout(location = 0) out vec4 frag_normal;
+
+void main() {
+    // Vertices without a normal are taken to face the camera.
+    vec3 n = dot(v_normal, v_normal) > 0.0 ? normalize(v_normal) : vec3(0.0, 0.0, 1.0);
+    if (!gl_FrontFacing) {
+        n = -n;
+    }
+    frag_normal = vec4(n * 0.5 + 0.5, 1.0);
+}
+";
+
+/// Covers the viewport with one triangle made up from `gl_VertexID`, for drawing 3 vertices
+/// without any attributes, with `v_uv` running from 0 to 1 across the viewport.
+const FULL_SCREEN_VERTEX_SHADER_SOURCE: &str = "
+#version 330 core
+
+out vec2 v_uv;
+
+void main() {
+    v_uv = vec2((gl_VertexID << 1) & 2, gl_VertexID & 2);
+    gl_Position = vec4(v_uv * 2.0 - 1.0, 0.0, 1.0);
+}
+";
+
+/// The share of the kernel's samples around each pixel's view space position, turned about its
+/// normal by the noise, that aren't behind the surface drawn there. Only surfaces within the
+/// radius of the pixel count, so the background doesn't darken the edges of what's in front.
+const SSAO_FRAGMENT_SHADER_SOURCE: &str = "
+#version 330 core
+
+layout(std140) uniform Matrices {
+    mat4 uView;
+    mat4 uProjection;
+    mat4 uViewProjection;
+};
+uniform mat4 uInverseProjection;
+uniform sampler2D uDepth;
+uniform sampler2D uNormals;
+uniform sampler2D uNoise;
+// Sized to MAX_SSAO_SAMPLES.
+uniform vec3 uKernel[64];
+uniform int uSampleCount;
+uniform float uRadius;
+uniform float uBias;
+uniform float uIntensity;
+
+in vec2 v_uv;
+
+layout(location = 0) out vec4 frag_occlusion;
+
+vec3 view_position(vec2 uv) {
+    float depth = texture(uDepth, uv).r;
+    vec4 position = uInverseProjection * vec4(vec3(uv, depth) * 2.0 - 1.0, 1.0);
+    return position.xyz / position.w;
+}
+
+void main() {
+    // Nothing was drawn here.
+    if (texture(uDepth, v_uv).r == 1.0) {
+        frag_occlusion = vec4(1.0);
+        return;
+    }
+    vec3 position = view_position(v_uv);
+    vec3 normal = normalize(texture(uNormals, v_uv).xyz * 2.0 - 1.0);
+    vec3 random = vec3(texelFetch(uNoise, ivec2(gl_FragCoord.xy) & 3, 0).xy * 2.0 - 1.0, 0.0);
+    vec3 tangent = random - normal * dot(random, normal);
+    // The random direction can be along a normal facing sideways.
+    tangent = dot(tangent, tangent) > 1e-6
+        ? normalize(tangent)
+        : normalize(cross(normal, vec3(0.0, 0.0, 1.0)));
+    mat3 tbn = mat3(tangent, cross(normal, tangent), normal);
+
+    float occluded = 0.0;
+    for (int i = 0; i < uSampleCount; i++) {
+        vec3 sample_position = position + tbn * uKernel[i] * uRadius;
+        vec4 clip = uProjection * vec4(sample_position, 1.0);
+        float surface = view_position(clip.xy / clip.w * 0.5 + 0.5).z;
+        float in_range = smoothstep(0.0, 1.0, uRadius / abs(position.z - surface));
+        occluded += (surface >= sample_position.z + uBias ? 1.0 : 0.0) * in_range;
+    }
+    frag_occlusion = vec4(pow(1.0 - occluded / float(uSampleCount), uIntensity));
+}
+";
+
+/// Averages the occlusion over the 4x4 pixels around each, weighting out the ones further than a
+/// fraction of the radius in front of or behind it, which are on another surface.
+const SSAO_BLUR_FRAGMENT_SHADER_SOURCE: &str = "
+#version 330 core
+
+uniform mat4 uInverseProjection;
+uniform sampler2D uOcclusion;
+uniform sampler2D uDepth;
+uniform float uRadius;
+
+layout(location = 0) out vec4 frag_occlusion;
+
+float view_depth(ivec2 texel) {
+    float depth = texelFetch(uDepth, texel, 0).r * 2.0 - 1.0;
+    vec4 position = uInverseProjection * vec4(0.0, 0.0, depth, 1.0);
+    return position.z / position.w;
+}
+
+void main() {
+    ivec2 center = ivec2(gl_FragCoord.xy);
+    ivec2 last = textureSize(uOcclusion, 0) - 1;
+    float depth = view_depth(center);
+    float total = 0.0;
+    float weights = 0.0;
+    // The size of the noise's tile, so its pattern averages out.
+    for (int x = -2; x < 2; x++) {
+        for (int y = -2; y < 2; y++) {
+            ivec2 texel = clamp(center + ivec2(x, y), ivec2(0), last);
+            float weight = max(1.0 - abs(view_depth(texel) - depth) / (0.5 * uRadius), 0.0);
+            total += texelFetch(uOcclusion, texel, 0).r * weight;
+            weights += weight;
+        }
+    }
+    // The center always weighs 1.
+    frag_occlusion = vec4(total / weights);
+}
+";
+
+/// Draws the outline color wherever the Sobel gradient of the view depth, relative to the
+/// pixel's own, or of the normals is over its threshold, and nothing elsewhere. Both are divided
+/// by the filter's weight on each side, so a step between two values measures as their
+/// difference.
+const EDGE_OUTLINE_FRAGMENT_SHADER_SOURCE: &str = "
+#version 330 core
+
+uniform mat4 uInverseProjection;
+uniform sampler2D uDepth;
+uniform sampler2D uNormals;
+// Bottom left corner of the viewport, which pixel (0, 0) of the textures is at.
+uniform vec2 uOrigin;
+uniform float uDepthThreshold;
+uniform float uNormalThreshold;
+uniform vec3 uColor;
+uniform float uThickness;
+
+layout(location = 0) out vec4 frag_color;
+
+ivec2 clamp_to_texture(ivec2 texel) {
+    return clamp(texel, ivec2(0), textureSize(uDepth, 0) - 1);
+}
+
+// Distance in front of the camera.
+float view_depth(ivec2 texel) {
+    float depth = texelFetch(uDepth, clamp_to_texture(texel), 0).r * 2.0 - 1.0;
+    vec4 position = uInverseProjection * vec4(0.0, 0.0, depth, 1.0);
+    return -position.z / position.w;
+}
+
+// Zero where nothing was drawn.
+vec3 view_normal(ivec2 texel) {
+    vec4 normal = texelFetch(uNormals, clamp_to_texture(texel), 0);
+    return normal.a > 0.0 ? normal.xyz * 2.0 - 1.0 : vec3(0.0);
+}
+
+void main() {
+    ivec2 center = ivec2(gl_FragCoord.xy - uOrigin);
+    int spacing = max(int(round(uThickness)), 1);
+    // Row by row from the bottom left.
+    float depths[9];
+    vec3 normals[9];
+    for (int y = 0; y < 3; y++) {
+        for (int x = 0; x < 3; x++) {
+            ivec2 texel = center + ivec2(x - 1, y - 1) * spacing;
+            depths[y * 3 + x] = view_depth(texel);
+            normals[y * 3 + x] = view_normal(texel);
+        }
+    }
+
+    float depth_x = depths[2] + 2.0 * depths[5] + depths[8] - depths[0] - 2.0 * depths[3] - depths[6];
+    float depth_y = depths[6] + 2.0 * depths[7] + depths[8] - depths[0] - 2.0 * depths[1] - depths[2];
+    vec3 normal_x = normals[2] + 2.0 * normals[5] + normals[8] - normals[0] - 2.0 * normals[3]
+        - normals[6];
+    vec3 normal_y = normals[6] + 2.0 * normals[7] + normals[8] - normals[0] - 2.0 * normals[1]
+        - normals[2];
+    float depth_edge = length(vec2(depth_x, depth_y)) / 4.0 / max(depths[4], 1e-6);
+    float normal_edge = sqrt(dot(normal_x, normal_x) + dot(normal_y, normal_y)) / 4.0;
+    if (depth_edge <= uDepthThreshold && normal_edge <= uNormalThreshold) {
+        discard;
+    }
+    frag_color = vec4(uColor, 1.0);
+}
+";
+
+/// Per-fragment Phong lighting from up to [`MAX_LIGHTS`] lights, in world space, with reflective
+/// materials mirroring the skybox. As the toon program, it bands the diffuse light and cuts off
+/// highlights.
+const PHONG_FRAGMENT_SHADER_SOURCE: &str = "
+#version 330 core
+
+struct Material {
+    vec3 ambient;
+    vec3 diffuse;
+    vec3 specular;
+    float shininess;
+    float reflectivity;
+    float alpha;
+};
+
+// Directional lights store the direction they travel in as `vector`, point lights their position.
+struct Light {
+    bool point;
+    vec3 vector;
+    vec3 color;
+    float range;
+};
+
+uniform Material uMaterial;
+// Sized to MAX_LIGHTS.
+uniform Light uLights[8];
+uniform int uLightCount;
+uniform vec3 uViewPos;
+uniform sampler2D uTexture;
+uniform samplerCube uEnvironment;
+uniform bool uUseEnvironment;
+uniform sampler2D uNormalMap;
+uniform bool uUseNormalMap;
+uniform sampler2DShadow uShadowMap;
+uniform bool uUseShadows;
+// Index of the light the shadow map was drawn from.
+uniform int uShadowLight;
+uniform mat4 uLightMatrix;
+uniform bool uUseVertexColor;
+uniform vec3 uBaseColor;
+// Screen-space ambient occlusion, read at the fragment's pixel relative to the viewport's corner.
+uniform sampler2D uOcclusion;
+uniform bool uUseOcclusion;
+uniform vec2 uOcclusionOrigin;
+// Levels the diffuse light of each light is rounded up to for cel shading, or 0 for smooth shading.
+uniform int uToonBands;
+
+in vec3 v_position;
+in vec3 v_normal;
+in vec3 v_color;
+in vec2 v_uv;
+in mat3 v_tbn;
+
+layout(location = 0) out vec4 frag_color;
+
+// How much of the shadow light reaches `world_position`, averaging the depth comparisons of the
+// 3x3 texels around it.
+float shadow_visibility(vec3 world_position, float bias) {
+    vec4 light_position = uLightMatrix * vec4(world_position, 1.0);
+    vec3 coords = light_position.xyz / light_position.w * 0.5 + 0.5;
+    // Past the far plane is still behind everything the map saw.
+    float depth = min(coords.z, 1.0) - bias;
+    vec2 texel = 1.0 / vec2(textureSize(uShadowMap, 0));
+    float visibility = 0.0;
+    for (int x = -1; x <= 1; x++) {
+        for (int y = -1; y <= 1; y++) {
+            visibility += texture(uShadowMap, vec3(coords.xy + vec2(x, y) * texel, depth));
+        }
+    }
+    return visibility / 9.0;
+}
+
+void main() {
+    vec3 color = uUseVertexColor ? v_color : uBaseColor;
+    vec4 base = vec4(color, 1.0) * texture(uTexture, v_uv);
+
+    // Vertices without a normal are drawn unlit rather than black.
+    if (dot(v_normal, v_normal) == 0.0) {
+        frag_color = vec4(base.rgb, base.a * uMaterial.alpha);
+        return;
+    }
+
+    vec3 n = normalize(v_normal);
+    // Meshes without tangents keep their interpolated normal.
+    if (uUseNormalMap && dot(v_tbn[0], v_tbn[0]) > 0.0) {
+        mat3 tbn = mat3(normalize(v_tbn[0]), normalize(v_tbn[1]), n);
+        n = normalize(tbn * (texture(uNormalMap, v_uv).rgb * 2.0 - 1.0));
+    }
+    vec3 to_view = normalize(uViewPos - v_position);
+    vec3 diffuse = vec3(0.0);
+    vec3 specular = vec3(0.0);
+    for (int i = 0; i < uLightCount; i++) {
+        Light light = uLights[i];
+        vec3 light_dir = light.vector;
+        vec3 color = light.color;
+        if (light.point) {
+            vec3 offset = v_position - light.vector;
+            float dist = length(offset);
+            light_dir = offset / max(dist, 1e-4);
+            // Falls off smoothly to zero at the range rather than cutting off.
+            float falloff = clamp(1.0 - dist / light.range, 0.0, 1.0);
+            color *= falloff * falloff;
+        }
+        if (uUseShadows && i == uShadowLight) {
+            // The surface's own normal, as normal maps don't move the geometry in the map. Faces
+            // at a grazing angle to the light need the most bias to not shadow themselves.
+            float facing = max(dot(normalize(v_normal), -light_dir), 0.0);
+            color *= shadow_visibility(v_position, max(0.005 * (1.0 - facing), 0.0005));
+        }
+
+        float amount = max(dot(n, -light_dir), 0.0);
+        if (uToonBands > 0) {
+            // Rounded up so the band edge nearest the dark side stays where the light ends.
+            amount = ceil(amount * float(uToonBands)) / float(uToonBands);
+        }
+        diffuse += color * amount;
+        if (amount > 0.0) {
+            float highlight = pow(max(dot(to_view, reflect(light_dir, n)), 0.0), uMaterial.shininess);
+            if (uToonBands > 0) {
+                highlight = step(0.5, highlight);
+            }
+            specular += color * highlight;
+        }
+    }
+
+    float ambient_occlusion = 1.0;
+    if (uUseOcclusion) {
+        ambient_occlusion = texelFetch(uOcclusion, ivec2(gl_FragCoord.xy - uOcclusionOrigin), 0).r;
+    }
+    vec3 lit = base.rgb * (uMaterial.ambient * ambient_occlusion + uMaterial.diffuse * diffuse);
+    if (uUseEnvironment && uMaterial.reflectivity > 0.0) {
+        vec3 reflected = reflect(normalize(v_position - uViewPos), n);
+        lit = mix(lit, texture(uEnvironment, reflected).rgb, uMaterial.reflectivity);
+    }
+    lit += uMaterial.specular * specular;
+    frag_color = vec4(lit, base.a * uMaterial.alpha);
+}
+";
+
+const FRAGMENT_SHADER_SOURCE: &str = "
+#version 330 core
+
+// Only the opacity applies without lighting.
+struct Material {
+    float alpha;
+};
+
+uniform Material uMaterial;
+uniform sampler2D uTexture;
+uniform bool uUseVertexColor;
+uniform vec3 uBaseColor;
+// Darkens where the shadow light is blocked, for the grid.
+uniform bool uReceiveShadows;
+uniform sampler2DShadow uShadowMap;
+uniform mat4 uLightMatrix;
+
+in vec3 v_position;
+in vec3 v_color;
+in vec2 v_uv;
+
+layout(location = 0) out vec4 frag_color;
+
+// As in the lit program.
+float shadow_visibility(vec3 world_position, float bias) {
+    vec4 light_position = uLightMatrix * vec4(world_position, 1.0);
+    vec3 coords = light_position.xyz / light_position.w * 0.5 + 0.5;
+    float depth = min(coords.z, 1.0) - bias;
+    vec2 texel = 1.0 / vec2(textureSize(uShadowMap, 0));
+    float visibility = 0.0;
+    for (int x = -1; x <= 1; x++) {
+        for (int y = -1; y <= 1; y++) {
+            visibility += texture(uShadowMap, vec3(coords.xy + vec2(x, y) * texel, depth));
+        }
+    }
+    return visibility / 9.0;
+}
+
+void main() {
+    vec3 color = uUseVertexColor ? v_color : uBaseColor;
+    frag_color = vec4(color, uMaterial.alpha) * texture(uTexture, v_uv);
+    if (uReceiveShadows) {
+        // Lines have no facing to bias by.
+        frag_color.rgb *= mix(0.35, 1.0, shadow_visibility(v_position, 0.005));
+    }
+}
+";
+
+/// Looks up the direction to each fragment from the center of the cube in the cube map.
+const SKYBOX_VERTEX_SHADER_SOURCE: &str = "
+#version 330 core
+
+in vec3 position;
+
+uniform mat4 uSkyMatrix;
+
+out vec3 v_direction;
+
+void main() {
+    v_direction = position;
+    // A depth of w puts the cube on the far plane, so no clip plane setting cuts it off.
+    gl_Position = (uSkyMatrix * vec4(position, 1.0)).xyww;
+}
+";
+
+const SKYBOX_FRAGMENT_SHADER_SOURCE: &str = "
+#version 330 core
+
+uniform samplerCube uSkybox;
+
+in vec3 v_direction;
+
+layout(location = 0) out vec4 frag_color;
+
+void main() {
+    frag_color = texture(uSkybox, v_direction);
+}
+";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn copies(counts: [u32; 3]) -> u64 {
+        counts.iter().map(|&count| u64::from(count)).product()
+    }
+
+    #[test]
+    fn array_counts_under_the_cap_are_kept() {
+        assert_eq!(InstanceArray::capped_counts([3, 4, 5]), [3, 4, 5]);
+        assert_eq!(InstanceArray::capped_counts([0, 2, 0]), [1, 2, 1]);
+        assert_eq!(InstanceArray::capped_counts([16, 16, 16]), [16, 16, 16]);
+        assert_eq!(InstanceArray::capped_counts([4096, 1, 1]), [4096, 1, 1]);
+    }
+
+    #[test]
+    fn huge_array_counts_are_capped() {
+        assert_eq!(InstanceArray::capped_counts([u32::MAX, 1, 1]), [4096, 1, 1]);
+        assert_eq!(InstanceArray::capped_counts([1, 0, u32::MAX]), [1, 1, 4096]);
+        assert_eq!(InstanceArray::capped_counts([u32::MAX; 3]), [16, 16, 16]);
+    }
+
+    #[test]
+    fn mixed_array_counts_lower_the_largest_first() {
+        assert_eq!(InstanceArray::capped_counts([100, 100, 1]), [64, 64, 1]);
+        assert_eq!(InstanceArray::capped_counts([2, u32::MAX, 3]), [2, 682, 3]);
+        let counts = InstanceArray::capped_counts([4097, 2, 50]);
+        assert!(copies(counts) <= MAX_ARRAY_INSTANCES, "{counts:?}");
+        // Raising any count would go over again.
+        for axis in 0..3 {
+            let mut raised = counts;
+            raised[axis] += 1;
+            assert!(copies(raised) > MAX_ARRAY_INSTANCES, "{raised:?}");
+        }
+    }
 }
-\0";
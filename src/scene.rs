@@ -0,0 +1,694 @@
+use std::{borrow::Cow, fs, path::Path};
+
+use glam::{vec3, DVec3, Mat4, Vec2, Vec3, Vec4};
+use image::{ImageFormat, Rgba, RgbaImage};
+
+use crate::{
+    error::{bail, ensure, Context, ModelLoadingError},
+    material::Material,
+    mesh::{validate_indices, Aabb, Mesh, MeshData, PrimitiveMode, Vertex},
+    mtl,
+    obj::{load_obj_model, parse_obj_model, ObjGroup, ObjModel},
+    texture::Texture,
+    trace::TracedGl,
+    transform::Transform,
+};
+
+/// Color of the texture standing in for images that fail to decode, loud enough to notice.
+const PLACEHOLDER_COLOR: Rgba<u8> = Rgba([255, 0, 255, 255]);
+
+/// Distance between the centers of neighbouring models laid out by [`Scene::load_dir`], leaving
+/// a gap between their unit cubes.
+const DIR_LAYOUT_SPACING: f32 = 1.5;
+
+/// Reads a file a model refers to, like a glTF buffer or image or an OBJ material library or
+/// texture, given the reference as the model spells it, relative to wherever the model came from.
+pub type Fetch<'a> = dyn Fn(&str) -> Result<Vec<u8>, ModelLoadingError> + 'a;
+
+/// Index of a mesh in a [`Scene`] (or a [`crate::renderer::Renderer`]'s meshes).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MeshId(pub usize);
+
+/// Index of a node in a [`Scene`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId(pub usize);
+
+/// A transform in a hierarchy. Its world matrix is its parent's with its own `transform` applied
+/// on top, so moving a node moves everything below it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    pub transform: Transform,
+    /// Mesh whose `model_matrix` follows this node's world matrix.
+    pub mesh: Option<MeshId>,
+    parent: Option<NodeId>,
+    world_matrix: Mat4,
+}
+
+impl Node {
+    pub fn new(transform: Transform) -> Self {
+        Self {
+            transform,
+            mesh: None,
+            parent: None,
+            world_matrix: transform.matrix(),
+        }
+    }
+
+    pub fn with_mesh(mut self, mesh: MeshId) -> Self {
+        self.mesh = Some(mesh);
+        self
+    }
+
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    /// World matrix as of the last [`Scene::update_world_transforms`].
+    pub fn world_matrix(&self) -> Mat4 {
+        self.world_matrix
+    }
+}
+
+/// A collection of meshes loaded together, e.g. from one glTF file, and optionally a hierarchy of
+/// nodes positioning them.
+#[derive(Default)]
+pub struct Scene {
+    pub meshes: Vec<Mesh>,
+    /// Every node comes after its parent, see [`Scene::add_child`].
+    pub nodes: Vec<Node>,
+}
+
+impl Scene {
+    /// Loads a model file, picking the loader from the file extension, or every OBJ file in a
+    /// directory with [`Scene::load_dir`]. Files with an unknown extension are read as OBJ.
+    pub fn load_file(gl: &TracedGl, path: &Path) -> Result<Self, ModelLoadingError> {
+        if path.is_dir() {
+            return Self::load_dir(gl, path);
+        }
+        let extension = path.extension().and_then(|ext| ext.to_str());
+        let mesh = match extension.map(str::to_ascii_lowercase).as_deref() {
+            Some("gltf" | "glb") => return Self::load_gltf(gl, path),
+            Some("ply") => Mesh::from_ply(gl, path)?,
+            Some("stl") => Mesh::from_stl(gl, path)?,
+            _ => return Self::load_obj(gl, path),
+        };
+        Ok(Scene {
+            meshes: vec![mesh],
+            ..Default::default()
+        })
+    }
+
+    /// Loads every triangle primitive reachable from the default scene (or the first scene) of a
+    /// `.gltf` or `.glb` file. Each primitive becomes one [`Mesh`] with its node's world transform
+    /// as the `model_matrix`, the base color factor baked into the vertex colors and the base color
+    /// texture attached. Images that can't be decoded are replaced with a magenta placeholder.
+    pub fn load_gltf(gl: &TracedGl, path: &Path) -> Result<Self, ModelLoadingError> {
+        let bytes = fs::read(path).map_err(ModelLoadingError::file_io(path))?;
+        let base = path.parent().unwrap_or(Path::new(""));
+        let fetch = |uri: &str| {
+            let path = base.join(&*urlencoding::decode(uri)?);
+            fs::read(&path).map_err(ModelLoadingError::file_io(&path))
+        };
+        Self::load_gltf_from_memory(gl, &bytes, &fetch)
+            .with_context(|| format!("failed to load {}", path.display()))
+    }
+
+    /// Like [`Scene::load_gltf`] for the contents of a `.gltf` or `.glb` file, getting the
+    /// buffers and images it refers to by URI, other than `data:` URIs, from `fetch`.
+    pub fn load_gltf_from_memory(
+        gl: &TracedGl,
+        bytes: &[u8],
+        fetch: &Fetch,
+    ) -> Result<Self, ModelLoadingError> {
+        let gltf::Gltf { document, blob } = gltf::Gltf::from_slice(bytes)
+            .map_err(|err| ModelLoadingError::InvalidData(format!("invalid glTF: {err}")))?;
+        let buffers = load_buffers(&document, blob, fetch)?;
+        let images = load_images(&document, &buffers, fetch);
+
+        let mut scene = Scene::default();
+        let Some(gltf_scene) = document
+            .default_scene()
+            .or_else(|| document.scenes().next())
+        else {
+            return Ok(scene);
+        };
+
+        let mut stack: Vec<_> = gltf_scene
+            .nodes()
+            .map(|node| (node, Mat4::IDENTITY))
+            .collect();
+        while let Some((node, parent_transform)) = stack.pop() {
+            let transform = parent_transform * Mat4::from_cols_array_2d(&node.transform().matrix());
+            stack.extend(node.children().map(|child| (child, transform)));
+
+            let Some(gltf_mesh) = node.mesh() else {
+                continue;
+            };
+            for primitive in gltf_mesh.primitives() {
+                if primitive.mode() != gltf::mesh::Mode::Triangles {
+                    log::warn!(
+                        "skipping {:?} primitive in mesh {:?}, only triangles are supported",
+                        primitive.mode(),
+                        gltf_mesh.name().unwrap_or_default()
+                    );
+                    continue;
+                }
+                let mesh = load_primitive(gl, &primitive, &buffers, &images).inspect_err(|_| {
+                    for mesh in &scene.meshes {
+                        mesh.delete(gl);
+                    }
+                })?;
+                let Some(mut mesh) = mesh else {
+                    log::warn!(
+                        "skipping primitive without positions in mesh {:?}",
+                        gltf_mesh.name().unwrap_or_default()
+                    );
+                    continue;
+                };
+                mesh.model_matrix = transform;
+                scene.meshes.push(mesh);
+            }
+        }
+
+        Ok(scene)
+    }
+
+    /// Loads a Wavefront OBJ file as one mesh per `usemtl` material, named after it, with the
+    /// material and diffuse texture defined for it in the `.mtl` libraries the file references.
+    /// Smooth normals are computed for meshes without any. Missing libraries and materials are
+    /// logged and leave the meshes with the default material, and textures that fail to load are
+    /// replaced with a magenta placeholder.
+    pub fn load_obj(gl: &TracedGl, path: &Path) -> Result<Self, ModelLoadingError> {
+        let model = load_obj_model(path)?;
+        let base = path.parent().unwrap_or(Path::new(""));
+        let fetch = |name: &str| {
+            let path = base.join(name);
+            fs::read(&path).map_err(ModelLoadingError::file_io(&path))
+        };
+        Self::load_obj_model(gl, model, &fetch)
+    }
+
+    /// Like [`Scene::load_obj`] for the contents of an OBJ file, getting the material libraries
+    /// and the textures named in them from `fetch`.
+    pub fn load_obj_from_memory(
+        gl: &TracedGl,
+        source: &str,
+        fetch: &Fetch,
+    ) -> Result<Self, ModelLoadingError> {
+        let model = parse_obj_model(source).context("failed to parse OBJ")?;
+        Self::load_obj_model(gl, model, fetch)
+    }
+
+    fn load_obj_model(
+        gl: &TracedGl,
+        model: ObjModel,
+        fetch: &Fetch,
+    ) -> Result<Self, ModelLoadingError> {
+        let materials = mtl::load_libraries(&model.material_libraries, fetch);
+
+        let mut scene = Scene::default();
+        for group in model.groups {
+            let mtl = group.material.as_ref().and_then(|name| {
+                let mtl = materials.get(name);
+                if mtl.is_none() {
+                    log::warn!("material {name:?} isn't defined, using the default");
+                }
+                mtl
+            });
+            let mesh = load_obj_group(gl, group, mtl, fetch).inspect_err(|_| {
+                for mesh in &scene.meshes {
+                    mesh.delete(gl);
+                }
+            })?;
+            scene.meshes.push(mesh);
+        }
+        Ok(scene)
+    }
+
+    /// Loads every `.obj` file directly inside `dir` as its own model, with its meshes named after
+    /// the file. Each is fitted to a unit cube, and they're laid out in rows on the XZ plane in
+    /// file name order so they don't overlap. Files that fail to load are logged and skipped.
+    pub fn load_dir(gl: &TracedGl, dir: &Path) -> Result<Self, ModelLoadingError> {
+        let mut paths: Vec<_> = fs::read_dir(dir)
+            .map_err(ModelLoadingError::file_io(dir))?
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| {
+                path.is_file()
+                    && path
+                        .extension()
+                        .is_some_and(|ext| ext.eq_ignore_ascii_case("obj"))
+            })
+            .collect();
+        paths.sort();
+
+        let mut scene = Scene::default();
+        for path in &paths {
+            match Self::load_obj(gl, path) {
+                Ok(mut single) => {
+                    if let Some(name) = path.file_name() {
+                        for mesh in &mut single.meshes {
+                            mesh.set_name(name.to_string_lossy());
+                        }
+                    }
+                    single.normalize_to_unit_cube();
+                    scene.meshes.extend(single.meshes);
+                }
+                Err(err) => log::warn!("skipping {}: {err:#}", path.display()),
+            }
+        }
+
+        // As close to square as the count allows, filled a row at a time.
+        let columns = (scene.meshes.len() as f32).sqrt().ceil().max(1.0) as usize;
+        for (i, mesh) in scene.meshes.iter_mut().enumerate() {
+            let cell = vec3((i % columns) as f32, 0.0, (i / columns) as f32);
+            mesh.model_matrix =
+                Mat4::from_translation(cell * DIR_LAYOUT_SPACING) * mesh.model_matrix;
+        }
+        Ok(scene)
+    }
+
+    /// Adds a mesh, returning its id for attaching it to a [`Node`].
+    pub fn add_mesh(&mut self, mesh: Mesh) -> MeshId {
+        self.meshes.push(mesh);
+        MeshId(self.meshes.len() - 1)
+    }
+
+    /// Adds a node without a parent.
+    pub fn add_node(&mut self, node: Node) -> NodeId {
+        self.nodes.push(Node {
+            parent: None,
+            ..node
+        });
+        NodeId(self.nodes.len() - 1)
+    }
+
+    /// Adds `node` below `parent`. Panics if `parent` isn't a node of this scene.
+    pub fn add_child(&mut self, parent: NodeId, node: Node) -> NodeId {
+        assert!(
+            parent.0 < self.nodes.len(),
+            "no node {parent:?} in the scene"
+        );
+        self.nodes.push(Node {
+            parent: Some(parent),
+            ..node
+        });
+        NodeId(self.nodes.len() - 1)
+    }
+
+    /// Recomputes every node's world matrix from its parent's and copies it to the `model_matrix`
+    /// of its mesh. Call it after changing node transforms. Meshes without a node are left alone.
+    pub fn update_world_transforms(&mut self) {
+        for i in 0..self.nodes.len() {
+            let node = &self.nodes[i];
+            let parent_matrix = node
+                .parent
+                .map_or(Mat4::IDENTITY, |parent| self.nodes[parent.0].world_matrix);
+            let world_matrix = parent_matrix * node.transform.matrix();
+            self.nodes[i].world_matrix = world_matrix;
+            if let Some(mesh) = self.nodes[i]
+                .mesh
+                .and_then(|mesh| self.meshes.get_mut(mesh.0))
+            {
+                mesh.model_matrix = world_matrix;
+            }
+        }
+    }
+
+    /// Moves the meshes and nodes of `other` into this scene, keeping their ids pointing at each
+    /// other.
+    pub fn append(&mut self, other: Scene) {
+        let (mesh_offset, node_offset) = (self.meshes.len(), self.nodes.len());
+        self.meshes.extend(other.meshes);
+        self.nodes.extend(other.nodes.into_iter().map(|node| Node {
+            mesh: node.mesh.map(|mesh| MeshId(mesh.0 + mesh_offset)),
+            parent: node.parent.map(|parent| NodeId(parent.0 + node_offset)),
+            ..node
+        }));
+    }
+
+    /// Combines the static meshes sharing a material into one mesh each, so drawing them takes a
+    /// single draw call instead of one per mesh. Each mesh's `model_matrix` is baked into its
+    /// vertices and the merged mesh gets the identity. Only meshes that also agree on
+    /// [`Mesh::flip_uv_v`] and [`Mesh::origin_offset`] are merged, and the merged mesh keeps
+    /// those and the names of its sources. Meshes `is_animated` returns true for are left alone,
+    /// as are textured ones and ones not drawn as triangles or using a custom layout.
+    ///
+    /// The merged meshes come after the rest, so [`MeshId`]s change. Nodes are updated to match,
+    /// and a node whose mesh was merged loses it. Returns how many draw calls that saves.
+    pub fn merge_static(
+        &mut self,
+        gl: &TracedGl,
+        is_animated: impl Fn(MeshId, &Mesh) -> bool,
+    ) -> Result<usize, ModelLoadingError> {
+        // Materials are compared with floats, so a few groups are found by a linear search.
+        let mut groups: Vec<((Material, bool, DVec3), Vec<usize>)> = Vec::new();
+        for (i, mesh) in self.meshes.iter().enumerate() {
+            if is_animated(MeshId(i), mesh)
+                || mesh.texture().is_some()
+                || mesh.primitive_mode() != PrimitiveMode::Triangles
+            {
+                continue;
+            }
+            let key = (
+                mesh.material().clone(),
+                mesh.flip_uv_v(),
+                mesh.origin_offset(),
+            );
+            match groups.iter_mut().find(|(group, _)| *group == key) {
+                Some((_, members)) => members.push(i),
+                None => groups.push((key, vec![i])),
+            }
+        }
+
+        let mut merged = Vec::new();
+        let mut merged_away = vec![false; self.meshes.len()];
+        for ((material, flip_uv_v, origin_offset), members) in groups {
+            let mut data = MeshData {
+                origin_offset,
+                ..Default::default()
+            };
+            let mut sources = Vec::new();
+            for i in members {
+                let mesh = &self.meshes[i];
+                let Some(mesh_data) = mesh.read_back(gl) else {
+                    continue;
+                };
+                data.append_transformed(&mesh_data, &mesh.model_matrix);
+                sources.push(i);
+            }
+            if sources.len() < 2 {
+                continue;
+            }
+            let mut mesh = Mesh::from_data(gl, &data)?;
+            mesh.set_material(material);
+            mesh.set_flip_uv_v(flip_uv_v);
+            // Meshes from the same file share its name, which is kept as is.
+            let mut names: Vec<&str> = Vec::new();
+            for name in sources.iter().filter_map(|&i| self.meshes[i].name()) {
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+            if !names.is_empty() {
+                mesh.set_name(names.join(", "));
+            }
+            merged.push(mesh);
+            for i in sources {
+                merged_away[i] = true;
+            }
+        }
+
+        let mut new_ids = Vec::with_capacity(self.meshes.len());
+        let mut kept = Vec::with_capacity(self.meshes.len());
+        for (mesh, merged_away) in self.meshes.drain(..).zip(&merged_away) {
+            if *merged_away {
+                mesh.delete(gl);
+                new_ids.push(None);
+            } else {
+                new_ids.push(Some(MeshId(kept.len())));
+                kept.push(mesh);
+            }
+        }
+        let saved = merged_away.iter().filter(|&&merged| merged).count() - merged.len();
+        self.meshes = kept;
+        self.meshes.extend(merged);
+        for node in &mut self.nodes {
+            node.mesh = node.mesh.and_then(|mesh| new_ids[mesh.0]);
+        }
+        Ok(saved)
+    }
+
+    /// The mesh whose triangles, after its `model_matrix`, a ray from `ray_origin` along
+    /// `ray_dir` hits first.
+    pub fn pick(&self, ray_origin: Vec3, ray_dir: Vec3) -> Option<MeshId> {
+        pick_closest(
+            self.meshes.iter().map(|mesh| (mesh, mesh.model_matrix)),
+            ray_origin,
+            ray_dir,
+        )
+    }
+
+    /// Bounds of every mesh after its `model_matrix`, or `None` if the scene is empty.
+    pub fn bounds(&self) -> Option<Aabb> {
+        self.meshes
+            .iter()
+            .filter_map(|mesh| Some(mesh.bounds()?.transformed(&mesh.model_matrix)))
+            .reduce(|a, b| a.union(&b))
+    }
+
+    /// Moves the scene's bounding box to be centered on the origin and scales it uniformly so its
+    /// largest side is 1. Returns the transform applied to every mesh's `model_matrix`, whose
+    /// inverse undoes it.
+    pub fn normalize_to_unit_cube(&mut self) -> Mat4 {
+        let Some(bounds) = self.bounds() else {
+            return Mat4::IDENTITY;
+        };
+        let largest_side = bounds.size().max_element();
+        // A single point or a flat line can't be scaled up to a cube, only centered.
+        let scale = if largest_side > 0.0 {
+            1.0 / largest_side
+        } else {
+            1.0
+        };
+        let transform =
+            Mat4::from_scale(Vec3::splat(scale)) * Mat4::from_translation(-bounds.center());
+        for mesh in &mut self.meshes {
+            mesh.model_matrix = transform * mesh.model_matrix;
+        }
+        transform
+    }
+}
+
+/// The first mesh hit by the ray, with each mesh transformed by the matrix paired with it.
+pub(crate) fn pick_closest<'a>(
+    meshes: impl IntoIterator<Item = (&'a Mesh, Mat4)>,
+    ray_origin: Vec3,
+    ray_dir: Vec3,
+) -> Option<MeshId> {
+    meshes
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, (mesh, model))| {
+            Some((MeshId(i), mesh.intersect_ray(&model, ray_origin, ray_dir)?))
+        })
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(id, _)| id)
+}
+
+fn load_primitive(
+    gl: &TracedGl,
+    primitive: &gltf::Primitive,
+    buffers: &[gltf::buffer::Data],
+    images: &[RgbaImage],
+) -> Result<Option<Mesh>, ModelLoadingError> {
+    let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+    let Some(positions) = reader.read_positions() else {
+        return Ok(None);
+    };
+    let positions: Vec<Vec3> = positions.map(Vec3::from).collect();
+
+    let pbr = primitive.material().pbr_metallic_roughness();
+    let base_color = Vec4::from(pbr.base_color_factor()).truncate();
+
+    let vertices: Vec<Vertex> = positions
+        .iter()
+        .map(|&position| Vertex {
+            position,
+            color: base_color,
+            ..Default::default()
+        })
+        .collect();
+    let indices: Vec<u32> = match reader.read_indices() {
+        Some(indices) => indices.into_u32().collect(),
+        None => (0..vertices.len() as u32).collect(),
+    };
+    // Checked before anything indexes the vertices with them.
+    validate_indices(&indices, vertices.len(), PrimitiveMode::Triangles)
+        .with_context(|| format!("primitive {} of the glTF mesh", primitive.index()))?;
+    let mut data = MeshData {
+        vertices,
+        indices,
+        ..Default::default()
+    };
+
+    if let Some(uvs) = reader.read_tex_coords(0) {
+        for (vertex, uv) in data.vertices.iter_mut().zip(uvs.into_f32()) {
+            vertex.uv = Vec2::from(uv);
+        }
+    }
+    if let Some(colors) = reader.read_colors(0) {
+        for (vertex, color) in data.vertices.iter_mut().zip(colors.into_rgb_f32()) {
+            vertex.color *= Vec3::from(color);
+        }
+    }
+    match reader.read_normals() {
+        Some(normals) => {
+            for (vertex, normal) in data.vertices.iter_mut().zip(normals) {
+                vertex.normal = Vec3::from(normal);
+            }
+        }
+        // glTF says primitives without normals are flat shaded.
+        None => data.compute_flat_normals(),
+    }
+
+    let mut mesh = Mesh::from_data(gl, &data)?;
+    if primitive.material().alpha_mode() == gltf::material::AlphaMode::Blend {
+        mesh.set_material(Material {
+            alpha: pbr.base_color_factor()[3],
+            ..mesh.material().clone()
+        });
+    }
+    if let Some(info) = pbr.base_color_texture() {
+        let image = &images[info.texture().source().index()];
+        match Texture::from_image(gl, image) {
+            Ok(texture) => mesh.set_texture(gl, texture),
+            Err(err) => {
+                mesh.delete(gl);
+                return Err(err);
+            }
+        }
+    }
+    Ok(Some(mesh))
+}
+
+/// Uploads the faces of one `usemtl` group, with its material if it has one. OBJ UVs start at the
+/// bottom of the texture, so V is flipped.
+fn load_obj_group(
+    gl: &TracedGl,
+    group: ObjGroup,
+    mtl: Option<&mtl::MtlMaterial>,
+    fetch: &Fetch,
+) -> Result<Mesh, ModelLoadingError> {
+    let mut data = group.data;
+    if !data.has_normals() {
+        data.compute_smooth_normals();
+    }
+    let mut mesh = Mesh::from_data(gl, &data)?;
+    mesh.set_flip_uv_v(true);
+    if let Some(name) = group.material {
+        mesh.set_name(name);
+    }
+    let Some(mtl) = mtl else {
+        return Ok(mesh);
+    };
+    mesh.set_material(mtl.material.clone());
+    if let Some(path) = &mtl.diffuse_texture {
+        let name = path.to_string_lossy();
+        let image = fetch(&name)
+            .and_then(|bytes| {
+                image::load_from_memory(&bytes).with_context(|| format!("texture {name:?}"))
+            })
+            .map(|image| image.to_rgba8())
+            .unwrap_or_else(|err| {
+                log::warn!("{err:#}, using a placeholder texture");
+                RgbaImage::from_pixel(1, 1, PLACEHOLDER_COLOR)
+            });
+        match Texture::from_image(gl, &image) {
+            Ok(texture) => mesh.set_texture(gl, texture),
+            Err(err) => {
+                mesh.delete(gl);
+                return Err(err);
+            }
+        }
+    }
+    Ok(mesh)
+}
+
+/// Reads every buffer of the document, from the binary chunk of a `.glb` file, a `data:` URI or
+/// `fetch`.
+fn load_buffers(
+    document: &gltf::Document,
+    mut blob: Option<Vec<u8>>,
+    fetch: &Fetch,
+) -> Result<Vec<gltf::buffer::Data>, ModelLoadingError> {
+    document
+        .buffers()
+        .map(|buffer| {
+            let index = buffer.index();
+            let data = match buffer.source() {
+                gltf::buffer::Source::Bin => blob
+                    .take()
+                    .with_context(|| format!("buffer {index} refers to a missing binary chunk"))?,
+                gltf::buffer::Source::Uri(uri) => match uri.strip_prefix("data:") {
+                    Some(data) => decode_data_uri(data)?.0,
+                    None => fetch(uri).with_context(|| format!("buffer {index}"))?,
+                },
+            };
+            ensure!(
+                data.len() >= buffer.length(),
+                "buffer {index} is {} bytes, shorter than the {} it declares",
+                data.len(),
+                buffer.length()
+            );
+            Ok(gltf::buffer::Data(data))
+        })
+        .collect()
+}
+
+/// The bytes of a base64 `data:` URI, given what follows `data:`, and the MIME type it declares.
+fn decode_data_uri(data: &str) -> Result<(Vec<u8>, Option<&str>), ModelLoadingError> {
+    let (header, payload) = data.split_once(',').context("malformed data URI")?;
+    let Some(declared) = header.strip_suffix(";base64") else {
+        bail!(UnsupportedFormat, "only base64 data URIs are supported");
+    };
+    let bytes = base64::decode(payload).context("invalid base64 in data URI")?;
+    Ok((bytes, (!declared.is_empty()).then_some(declared)))
+}
+
+/// Decodes every image in the document, in order, standing in a placeholder for any that fail.
+fn load_images(
+    document: &gltf::Document,
+    buffers: &[gltf::buffer::Data],
+    fetch: &Fetch,
+) -> Vec<RgbaImage> {
+    document
+        .images()
+        .map(|image| {
+            load_image(&image, buffers, fetch).unwrap_or_else(|err| {
+                log::warn!(
+                    "image {}: {err:#}, using a placeholder texture",
+                    image.index()
+                );
+                RgbaImage::from_pixel(1, 1, PLACEHOLDER_COLOR)
+            })
+        })
+        .collect()
+}
+
+/// Reads an image from its buffer view, as in `.glb` files, its `data:` URI or `fetch`, and
+/// decodes it as the declared MIME type.
+fn load_image(
+    image: &gltf::Image,
+    buffers: &[gltf::buffer::Data],
+    fetch: &Fetch,
+) -> Result<RgbaImage, ModelLoadingError> {
+    let (bytes, mime_type): (Cow<[u8]>, Option<&str>) = match image.source() {
+        gltf::image::Source::View { view, mime_type } => {
+            let bytes = buffers[view.buffer().index()]
+                .get(view.offset()..view.offset() + view.length())
+                .context("buffer view is outside its buffer")?;
+            (Cow::Borrowed(bytes), Some(mime_type))
+        }
+        gltf::image::Source::Uri { uri, mime_type } => match uri.strip_prefix("data:") {
+            Some(data) => {
+                let (bytes, declared) = decode_data_uri(data)?;
+                (Cow::Owned(bytes), mime_type.or(declared))
+            }
+            None => (Cow::Owned(fetch(uri)?), mime_type),
+        },
+    };
+
+    let decoded = match mime_type {
+        Some("image/png") => image::load_from_memory_with_format(&bytes, ImageFormat::Png),
+        Some("image/jpeg") => image::load_from_memory_with_format(&bytes, ImageFormat::Jpeg),
+        Some(other) => bail!(UnsupportedFormat, "unsupported image type {other}"),
+        // glTF requires a type for buffer views only, so guess from the contents otherwise.
+        None => image::load_from_memory(&bytes),
+    };
+    let decoded = decoded.context("failed to decode image")?;
+    ensure!(
+        decoded.width() > 0 && decoded.height() > 0,
+        "image is empty"
+    );
+    Ok(decoded.to_rgba8())
+}
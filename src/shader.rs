@@ -0,0 +1,341 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    ffi::CString,
+    fmt, fs,
+    path::Path,
+};
+
+use glam::{Mat3, Mat4, Vec2, Vec3};
+
+use crate::{
+    error::ModelLoadingError,
+    mesh::{CURVATURE_ATTRIBUTE, INSTANCE_MATRIX_ATTRIBUTE, VERTEX_ATTRIBUTES},
+    trace::TracedGl,
+    window::gl,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShaderStage {
+    Vertex,
+    Fragment,
+}
+
+impl ShaderStage {
+    fn gl_enum(self) -> gl::types::GLenum {
+        match self {
+            ShaderStage::Vertex => gl::VERTEX_SHADER,
+            ShaderStage::Fragment => gl::FRAGMENT_SHADER,
+        }
+    }
+}
+
+impl fmt::Display for ShaderStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShaderStage::Vertex => write!(f, "vertex"),
+            ShaderStage::Fragment => write!(f, "fragment"),
+        }
+    }
+}
+
+/// Uniform buffer binding point of the `Matrices` block, which the renderer fills with the camera's
+/// view, projection and view-projection matrices. Every program declaring the block reads it.
+pub(crate) const MATRICES_BINDING: gl::types::GLuint = 0;
+
+/// A linked shader program along with the locations of its active uniforms.
+///
+/// Without direct state access, setting a uniform binds the program as a side effect.
+#[derive(Debug)]
+pub struct ShaderProgram {
+    program: gl::types::GLuint,
+    uniforms: HashMap<String, gl::types::GLint>,
+}
+
+impl ShaderProgram {
+    /// Reads, compiles and links a vertex and fragment shader from disk.
+    pub fn from_files(gl: &TracedGl, vert: &Path, frag: &Path) -> Result<Self, ModelLoadingError> {
+        let read = |path: &Path| fs::read_to_string(path).map_err(ModelLoadingError::file_io(path));
+        Self::from_sources(gl, &read(vert)?, &read(frag)?)
+    }
+
+    pub fn from_sources(gl: &TracedGl, vert: &str, frag: &str) -> Result<Self, ModelLoadingError> {
+        unsafe {
+            let vertex_shader = create_shader(gl, ShaderStage::Vertex, vert)?;
+            let fragment_shader = create_shader(gl, ShaderStage::Fragment, frag)
+                .inspect_err(|_| gl.DeleteShader(vertex_shader))?;
+
+            let program = link_program(gl, &[vertex_shader, fragment_shader]);
+
+            gl.DeleteShader(vertex_shader);
+            gl.DeleteShader(fragment_shader);
+
+            let program = program?;
+            Ok(Self {
+                program,
+                uniforms: active_uniforms(gl, program),
+            })
+        }
+    }
+
+    pub fn id(&self) -> gl::types::GLuint {
+        self.program
+    }
+
+    /// Location of the uniform `name`, or -1 (which GL silently ignores) if the program has no
+    /// such active uniform.
+    pub fn uniform_location(&self, name: &str) -> gl::types::GLint {
+        self.uniforms.get(name).copied().unwrap_or(-1)
+    }
+
+    pub fn set_uniform_mat4(&self, gl: &TracedGl, name: &str, m: &Mat4) {
+        let (location, m) = (self.uniform_location(name), m.to_cols_array());
+        unsafe {
+            if gl.direct_state_access() {
+                gl.ProgramUniformMatrix4fv(self.program, location, 1, gl::FALSE, m.as_ptr());
+            } else {
+                gl.UseProgram(self.program);
+                gl.UniformMatrix4fv(location, 1, gl::FALSE, m.as_ptr());
+            }
+        }
+    }
+
+    pub fn set_uniform_mat3(&self, gl: &TracedGl, name: &str, m: &Mat3) {
+        let (location, m) = (self.uniform_location(name), m.to_cols_array());
+        unsafe {
+            if gl.direct_state_access() {
+                gl.ProgramUniformMatrix3fv(self.program, location, 1, gl::FALSE, m.as_ptr());
+            } else {
+                gl.UseProgram(self.program);
+                gl.UniformMatrix3fv(location, 1, gl::FALSE, m.as_ptr());
+            }
+        }
+    }
+
+    pub fn set_uniform_f32(&self, gl: &TracedGl, name: &str, v: f32) {
+        let location = self.uniform_location(name);
+        unsafe {
+            if gl.direct_state_access() {
+                gl.ProgramUniform1f(self.program, location, v);
+            } else {
+                gl.UseProgram(self.program);
+                gl.Uniform1f(location, v);
+            }
+        }
+    }
+
+    /// Sets an `int` uniform, or which texture unit a sampler reads from.
+    pub fn set_uniform_i32(&self, gl: &TracedGl, name: &str, v: i32) {
+        let location = self.uniform_location(name);
+        unsafe {
+            if gl.direct_state_access() {
+                gl.ProgramUniform1i(self.program, location, v);
+            } else {
+                gl.UseProgram(self.program);
+                gl.Uniform1i(location, v);
+            }
+        }
+    }
+
+    /// Sets a GLSL `bool` uniform, which is set through the integer functions.
+    pub fn set_uniform_bool(&self, gl: &TracedGl, name: &str, v: bool) {
+        let location = self.uniform_location(name);
+        unsafe {
+            if gl.direct_state_access() {
+                gl.ProgramUniform1i(self.program, location, v.into());
+            } else {
+                gl.UseProgram(self.program);
+                gl.Uniform1i(location, v.into());
+            }
+        }
+    }
+
+    pub fn set_uniform_vec2(&self, gl: &TracedGl, name: &str, v: Vec2) {
+        let location = self.uniform_location(name);
+        unsafe {
+            if gl.direct_state_access() {
+                gl.ProgramUniform2f(self.program, location, v.x, v.y);
+            } else {
+                gl.UseProgram(self.program);
+                gl.Uniform2f(location, v.x, v.y);
+            }
+        }
+    }
+
+    pub fn set_uniform_vec3(&self, gl: &TracedGl, name: &str, v: Vec3) {
+        let location = self.uniform_location(name);
+        unsafe {
+            if gl.direct_state_access() {
+                gl.ProgramUniform3f(self.program, location, v.x, v.y, v.z);
+            } else {
+                gl.UseProgram(self.program);
+                gl.Uniform3f(location, v.x, v.y, v.z);
+            }
+        }
+    }
+
+    /// Deletes the GL program. The `ShaderProgram` must not be used afterwards.
+    pub fn delete(&self, gl: &TracedGl) {
+        unsafe { gl.DeleteProgram(self.program) };
+    }
+}
+
+/// Shader programs registered by name, e.g. to switch between lit and unlit shading at runtime.
+///
+/// Every program is linked with the same attribute locations, so any of them can draw any mesh.
+#[derive(Debug, Default)]
+pub struct ShaderLibrary {
+    programs: BTreeMap<String, ShaderProgram>,
+}
+
+impl ShaderLibrary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `program` under `name`, returning the program it replaced. The returned program
+    /// is no longer owned by the library, so the caller has to delete it.
+    pub fn insert(
+        &mut self,
+        name: impl Into<String>,
+        program: ShaderProgram,
+    ) -> Option<ShaderProgram> {
+        self.programs.insert(name.into(), program)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ShaderProgram> {
+        self.programs.get(name)
+    }
+
+    /// Names of the registered programs, in sorted order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.programs.keys().map(String::as_str)
+    }
+
+    /// Deletes every program in the library. It must not be used afterwards.
+    pub fn delete(&self, gl: &TracedGl) {
+        for program in self.programs.values() {
+            program.delete(gl);
+        }
+    }
+}
+
+/// Queries every active uniform of `program`. Arrays are stored under both `name[0]` and `name`.
+unsafe fn active_uniforms(
+    gl: &TracedGl,
+    program: gl::types::GLuint,
+) -> HashMap<String, gl::types::GLint> {
+    let mut count = 0;
+    gl.GetProgramiv(program, gl::ACTIVE_UNIFORMS, &mut count);
+    let mut max_len = 0;
+    gl.GetProgramiv(program, gl::ACTIVE_UNIFORM_MAX_LENGTH, &mut max_len);
+
+    let mut uniforms = HashMap::new();
+    for index in 0..count as gl::types::GLuint {
+        let mut name = vec![0u8; max_len.max(1) as usize];
+        let (mut len, mut size, mut ty) = (0, 0, 0);
+        gl.GetActiveUniform(
+            program,
+            index,
+            max_len,
+            &mut len,
+            &mut size,
+            &mut ty,
+            name.as_mut_ptr().cast(),
+        );
+        // Keep the terminating nul so the name can be passed back to GL as-is.
+        name.truncate(len as usize + 1);
+        let location = gl.GetUniformLocation(program, name.as_ptr().cast());
+        name.pop();
+
+        let name = String::from_utf8_lossy(&name).into_owned();
+        if let Some(base) = name.strip_suffix("[0]") {
+            uniforms.insert(base.to_owned(), location);
+            // Arrays of basic types are listed once, by their first element.
+            for element in 1..size {
+                let element_name = format!("{base}[{element}]");
+                let c_name =
+                    CString::new(element_name.as_str()).expect("uniform names have no nul");
+                let location = gl.GetUniformLocation(program, c_name.as_ptr());
+                uniforms.insert(element_name, location);
+            }
+        }
+        uniforms.insert(name, location);
+    }
+    uniforms
+}
+
+/// Compiles a single shader stage.
+pub(crate) unsafe fn create_shader(
+    gl: &TracedGl,
+    stage: ShaderStage,
+    source: &str,
+) -> Result<gl::types::GLuint, ModelLoadingError> {
+    let shader = gl.CreateShader(stage.gl_enum());
+    gl.ShaderSource(
+        shader,
+        1,
+        [source.as_ptr().cast()].as_ptr(),
+        [source.len() as gl::types::GLint].as_ptr(),
+    );
+    gl.CompileShader(shader);
+
+    let mut status = 0;
+    gl.GetShaderiv(shader, gl::COMPILE_STATUS, &mut status);
+    if status == gl::FALSE as gl::types::GLint {
+        let log = shader_info_log(gl, shader);
+        gl.DeleteShader(shader);
+        return Err(ModelLoadingError::ShaderCompile { stage, log });
+    }
+    Ok(shader)
+}
+
+/// Links `shaders` into a program. The shaders are left for the caller to delete.
+pub(crate) unsafe fn link_program(
+    gl: &TracedGl,
+    shaders: &[gl::types::GLuint],
+) -> Result<gl::types::GLuint, ModelLoadingError> {
+    let program = gl.CreateProgram();
+    for &shader in shaders {
+        gl.AttachShader(program, shader);
+    }
+    for (location, name) in VERTEX_ATTRIBUTES
+        .into_iter()
+        .chain([INSTANCE_MATRIX_ATTRIBUTE, CURVATURE_ATTRIBUTE])
+    {
+        gl.BindAttribLocation(program, location, name.as_ptr());
+    }
+    gl.LinkProgram(program);
+
+    let mut status = 0;
+    gl.GetProgramiv(program, gl::LINK_STATUS, &mut status);
+    if status == gl::FALSE as gl::types::GLint {
+        let log = program_info_log(gl, program);
+        gl.DeleteProgram(program);
+        return Err(ModelLoadingError::ShaderLink { log });
+    }
+    let matrices = gl.GetUniformBlockIndex(program, c"Matrices".as_ptr());
+    if matrices != gl::INVALID_INDEX {
+        gl.UniformBlockBinding(program, matrices, MATRICES_BINDING);
+    }
+    Ok(program)
+}
+
+unsafe fn shader_info_log(gl: &TracedGl, shader: gl::types::GLuint) -> String {
+    let mut len = 0;
+    gl.GetShaderiv(shader, gl::INFO_LOG_LENGTH, &mut len);
+    let mut log = vec![0u8; len.max(1) as usize];
+    let mut written = 0;
+    gl.GetShaderInfoLog(shader, len, &mut written, log.as_mut_ptr().cast());
+    log.truncate(written as usize);
+    String::from_utf8_lossy(&log).trim_end().to_owned()
+}
+
+unsafe fn program_info_log(gl: &TracedGl, program: gl::types::GLuint) -> String {
+    let mut len = 0;
+    gl.GetProgramiv(program, gl::INFO_LOG_LENGTH, &mut len);
+    let mut log = vec![0u8; len.max(1) as usize];
+    let mut written = 0;
+    gl.GetProgramInfoLog(program, len, &mut written, log.as_mut_ptr().cast());
+    log.truncate(written as usize);
+    String::from_utf8_lossy(&log).trim_end().to_owned()
+}
@@ -0,0 +1,277 @@
+use std::{fs, path::Path};
+
+use glam::{vec3, Vec3};
+
+use crate::{
+    error::{bail, ensure, Context, ModelLoadingError},
+    mesh::{MeshData, Vertex},
+};
+
+/// Color given to every vertex, as STL files don't store one.
+pub const DEFAULT_COLOR: Vec3 = vec3(0.8, 0.8, 0.8);
+
+/// Size of the header a binary STL starts with, followed by a `u32` triangle count.
+const BINARY_HEADER_SIZE: usize = 80;
+/// Normal, three vertices and a 2 byte attribute count, per triangle.
+const BINARY_TRIANGLE_SIZE: usize = 50;
+
+/// Parses an ASCII or binary STL file into a flat shaded triangle list.
+///
+/// Each triangle gets its own three vertices sharing the facet normal, or one computed from the
+/// winding when the file leaves it zero.
+pub fn load_stl(path: &Path) -> Result<MeshData, ModelLoadingError> {
+    let bytes = fs::read(path).map_err(ModelLoadingError::file_io(path))?;
+    parse_stl(&bytes).with_context(|| format!("failed to parse {}", path.display()))
+}
+
+pub fn parse_stl(bytes: &[u8]) -> Result<MeshData, ModelLoadingError> {
+    let triangles = if is_binary(bytes) {
+        parse_binary(bytes)?
+    } else {
+        let source = std::str::from_utf8(bytes).context("ASCII STL is not valid UTF-8")?;
+        parse_ascii(source)?
+    };
+
+    if triangles.is_empty() {
+        bail!("no facets found");
+    }
+    let vertices: Vec<Vertex> = triangles
+        .into_iter()
+        .flat_map(|(normal, corners)| {
+            let normal = match normal.try_normalize() {
+                Some(normal) => normal,
+                None => (corners[1] - corners[0])
+                    .cross(corners[2] - corners[0])
+                    .normalize_or_zero(),
+            };
+            corners.map(|position| Vertex {
+                position,
+                color: DEFAULT_COLOR,
+                normal,
+                ..Default::default()
+            })
+        })
+        .collect();
+    let indices = (0..vertices.len() as u32).collect();
+    Ok(MeshData {
+        vertices,
+        indices,
+        ..Default::default()
+    })
+}
+
+/// ASCII files start with `solid`, but so do plenty of binary ones, so a file whose size matches
+/// the triangle count in its header is taken to be binary either way. So is one with a NUL byte,
+/// which ASCII files never have, to report truncated binary files as such.
+fn is_binary(bytes: &[u8]) -> bool {
+    if let Some(count) = bytes.get(BINARY_HEADER_SIZE..BINARY_HEADER_SIZE + 4) {
+        let count = u32::from_le_bytes(count.try_into().unwrap()) as u64;
+        let expected = (BINARY_HEADER_SIZE + 4) as u64 + count * BINARY_TRIANGLE_SIZE as u64;
+        if expected == bytes.len() as u64 {
+            return true;
+        }
+    }
+    !bytes.trim_ascii_start().starts_with(b"solid") || bytes.contains(&0)
+}
+
+fn parse_binary(bytes: &[u8]) -> Result<Vec<(Vec3, [Vec3; 3])>, ModelLoadingError> {
+    let count = bytes
+        .get(BINARY_HEADER_SIZE..BINARY_HEADER_SIZE + 4)
+        .context("binary STL is shorter than its header")?;
+    let count = u32::from_le_bytes(count.try_into().unwrap()) as usize;
+    let body = &bytes[BINARY_HEADER_SIZE + 4..];
+    ensure!(
+        body.len() / BINARY_TRIANGLE_SIZE >= count,
+        "binary STL is truncated: header says {count} triangles but there is only room for {}",
+        body.len() / BINARY_TRIANGLE_SIZE
+    );
+
+    let read_vec3 = |bytes: &[u8]| {
+        let component = |i: usize| f32::from_le_bytes(bytes[i * 4..i * 4 + 4].try_into().unwrap());
+        vec3(component(0), component(1), component(2))
+    };
+    Ok(body
+        .chunks_exact(BINARY_TRIANGLE_SIZE)
+        .take(count)
+        .map(|triangle| {
+            let normal = read_vec3(&triangle[0..12]);
+            let corners = [12, 24, 36].map(|offset| read_vec3(&triangle[offset..offset + 12]));
+            (normal, corners)
+        })
+        .collect())
+}
+
+fn parse_ascii(source: &str) -> Result<Vec<(Vec3, [Vec3; 3])>, ModelLoadingError> {
+    let mut triangles = Vec::new();
+    let mut facet: Option<(Vec3, Vec<Vec3>)> = None;
+
+    for (line_no, line) in source.lines().enumerate() {
+        let line_no = line_no + 1;
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+
+        match keyword {
+            "facet" => {
+                ensure!(
+                    facet.is_none(),
+                    "line {line_no}: facet inside another facet"
+                );
+                let normal = match tokens.next() {
+                    Some("normal") => parse_vec3(&mut tokens).context(format!("line {line_no}"))?,
+                    _ => Vec3::ZERO,
+                };
+                facet = Some((normal, Vec::new()));
+            }
+            "vertex" => {
+                let Some((_, corners)) = facet.as_mut() else {
+                    bail!("line {line_no}: vertex outside a facet");
+                };
+                corners.push(parse_vec3(&mut tokens).context(format!("line {line_no}"))?);
+            }
+            "endfacet" => {
+                let Some((normal, corners)) = facet.take() else {
+                    bail!("line {line_no}: endfacet without a facet");
+                };
+                let Ok(corners) = <[Vec3; 3]>::try_from(corners) else {
+                    bail!("line {line_no}: facet doesn't have exactly 3 vertices");
+                };
+                triangles.push((normal, corners));
+            }
+            // The solid's name and the loop markers don't affect geometry.
+            _ => (),
+        }
+    }
+
+    ensure!(facet.is_none(), "file ends inside a facet");
+    Ok(triangles)
+}
+
+fn parse_vec3<'a>(tokens: &mut impl Iterator<Item = &'a str>) -> Result<Vec3, ModelLoadingError> {
+    let mut component = || -> Result<f32, ModelLoadingError> {
+        let token = tokens.next().context("expected 3 components")?;
+        token
+            .parse()
+            .with_context(|| format!("invalid number {token:?}"))
+    };
+    Ok(vec3(component()?, component()?, component()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A binary STL of `triangles`, each a normal and three corners, with `header` at the start of
+    /// its header.
+    fn binary_stl(header: &[u8], triangles: &[(Vec3, [Vec3; 3])]) -> Vec<u8> {
+        let mut bytes = header.to_vec();
+        bytes.resize(BINARY_HEADER_SIZE, b' ');
+        bytes.extend((triangles.len() as u32).to_le_bytes());
+        for (normal, corners) in triangles {
+            for vector in [*normal].iter().chain(corners) {
+                bytes.extend(vector.to_array().iter().flat_map(|c| c.to_le_bytes()));
+            }
+            bytes.extend([0, 0]);
+        }
+        bytes
+    }
+
+    const TRIANGLE: [Vec3; 3] = [Vec3::ZERO, Vec3::X, Vec3::Y];
+
+    fn assert_invalid(bytes: &[u8], message: &str) {
+        let err = parse_stl(bytes).unwrap_err();
+        assert!(
+            matches!(err, ModelLoadingError::InvalidData(_)),
+            "{err:?} isn't invalid data"
+        );
+        assert!(format!("{err:#}").contains(message), "{err:#}");
+    }
+
+    #[test]
+    fn binary_triangles_get_their_facet_normal() {
+        let square = [
+            (Vec3::Z, TRIANGLE),
+            (Vec3::Z * 2.0, [Vec3::X, vec3(1.0, 1.0, 0.0), Vec3::Y]),
+        ];
+        let data = parse_stl(&binary_stl(b"binary", &square)).unwrap();
+        assert_eq!(data.indices, [0, 1, 2, 3, 4, 5]);
+        let positions: Vec<Vec3> = data.vertices.iter().map(|v| v.position).collect();
+        assert_eq!(&positions[..3], TRIANGLE);
+        assert_eq!(positions[4], vec3(1.0, 1.0, 0.0));
+        assert!(data.vertices.iter().all(|v| v.normal == Vec3::Z));
+        assert!(data.vertices.iter().all(|v| v.color == DEFAULT_COLOR));
+    }
+
+    #[test]
+    fn ascii_facets_are_read_and_missing_normals_computed() {
+        let stl = "\
+solid square
+  facet normal 0 0 1
+    outer loop
+      vertex 0 0 0
+      vertex 1 0 0
+      vertex 0 1 0
+    endloop
+  endfacet
+  facet normal 0 0 0
+    outer loop
+      vertex 1 0 0
+      vertex 1 1 0
+      vertex 0 1 0
+    endloop
+  endfacet
+endsolid square
+";
+        let data = parse_stl(stl.as_bytes()).unwrap();
+        assert_eq!(data.vertices.len(), 6);
+        assert_eq!(data.vertices[1].position, Vec3::X);
+        assert_eq!(data.vertices[4].position, vec3(1.0, 1.0, 0.0));
+        // The second facet's normal comes from its counter-clockwise winding.
+        assert!(data.vertices.iter().all(|v| v.normal == Vec3::Z));
+    }
+
+    #[test]
+    fn binary_files_starting_with_solid_are_binary() {
+        let bytes = binary_stl(b"solid exported by a CAD tool", &[(Vec3::Z, TRIANGLE)]);
+        let data = parse_stl(&bytes).unwrap();
+        assert_eq!(data.vertices.len(), 3);
+        assert_eq!(data.vertices[2].position, Vec3::Y);
+    }
+
+    #[test]
+    fn truncated_binary_files_are_rejected() {
+        let bytes = binary_stl(b"binary", &[(Vec3::Z, TRIANGLE); 2]);
+        assert_invalid(
+            &bytes[..bytes.len() - 1],
+            "header says 2 triangles but there is only room for 1",
+        );
+        assert_invalid(&bytes[..40], "shorter than its header");
+        // Even with `solid` at the start, the NUL bytes give it away as binary.
+        let bytes = binary_stl(b"solid", &[(Vec3::Z, TRIANGLE); 2]);
+        assert_invalid(&bytes[..bytes.len() - 10], "binary STL is truncated");
+    }
+
+    #[test]
+    fn malformed_ascii_facets_are_rejected() {
+        let facet =
+            "facet normal 0 0 1\nouter loop\nvertex 0 0 0\nvertex 1 0 0\nvertex 0 1 0\nendloop\n";
+        assert_invalid(
+            format!("solid a\n{facet}{facet}endfacet\nendsolid a\n").as_bytes(),
+            "line 8: facet inside another facet",
+        );
+        assert_invalid(
+            format!("solid a\n{facet}").as_bytes(),
+            "file ends inside a facet",
+        );
+        assert_invalid(
+            b"solid a\nfacet normal 0 0 1\nvertex 0 0 0\nvertex 1 0 0\nendfacet\nendsolid a\n",
+            "line 5: facet doesn't have exactly 3 vertices",
+        );
+        assert_invalid(
+            b"solid a\nfacet normal 0 0 1\nvertex 0 zero 0\n",
+            "invalid number \"zero\"",
+        );
+        assert_invalid(b"solid a\nendsolid a\n", "no facets found");
+    }
+}
@@ -0,0 +1,339 @@
+use std::path::{Path, PathBuf};
+
+use image::RgbaImage;
+
+use crate::{
+    error::{ensure, ModelLoadingError},
+    trace::TracedGl,
+    window::gl,
+};
+
+/// An RGBA8 2D texture with a full mipmap chain.
+#[derive(Debug)]
+pub struct Texture {
+    texture: gl::types::GLuint,
+}
+
+impl Texture {
+    /// Loads a PNG or JPEG from disk.
+    pub fn from_file(gl: &TracedGl, path: &Path) -> Result<Self, ModelLoadingError> {
+        let image = image::open(path)
+            .map_err(ModelLoadingError::image_file(path))?
+            .to_rgba8();
+        Self::from_image(gl, &image)
+    }
+
+    pub fn from_image(gl: &TracedGl, image: &RgbaImage) -> Result<Self, ModelLoadingError> {
+        let (width, height) = image.dimensions();
+        let levels = width.max(height).max(1).ilog2() + 1;
+        let (width, height) = (width as gl::types::GLsizei, height as gl::types::GLsizei);
+        let parameters = [
+            (gl::TEXTURE_WRAP_S, gl::REPEAT),
+            (gl::TEXTURE_WRAP_T, gl::REPEAT),
+            (gl::TEXTURE_MIN_FILTER, gl::LINEAR_MIPMAP_LINEAR),
+            (gl::TEXTURE_MAG_FILTER, gl::LINEAR),
+        ];
+        unsafe {
+            let mut texture = 0;
+            if gl.direct_state_access() {
+                gl.CreateTextures(gl::TEXTURE_2D, 1, &mut texture);
+            } else {
+                gl.GenTextures(1, &mut texture);
+            }
+            if texture == 0 {
+                log::error!("failed to allocate {width}x{height} texture");
+                return Err(ModelLoadingError::GlError(gl.GetError()));
+            }
+
+            // Rows are tightly packed, which breaks the default 4 byte alignment for odd widths.
+            gl.PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            if gl.direct_state_access() {
+                gl.TextureStorage2D(
+                    texture,
+                    levels as gl::types::GLsizei,
+                    gl::RGBA8,
+                    width,
+                    height,
+                );
+                gl.TextureSubImage2D(
+                    texture,
+                    0,
+                    0,
+                    0,
+                    width,
+                    height,
+                    gl::RGBA,
+                    gl::UNSIGNED_BYTE,
+                    image.as_ptr().cast(),
+                );
+                gl.GenerateTextureMipmap(texture);
+                for (name, value) in parameters {
+                    gl.TextureParameteri(texture, name, value as i32);
+                }
+            } else {
+                gl.BindTexture(gl::TEXTURE_2D, texture);
+                gl.TexImage2D(
+                    gl::TEXTURE_2D,
+                    0,
+                    gl::RGBA8 as i32,
+                    width,
+                    height,
+                    0,
+                    gl::RGBA,
+                    gl::UNSIGNED_BYTE,
+                    image.as_ptr().cast(),
+                );
+                gl.GenerateMipmap(gl::TEXTURE_2D);
+                for (name, value) in parameters {
+                    gl.TexParameteri(gl::TEXTURE_2D, name, value as i32);
+                }
+                gl.BindTexture(gl::TEXTURE_2D, 0);
+            }
+
+            Ok(Self { texture })
+        }
+    }
+
+    /// A 1x1 texture of a single color, used for meshes without a texture of their own.
+    pub fn solid(gl: &TracedGl, rgba: [u8; 4]) -> Result<Self, ModelLoadingError> {
+        Self::from_image(gl, &RgbaImage::from_pixel(1, 1, image::Rgba(rgba)))
+    }
+
+    pub fn id(&self) -> gl::types::GLuint {
+        self.texture
+    }
+
+    /// Reads the full size image back from the GPU, e.g. to upload it again on another context.
+    pub fn read_back(&self, gl: &TracedGl) -> RgbaImage {
+        unsafe {
+            if gl.direct_state_access() {
+                let mut image = image_of_level_size(|name, value| {
+                    gl.GetTextureLevelParameteriv(self.texture, 0, name, value)
+                });
+                gl.GetTextureImage(
+                    self.texture,
+                    0,
+                    gl::RGBA,
+                    gl::UNSIGNED_BYTE,
+                    image.len() as gl::types::GLsizei,
+                    image.as_mut_ptr().cast(),
+                );
+                image
+            } else {
+                gl.BindTexture(gl::TEXTURE_2D, self.texture);
+                let mut image = image_of_level_size(|name, value| {
+                    gl.GetTexLevelParameteriv(gl::TEXTURE_2D, 0, name, value)
+                });
+                gl.GetTexImage(
+                    gl::TEXTURE_2D,
+                    0,
+                    gl::RGBA,
+                    gl::UNSIGNED_BYTE,
+                    image.as_mut_ptr().cast(),
+                );
+                gl.BindTexture(gl::TEXTURE_2D, 0);
+                image
+            }
+        }
+    }
+
+    /// Binds the texture to texture unit `unit`.
+    pub fn bind(&self, gl: &TracedGl, unit: gl::types::GLuint) {
+        unsafe {
+            if gl.direct_state_access() {
+                gl.BindTextureUnit(unit, self.texture);
+            } else {
+                gl.ActiveTexture(gl::TEXTURE0 + unit);
+                gl.BindTexture(gl::TEXTURE_2D, self.texture);
+            }
+        }
+    }
+
+    /// Deletes the GL texture. The `Texture` must not be used afterwards.
+    pub fn delete(&self, gl: &TracedGl) {
+        unsafe { gl.DeleteTextures(1, &self.texture) };
+    }
+}
+
+/// An RGBA8 cube map, e.g. for a skybox. Faces are in GL's order: +X, -X, +Y, -Y, +Z, -Z.
+#[derive(Debug)]
+pub struct CubeMap {
+    texture: gl::types::GLuint,
+}
+
+impl CubeMap {
+    /// Loads the six faces from PNG or JPEG files.
+    pub fn from_files(gl: &TracedGl, faces: &[PathBuf; 6]) -> Result<Self, ModelLoadingError> {
+        let mut images = Vec::with_capacity(6);
+        for path in faces {
+            let image = image::open(path)
+                .map_err(ModelLoadingError::image_file(path))?
+                .to_rgba8();
+            images.push(image);
+        }
+        let images: [RgbaImage; 6] = images.try_into().expect("one image per face");
+        Self::from_images(gl, &images)
+    }
+
+    /// Uploads the six faces, which must all be the same square size.
+    pub fn from_images(gl: &TracedGl, faces: &[RgbaImage; 6]) -> Result<Self, ModelLoadingError> {
+        let (size, height) = faces[0].dimensions();
+        ensure!(
+            size == height,
+            InvalidArgument,
+            "cube map faces must be square, face 0 is {size}x{height}"
+        );
+        for (index, face) in faces.iter().enumerate() {
+            let (width, height) = face.dimensions();
+            ensure!(
+                (width, height) == (size, size),
+                InvalidArgument,
+                "cube map face {index} is {width}x{height}, but face 0 is {size}x{size}"
+            );
+        }
+        let size = size as gl::types::GLsizei;
+        let parameters = [
+            (gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE),
+            (gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE),
+            (gl::TEXTURE_WRAP_R, gl::CLAMP_TO_EDGE),
+            (gl::TEXTURE_MIN_FILTER, gl::LINEAR),
+            (gl::TEXTURE_MAG_FILTER, gl::LINEAR),
+        ];
+        unsafe {
+            let mut texture = 0;
+            if gl.direct_state_access() {
+                gl.CreateTextures(gl::TEXTURE_CUBE_MAP, 1, &mut texture);
+            } else {
+                gl.GenTextures(1, &mut texture);
+            }
+            if texture == 0 {
+                log::error!("failed to allocate {size}x{size} cube map");
+                return Err(ModelLoadingError::GlError(gl.GetError()));
+            }
+
+            gl.PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            if gl.direct_state_access() {
+                gl.TextureStorage2D(texture, 1, gl::RGBA8, size, size);
+                // The faces are the layers of the cube map, in GL's order.
+                for (layer, face) in faces.iter().enumerate() {
+                    gl.TextureSubImage3D(
+                        texture,
+                        0,
+                        0,
+                        0,
+                        layer as gl::types::GLint,
+                        size,
+                        size,
+                        1,
+                        gl::RGBA,
+                        gl::UNSIGNED_BYTE,
+                        face.as_ptr().cast(),
+                    );
+                }
+                for (name, value) in parameters {
+                    gl.TextureParameteri(texture, name, value as i32);
+                }
+            } else {
+                gl.BindTexture(gl::TEXTURE_CUBE_MAP, texture);
+                for (index, face) in faces.iter().enumerate() {
+                    gl.TexImage2D(
+                        gl::TEXTURE_CUBE_MAP_POSITIVE_X + index as gl::types::GLenum,
+                        0,
+                        gl::RGBA8 as i32,
+                        size,
+                        size,
+                        0,
+                        gl::RGBA,
+                        gl::UNSIGNED_BYTE,
+                        face.as_ptr().cast(),
+                    );
+                }
+                for (name, value) in parameters {
+                    gl.TexParameteri(gl::TEXTURE_CUBE_MAP, name, value as i32);
+                }
+                gl.BindTexture(gl::TEXTURE_CUBE_MAP, 0);
+            }
+
+            Ok(Self { texture })
+        }
+    }
+
+    pub fn id(&self) -> gl::types::GLuint {
+        self.texture
+    }
+
+    /// Reads the six faces back from the GPU, in the order [`CubeMap::from_images`] takes them.
+    pub fn read_back(&self, gl: &TracedGl) -> [RgbaImage; 6] {
+        unsafe {
+            if gl.direct_state_access() {
+                let face = image_of_level_size(|name, value| {
+                    gl.GetTextureLevelParameteriv(self.texture, 0, name, value)
+                });
+                let (width, height) = face.dimensions();
+                std::array::from_fn(|layer| {
+                    let mut face = face.clone();
+                    gl.GetTextureSubImage(
+                        self.texture,
+                        0,
+                        0,
+                        0,
+                        layer as gl::types::GLint,
+                        width as gl::types::GLsizei,
+                        height as gl::types::GLsizei,
+                        1,
+                        gl::RGBA,
+                        gl::UNSIGNED_BYTE,
+                        face.len() as gl::types::GLsizei,
+                        face.as_mut_ptr().cast(),
+                    );
+                    face
+                })
+            } else {
+                gl.BindTexture(gl::TEXTURE_CUBE_MAP, self.texture);
+                let faces = std::array::from_fn(|index| {
+                    let target = gl::TEXTURE_CUBE_MAP_POSITIVE_X + index as gl::types::GLenum;
+                    let mut face = image_of_level_size(|name, value| {
+                        gl.GetTexLevelParameteriv(target, 0, name, value)
+                    });
+                    gl.GetTexImage(
+                        target,
+                        0,
+                        gl::RGBA,
+                        gl::UNSIGNED_BYTE,
+                        face.as_mut_ptr().cast(),
+                    );
+                    face
+                });
+                gl.BindTexture(gl::TEXTURE_CUBE_MAP, 0);
+                faces
+            }
+        }
+    }
+
+    /// Binds the cube map to texture unit `unit`.
+    pub fn bind(&self, gl: &TracedGl, unit: gl::types::GLuint) {
+        unsafe {
+            if gl.direct_state_access() {
+                gl.BindTextureUnit(unit, self.texture);
+            } else {
+                gl.ActiveTexture(gl::TEXTURE0 + unit);
+                gl.BindTexture(gl::TEXTURE_CUBE_MAP, self.texture);
+            }
+        }
+    }
+
+    /// Deletes the GL texture. The `CubeMap` must not be used afterwards.
+    pub fn delete(&self, gl: &TracedGl) {
+        unsafe { gl.DeleteTextures(1, &self.texture) };
+    }
+}
+
+/// A blank image the size of level 0 of a texture, queried with `get_level_parameter`.
+fn image_of_level_size(
+    mut get_level_parameter: impl FnMut(gl::types::GLenum, *mut gl::types::GLint),
+) -> RgbaImage {
+    let (mut width, mut height) = (0, 0);
+    get_level_parameter(gl::TEXTURE_WIDTH, &mut width);
+    get_level_parameter(gl::TEXTURE_HEIGHT, &mut height);
+    RgbaImage::new(width.max(0) as u32, height.max(0) as u32)
+}
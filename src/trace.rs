@@ -0,0 +1,155 @@
+use std::{
+    cell::RefCell,
+    fs::File,
+    io::{self, BufWriter, Write},
+    ops::Deref,
+    path::Path,
+};
+
+use crate::{error::ModelLoadingError, window::gl};
+
+/// A single recorded GL call.
+#[derive(Debug, Clone)]
+pub struct TraceCall {
+    pub name: &'static str,
+    pub args: Vec<(&'static str, String)>,
+    pub ret: Option<String>,
+}
+
+/// Thin wrapper around [`gl::Gl`] that can record every call made through it.
+///
+/// Every GL command has a forwarding method generated by `build.rs`, so calls made on a
+/// `TracedGl` resolve to the wrapper rather than to the inner [`gl::Gl`]. While no trace is active
+/// the only overhead is a flag check per call.
+pub struct TracedGl {
+    gl: gl::Gl,
+    calls: RefCell<Option<Vec<TraceCall>>>,
+    direct_state_access: bool,
+}
+
+impl TracedGl {
+    /// Wraps the function pointers of the current context, checking which GPU object setup path
+    /// it supports.
+    pub fn new(gl: gl::Gl) -> Self {
+        let (mut major, mut minor) = (0, 0);
+        unsafe {
+            gl.GetIntegerv(gl::MAJOR_VERSION, &mut major);
+            gl.GetIntegerv(gl::MINOR_VERSION, &mut minor);
+        }
+        Self {
+            gl,
+            calls: RefCell::new(None),
+            direct_state_access: (major, minor) >= (4, 5),
+        }
+    }
+
+    /// Whether the context has GL 4.5 direct state access. Without it the GPU object helpers fall
+    /// back to binding objects to edit them, which works back to GL 3.3.
+    pub fn direct_state_access(&self) -> bool {
+        self.direct_state_access
+    }
+
+    /// Starts recording, discarding anything recorded by a previous unfinished trace.
+    pub fn begin_trace(&self) {
+        *self.calls.borrow_mut() = Some(Vec::new());
+    }
+
+    /// Stops recording and returns the calls made since [`TracedGl::begin_trace`].
+    pub fn end_trace(&self) -> Option<Vec<TraceCall>> {
+        self.calls.borrow_mut().take()
+    }
+
+    pub fn is_tracing(&self) -> bool {
+        self.calls.borrow().is_some()
+    }
+
+    fn record(&self, name: &'static str, args: Vec<(&'static str, String)>, ret: Option<String>) {
+        if let Some(calls) = self.calls.borrow_mut().as_mut() {
+            calls.push(TraceCall { name, args, ret });
+        }
+    }
+}
+
+impl Deref for TracedGl {
+    type Target = gl::Gl;
+
+    fn deref(&self) -> &Self::Target {
+        &self.gl
+    }
+}
+
+include!(concat!(env!("OUT_DIR"), "/gl_trace.rs"));
+
+/// Writes `calls` to `path` in an apitrace-like dump format, one call per line:
+///
+/// ```text
+/// 3 glDrawArrays(mode = 4, first = 0, count = 3)
+/// 4 glGetError() = 0
+/// ```
+pub fn write_trace(path: &Path, calls: &[TraceCall]) -> Result<(), ModelLoadingError> {
+    let write = || -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        for (call_no, call) in calls.iter().enumerate() {
+            let args = call
+                .args
+                .iter()
+                .map(|(name, value)| format!("{name} = {value}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            write!(out, "{call_no} {}({args})", call.name)?;
+            if let Some(ret) = &call.ret {
+                write!(out, " = {ret}")?;
+            }
+            writeln!(out)?;
+        }
+        out.flush()
+    };
+    write().map_err(ModelLoadingError::file_io(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trace_is_written_one_numbered_call_per_line() {
+        let calls = [
+            TraceCall {
+                name: "glClear",
+                args: vec![("mask", "16640".to_owned())],
+                ret: None,
+            },
+            TraceCall {
+                name: "glDrawArrays",
+                args: vec![
+                    ("mode", "4".to_owned()),
+                    ("first", "0".to_owned()),
+                    ("count", "3".to_owned()),
+                ],
+                ret: None,
+            },
+            TraceCall {
+                name: "glGetError",
+                args: Vec::new(),
+                ret: Some("0".to_owned()),
+            },
+        ];
+        let path = std::env::temp_dir().join(format!("trace-{}.txt", std::process::id()));
+        write_trace(&path, &calls).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            text,
+            "0 glClear(mask = 16640)\n\
+             1 glDrawArrays(mode = 4, first = 0, count = 3)\n\
+             2 glGetError() = 0\n"
+        );
+    }
+
+    #[test]
+    fn traces_can_only_be_written_where_files_can_be_created() {
+        let path = Path::new("/nonexistent-directory/trace.txt");
+        let err = write_trace(path, &[]).unwrap_err();
+        assert!(matches!(err, ModelLoadingError::FileIo { .. }), "{err:?}");
+    }
+}
@@ -0,0 +1,67 @@
+use glam::{Mat4, Quat, Vec3};
+
+/// A translation, rotation and scale, applied to a model in scale, rotate, translate order.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Transform {
+    pub const IDENTITY: Self = Self {
+        translation: Vec3::ZERO,
+        rotation: Quat::IDENTITY,
+        scale: Vec3::ONE,
+    };
+
+    pub fn with_translation(mut self, translation: Vec3) -> Self {
+        self.translation = translation;
+        self
+    }
+
+    pub fn with_rotation(mut self, rotation: Quat) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn with_scale(mut self, scale: Vec3) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// Moves by `offset` in world space.
+    pub fn translate(&mut self, offset: Vec3) {
+        self.translation += offset;
+    }
+
+    /// Rotates about the world X axis, on top of the current rotation.
+    pub fn rotate_x(&mut self, radians: f32) {
+        self.rotation = Quat::from_rotation_x(radians) * self.rotation;
+    }
+
+    /// Rotates about the world Y axis, on top of the current rotation.
+    pub fn rotate_y(&mut self, radians: f32) {
+        self.rotation = Quat::from_rotation_y(radians) * self.rotation;
+    }
+
+    /// Rotates about the world Z axis, on top of the current rotation.
+    pub fn rotate_z(&mut self, radians: f32) {
+        self.rotation = Quat::from_rotation_z(radians) * self.rotation;
+    }
+
+    /// Multiplies the scale on every axis by `factor`.
+    pub fn scale_by(&mut self, factor: f32) {
+        self.scale *= factor;
+    }
+
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
+    }
+}
@@ -1,22 +1,122 @@
-use std::num::NonZero;
+use std::{
+    collections::HashSet,
+    num::NonZero,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
+use glam::{vec2, vec3, DVec3, Mat4, Vec3};
 use glutin::{
-    config::{Config, ConfigTemplateBuilder, GlConfig},
-    context::{ContextAttributesBuilder, NotCurrentContext, PossiblyCurrentContext},
+    config::{ColorBufferType, Config, ConfigTemplateBuilder, GlConfig},
+    context::{NotCurrentContext, PossiblyCurrentContext, PossiblyCurrentGlContext},
     display::GetGlDisplay,
     prelude::GlDisplay,
-    surface::{GlSurface, Surface, SurfaceAttributesBuilder, WindowSurface},
+    surface::{GlSurface, Surface, SurfaceAttributesBuilder, SwapInterval, WindowSurface},
 };
 use glutin_winit::{DisplayBuilder, GlWindow};
+use image::{Rgba, RgbaImage};
 use winit::{
     application::ApplicationHandler,
-    event::WindowEvent,
+    dpi::{LogicalSize, PhysicalPosition},
+    event::{DeviceEvent, ElementState, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent},
     event_loop::EventLoop,
+    keyboard::{KeyCode, PhysicalKey},
     raw_window_handle::HasWindowHandle,
     window::{Window, WindowAttributes},
 };
 
-use crate::renderer::Renderer;
+use crate::{
+    benchmark::BenchConfig,
+    camera::{Camera, CameraMode},
+    error::{Context, ModelLoadingError},
+    frame_timer::{FrameHistory, FrameStats, FrameTimer},
+    gl::create_core_context,
+    light::Light,
+    material::Material,
+    mesh::{Mesh, MeshData, Vertex},
+    renderer::{DebugView, EdgeStyle, Projection, Renderer, RendererSnapshot, SsaoParams},
+    scene::MeshId,
+    texture::Texture,
+};
+
+const DEFAULT_WINDOW_TITLE: &str = "Model Testing Window";
+/// Depth buffer precision preferred when picking a config.
+const DEPTH_BITS: u8 = 24;
+/// Stencil buffer precision preferred when picking a config, for outlining the picked mesh.
+const STENCIL_BITS: u8 = 8;
+/// How often the FPS shown in the title is refreshed.
+const TITLE_UPDATE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Default fly camera speed in world units per second.
+const CAMERA_SPEED: f32 = 2.5;
+/// Slowest and fastest the scroll wheel can set the fly camera's speed to.
+const MIN_CAMERA_SPEED: f32 = 0.05;
+const MAX_CAMERA_SPEED: f32 = 500.0;
+/// Factor the fly camera's speed is scaled by per line scrolled.
+const CAMERA_SPEED_STEP: f32 = 1.1;
+/// Speed multipliers while Left Ctrl (precise) or Left Shift (fast) is held.
+const PRECISE_SPEED_FACTOR: f32 = 0.25;
+const FAST_SPEED_FACTOR: f32 = 4.0;
+/// Radians of rotation per unit of raw mouse motion.
+const MOUSE_SENSITIVITY: f32 = 0.002;
+/// Radians of orbit per pixel the cursor is dragged.
+const ORBIT_SENSITIVITY: f32 = 0.01;
+/// Factor the orbit distance is scaled by per line scrolled.
+const ZOOM_STEP: f32 = 0.9;
+/// Pixels of touchpad scrolling treated as one line.
+const PIXELS_PER_LINE: f64 = 20.0;
+/// Radians per second the model turns at while an arrow key or Q/E is held.
+const MODEL_ROTATE_SPEED: f32 = std::f32::consts::FRAC_PI_2;
+/// Side length of the ground grid, in world units.
+const GRID_SIZE: f32 = 10.0;
+/// Number of grid cells along each side, so each cell is a quarter of the unit cube models are
+/// fitted to.
+const GRID_DIVISIONS: u32 = 40;
+/// Where the camera is kept between runs, in the working directory.
+const CAMERA_STATE_PATH: &str = "camera.json";
+/// Where the F10 benchmark writes its report, in the working directory.
+const BENCHMARK_REPORT_PATH: &str = "benchmark.json";
+/// Color of the bounding boxes the B key shows.
+const BOUNDS_COLOR: Vec3 = vec3(1.0, 0.8, 0.0);
+/// Color and thickness of the outline around the mesh picked by clicking on it.
+const SELECTION_COLOR: Vec3 = vec3(1.0, 0.5, 0.0);
+const SELECTION_THICKNESS: f32 = 0.04;
+/// Color of the edges drawn over the shaded meshes when the wireframe overlay is on.
+const OVERLAY_COLOR: Vec3 = vec3(0.1, 0.1, 0.1);
+/// Texels along each side of the shadow map the H key turns on.
+const SHADOW_MAP_RESOLUTION: u32 = 2048;
+/// Distance an orthographic view through the fly camera matches the size of the perspective view
+/// at, that of the default camera from the origin. Orbit cameras use their distance to the target.
+const ORTHOGRAPHIC_FLY_DISTANCE: f32 = 3.0;
+/// Reflectivity of the material the M key swaps in.
+const MIRROR_REFLECTIVITY: f32 = 0.9;
+/// Range of the point lights the L key swaps in, reaching a little past a unit cube model.
+const DEMO_LIGHT_RANGE: f32 = 4.0;
+/// Step the camera, model rotation and update hook advance by, however fast frames are drawn.
+const UPDATE_STEP: Duration = Duration::from_micros(16_667);
+/// Most time the updates catch up on after a slow frame, so a stall (e.g. dragging the window)
+/// doesn't leave them running step after step to make up for it.
+const MAX_UPDATE_BACKLOG: Duration = Duration::from_millis(250);
+/// Side of the quad the normal mapping demo shows behind the model, how far behind the origin it
+/// is, and how many tiles of bumps it's covered in along each side.
+const NORMAL_MAP_QUAD_SIZE: f32 = 3.0;
+const NORMAL_MAP_QUAD_DEPTH: f32 = 0.75;
+const NORMAL_MAP_TILES: f32 = 3.0;
+/// Pixels along each side of a tile of the generated bump texture, and the bump's radius in them.
+const BUMP_TILE_PIXELS: u32 = 64;
+const BUMP_RADIUS: f32 = 24.0;
+/// Path the generated normal map is given to the renderer under, as materials name theirs by path.
+const BUMP_NORMAL_MAP: &str = "generated/bumps.png";
+/// Radius of the circle the normal mapping demo's light moves around in front of the quad, and
+/// how fast, in radians per second.
+const NORMAL_MAP_LIGHT_RADIUS: f32 = 1.2;
+const NORMAL_MAP_LIGHT_SPEED: f32 = 1.5;
+/// Copies of the first mesh along each side of the instancing demo.
+const INSTANCE_GRID_SIDE: u32 = 32;
+/// Distance between neighbouring copies in the instancing demo.
+const INSTANCE_SPACING: f32 = 1.0;
+/// Scale of each copy in the instancing demo, leaving a gap between neighbours.
+const INSTANCE_SCALE: f32 = 0.5;
 
 pub mod gl {
     #![allow(clippy::all)]
@@ -29,31 +129,256 @@ pub struct GfWindow {
     renderer: Option<Renderer>,
     surface: Option<Surface<WindowSurface>>,
     context: Option<PossiblyCurrentContext>,
-    exit_state: anyhow::Result<()>,
+    exit_state: Result<(), ModelLoadingError>,
+    pause_on_unfocus: bool,
+    vsync: bool,
+    /// Fly camera speed in world units per second, before the held modifiers.
+    camera_speed: f32,
+    /// Whether the surface is sRGB and the renderer encodes to it.
+    srgb: bool,
+    rendering_active: bool,
+    camera: CameraMode,
+    /// Model the renderer was created from, or `None` for the built-in triangle.
+    model_path: Option<PathBuf>,
+    pressed_keys: HashSet<KeyCode>,
+    /// Whether the left mouse button is held, which rotates the orbit camera.
+    orbit_dragging: bool,
+    cursor_position: Option<PhysicalPosition<f64>>,
+    frame_timer: FrameTimer,
+    frame_history: FrameHistory,
+    /// Frame time not yet consumed by an [`UPDATE_STEP`].
+    update_backlog: Duration,
+    update_hook: Option<UpdateHook>,
+    redraw_hook: Option<RedrawHook>,
+    /// When the FPS in the title was last refreshed.
+    last_title_update: Instant,
+    title: String,
+    screenshot_requested: bool,
+    /// Per-copy transforms while the instancing demo is shown.
+    instance_transforms: Option<Vec<Mat4>>,
+    /// The meshes' own materials while they're swapped for a mirror finish.
+    original_materials: Option<Vec<Material>>,
+    /// The renderer's own lights while the two point lights of the lighting demo are shown.
+    original_lights: Option<Vec<Light>>,
+    /// Whether each mesh's bounding box is drawn over it.
+    show_bounds: bool,
+    /// Mesh last clicked on, which is drawn outlined.
+    selected_mesh: Option<MeshId>,
+    normal_map_demo: Option<NormalMapDemo>,
+    /// The renderer's meshes and settings while its GL objects are gone between a suspend and a
+    /// resume.
+    suspended_renderer: Option<RendererSnapshot>,
 }
 
-impl GfWindow {
-    pub fn new(event_loop: &EventLoop<()>) -> anyhow::Result<Self> {
-        let config_template_builder = ConfigTemplateBuilder::default();
-
-        let config_picker = |configs: Box<dyn Iterator<Item = Config> + '_>| {
-            configs
-                .reduce(|acc, config| {
-                    if config.num_samples() > acc.num_samples() {
-                        config
-                    } else {
-                        acc
-                    }
-                })
-                .unwrap()
+/// The bumpy quad shown by the normal mapping demo, lit by a light circling in front of it.
+struct NormalMapDemo {
+    quad: Mesh,
+    /// The renderer's own lights, put back when the demo ends.
+    original_lights: Vec<Light>,
+    /// Seconds since the demo started, which places the light.
+    time: f32,
+}
+
+/// Per-step logic plugged in with [`GfWindow::set_update_hook`], given the step length in seconds.
+type UpdateHook = Box<dyn FnMut(&mut Renderer, f32)>;
+
+/// Per-frame logic plugged in with [`GfWindow::on_redraw`], given the time since the last frame.
+type RedrawHook = Box<dyn FnMut(&mut Renderer, Duration)>;
+
+/// Bit depth of the window's color buffer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorDepth {
+    /// 8 bits per channel, available everywhere.
+    #[default]
+    Rgba8,
+    /// 10 bits per color channel with a 2 bit alpha channel.
+    Rgb10A2,
+    /// 16 bit floats per channel, for HDR output.
+    Rgba16F,
+}
+
+impl ColorDepth {
+    fn channel_bits(self) -> (u8, u8) {
+        match self {
+            ColorDepth::Rgba8 => (8, 8),
+            ColorDepth::Rgb10A2 => (10, 2),
+            ColorDepth::Rgba16F => (16, 16),
+        }
+    }
+
+    fn config_template(self) -> ConfigTemplateBuilder {
+        let (bits, alpha_bits) = self.channel_bits();
+        ConfigTemplateBuilder::default()
+            .with_buffer_type(ColorBufferType::Rgb {
+                r_size: bits,
+                g_size: bits,
+                b_size: bits,
+            })
+            .with_alpha_size(alpha_bits)
+            .with_float_pixels(self == ColorDepth::Rgba16F)
+    }
+
+    /// Template sizes are only lower bounds, so check the config is exactly this depth.
+    fn matches(self, config: &Config) -> bool {
+        let (bits, _) = self.channel_bits();
+        let color_bits_match = matches!(
+            config.color_buffer_type(),
+            Some(ColorBufferType::Rgb { r_size, g_size, b_size })
+                if r_size == bits && g_size == bits && b_size == bits
+        );
+        color_bits_match && config.float_pixels() == (self == ColorDepth::Rgba16F)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GfWindowBuilder {
+    title: String,
+    size: Option<(u32, u32)>,
+    vsync: bool,
+    color_depth: ColorDepth,
+    msaa: Option<u8>,
+    srgb: bool,
+    camera_speed: f32,
+}
+
+impl Default for GfWindowBuilder {
+    fn default() -> Self {
+        Self {
+            title: DEFAULT_WINDOW_TITLE.to_owned(),
+            size: None,
+            vsync: true,
+            color_depth: ColorDepth::default(),
+            msaa: None,
+            srgb: false,
+            camera_speed: CAMERA_SPEED,
+        }
+    }
+}
+
+impl GfWindowBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the window title. The frame rate is appended to it while running.
+    pub fn title(mut self, title: &str) -> Self {
+        self.title = title.to_owned();
+        self
+    }
+
+    /// Sets the initial inner size in logical pixels, instead of leaving it to the platform.
+    pub fn size(mut self, width: u32, height: u32) -> Self {
+        self.size = Some((width, height));
+        self
+    }
+
+    /// Whether to wait for vertical blank when presenting, see [`GfWindow::set_vsync`]. On by
+    /// default.
+    pub fn vsync(mut self, vsync: bool) -> Self {
+        self.vsync = vsync;
+        self
+    }
+
+    /// Requests a color buffer of the given depth, falling back to [`ColorDepth::Rgba8`] when the
+    /// platform has no matching config.
+    pub fn color_depth(mut self, color_depth: ColorDepth) -> Self {
+        self.color_depth = color_depth;
+        self
+    }
+
+    /// Prefers a config with exactly `samples` samples per pixel. Without it (or a config with
+    /// that count) the config with the most samples is picked.
+    pub fn msaa(mut self, samples: u8) -> Self {
+        self.msaa = Some(samples);
+        self
+    }
+
+    /// Prefers an sRGB capable config and has the renderer encode its linear output to sRGB when
+    /// writing to it, see [`Renderer::set_framebuffer_srgb`]. Off by default.
+    pub fn srgb(mut self, srgb: bool) -> Self {
+        self.srgb = srgb;
+        self
+    }
+
+    /// Sets the fly camera's speed in world units per second, see [`GfWindow::set_camera_speed`].
+    /// 2.5 by default, which suits models fitted to a unit cube.
+    pub fn camera_speed(mut self, units_per_sec: f32) -> Self {
+        self.camera_speed = units_per_sec;
+        self
+    }
+
+    pub fn build(self, event_loop: &EventLoop<()>) -> Result<GfWindow, ModelLoadingError> {
+        let (msaa, srgb) = (self.msaa, self.srgb);
+        let config_picker = |color_depth: ColorDepth| {
+            move |configs: Box<dyn Iterator<Item = Config> + '_>| {
+                configs
+                    .reduce(|acc, config| {
+                        let rank = |config: &Config| {
+                            let samples = config.num_samples();
+                            (
+                                color_depth.matches(config),
+                                !srgb || config.srgb_capable(),
+                                config.depth_size() >= DEPTH_BITS,
+                                config.stencil_size() >= STENCIL_BITS,
+                                msaa == Some(samples),
+                                samples,
+                            )
+                        };
+                        if rank(&config) > rank(&acc) {
+                            config
+                        } else {
+                            acc
+                        }
+                    })
+                    .expect("glutin only calls the picker with at least one config")
+            }
+        };
+        let mut window_attributes = WindowAttributes::default().with_title(&self.title);
+        if let Some((width, height)) = self.size {
+            window_attributes = window_attributes.with_inner_size(LogicalSize::new(width, height));
+        }
+        let build_display = |color_depth: ColorDepth| {
+            let mut template = color_depth.config_template();
+            if let Some(samples @ 1..) = msaa {
+                template = template.with_multisampling(samples);
+            }
+            DisplayBuilder::default()
+                .with_window_attributes(Some(window_attributes.clone()))
+                .build(event_loop, template, config_picker(color_depth))
         };
-        let window_attributes = WindowAttributes::default().with_title("Model Testing Window");
 
-        let (window, config) = DisplayBuilder::default()
-            .with_window_attributes(Some(window_attributes))
-            .build(event_loop, config_template_builder, config_picker)
-            .unwrap();
-        let window = window.unwrap();
+        // Templates only set lower bounds, so a deeper config can be found without an exact one,
+        // and none at all means building again for the 8 bit configs every platform has.
+        let (window, config) = match build_display(self.color_depth) {
+            Err(err) if self.color_depth != ColorDepth::Rgba8 => {
+                log::debug!("no config of at least {:?}: {err}", self.color_depth);
+                build_display(ColorDepth::Rgba8)
+            }
+            result => result,
+        }
+        .map_err(|err| {
+            ModelLoadingError::WindowCreation(format!(
+                "failed to create a window with a GL config: {err}"
+            ))
+        })?;
+        let window = window
+            .ok_or_else(|| ModelLoadingError::WindowCreation("no window was created".into()))?;
+        if self.color_depth != ColorDepth::Rgba8 && !self.color_depth.matches(&config) {
+            log::warn!(
+                "no {:?} config available, using the closest one",
+                self.color_depth
+            );
+        }
+        if config.depth_size() == 0 {
+            log::warn!("GL config has no depth buffer, faces will not be depth sorted");
+        }
+        if config.stencil_size() == 0 {
+            log::warn!("GL config has no stencil buffer, picked meshes will not be outlined");
+        }
+        let srgb = self.srgb && config.srgb_capable();
+        if self.srgb && !srgb {
+            log::warn!("no sRGB capable GL config, colors will be written uncorrected");
+        }
 
         Ok(GfWindow {
             window,
@@ -62,26 +387,265 @@ impl GfWindow {
             context: None,
             surface: None,
             exit_state: Ok(()),
+            pause_on_unfocus: true,
+            vsync: self.vsync,
+            camera_speed: self.camera_speed,
+            srgb,
+            rendering_active: true,
+            camera: CameraMode::Fly(load_camera()),
+            model_path: None,
+            pressed_keys: HashSet::new(),
+            orbit_dragging: false,
+            cursor_position: None,
+            frame_timer: FrameTimer::new(),
+            frame_history: FrameHistory::new(),
+            update_backlog: Duration::ZERO,
+            update_hook: None,
+            redraw_hook: None,
+            last_title_update: Instant::now(),
+            title: self.title,
+            screenshot_requested: false,
+            instance_transforms: None,
+            original_materials: None,
+            original_lights: None,
+            show_bounds: false,
+            selected_mesh: None,
+            normal_map_demo: None,
+            suspended_renderer: None,
         })
     }
-    pub fn create_context(&self) -> anyhow::Result<NotCurrentContext> {
+}
+
+/// Transforms laying copies of a model out on a square grid over the XZ plane, centered on the
+/// origin.
+fn instance_grid() -> Vec<Mat4> {
+    let offset = (INSTANCE_GRID_SIDE - 1) as f32 * INSTANCE_SPACING / 2.0;
+    (0..INSTANCE_GRID_SIDE)
+        .flat_map(|row| (0..INSTANCE_GRID_SIDE).map(move |column| (row, column)))
+        .map(|(row, column)| {
+            let position = Vec3::new(
+                column as f32 * INSTANCE_SPACING - offset,
+                0.0,
+                row as f32 * INSTANCE_SPACING - offset,
+            );
+            Mat4::from_translation(position) * Mat4::from_scale(Vec3::splat(INSTANCE_SCALE))
+        })
+        .collect()
+}
+
+/// A quad facing +Z covered in tiles of bumps, with tangents for normal mapping. The textures are
+/// given to `renderer`, which deletes them, while the caller has to delete the quad.
+fn normal_map_quad(renderer: &mut Renderer) -> Result<Mesh, ModelLoadingError> {
+    let (albedo, normal_map) = bump_textures();
+    let gl = renderer.gl();
+    let normal_map = Texture::from_image(gl, &normal_map)?;
+    let albedo = Texture::from_image(gl, &albedo).inspect_err(|_| normal_map.delete(gl))?;
+
+    let half = NORMAL_MAP_QUAD_SIZE / 2.0;
+    // V increases downwards, as the textures are uploaded top row first.
+    let corner = |x: f32, y: f32| Vertex {
+        position: vec3(x * half, y * half, -NORMAL_MAP_QUAD_DEPTH),
+        color: Vec3::ONE,
+        normal: Vec3::Z,
+        uv: vec2(x + 1.0, 1.0 - y) * 0.5 * NORMAL_MAP_TILES,
+        ..Default::default()
+    };
+    let mut data = MeshData {
+        vertices: vec![
+            corner(-1.0, -1.0),
+            corner(1.0, -1.0),
+            corner(1.0, 1.0),
+            corner(-1.0, 1.0),
+        ],
+        indices: vec![0, 1, 2, 0, 2, 3],
+        ..Default::default()
+    };
+    data.compute_tangents();
+    let mut quad = Mesh::from_data(gl, &data).inspect_err(|_| {
+        normal_map.delete(gl);
+        albedo.delete(gl);
+    })?;
+    quad.set_texture(gl, albedo);
+    quad.set_material(Material {
+        specular: Vec3::splat(0.5),
+        ..Material::default().with_normal_map(BUMP_NORMAL_MAP)
+    });
+    renderer.insert_normal_map(BUMP_NORMAL_MAP, normal_map);
+    Ok(quad)
+}
+
+/// A tile of the normal mapping demo's texture, in two shades so the tiles are told apart, and its
+/// normal map, with a hemispherical bump in the middle of the tile.
+fn bump_textures() -> (RgbaImage, RgbaImage) {
+    let size = BUMP_TILE_PIXELS * 2;
+    let albedo = RgbaImage::from_fn(size, size, |x, y| {
+        if (x / BUMP_TILE_PIXELS + y / BUMP_TILE_PIXELS).is_multiple_of(2) {
+            Rgba([200, 170, 130, 255])
+        } else {
+            Rgba([170, 140, 105, 255])
+        }
+    });
+    let normal_map = RgbaImage::from_fn(size, size, |x, y| {
+        let center = (BUMP_TILE_PIXELS as f32 - 1.0) / 2.0;
+        let offset = vec2(
+            (x % BUMP_TILE_PIXELS) as f32 - center,
+            (y % BUMP_TILE_PIXELS) as f32 - center,
+        );
+        // The sphere's normal inside the bump, and flat around it.
+        let height = (BUMP_RADIUS * BUMP_RADIUS - offset.length_squared())
+            .max(0.0)
+            .sqrt();
+        let normal = if height > 0.0 {
+            offset.extend(height).normalize()
+        } else {
+            Vec3::Z
+        };
+        let [r, g, b] = (normal * 0.5 + 0.5)
+            .to_array()
+            .map(|c| (c * 255.0).round() as u8);
+        Rgba([r, g, b, 255])
+    });
+    (albedo, normal_map)
+}
+
+/// The camera saved by the last run, or the default one when there's none or it can't be read.
+fn load_camera() -> Camera {
+    let path = Path::new(CAMERA_STATE_PATH);
+    if !path.exists() {
+        return Camera::default();
+    }
+    Camera::load(path).unwrap_or_else(|err| {
+        log::warn!("{err:#}, using the default camera");
+        Camera::default()
+    })
+}
+
+/// `screenshot-<unix time in milliseconds>.png` in the working directory.
+fn screenshot_path() -> PathBuf {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    PathBuf::from(format!("screenshot-{timestamp}.png"))
+}
+
+impl GfWindow {
+    pub fn new(event_loop: &EventLoop<()>) -> Result<Self, ModelLoadingError> {
+        GfWindowBuilder::default().build(event_loop)
+    }
+    pub fn create_context(&self) -> Result<NotCurrentContext, ModelLoadingError> {
         let window_handle = self.window.window_handle()?.as_raw();
-        let context_attributes = ContextAttributesBuilder::new().build(Some(window_handle));
         let gl_display = self.config.display();
-        unsafe { Ok(gl_display.create_context(&self.config, &context_attributes)?) }
+        unsafe { create_core_context(&gl_display, &self.config, Some(window_handle)) }
     }
-    pub fn create_window_surface(&self) -> anyhow::Result<Surface<WindowSurface>> {
+    pub fn create_window_surface(&self) -> Result<Surface<WindowSurface>, ModelLoadingError> {
         let display = self.config.display();
-        let surface_attributes_builder = SurfaceAttributesBuilder::new();
+        // Without sRGB the platform's default surface is kept.
+        let surface_attributes_builder =
+            SurfaceAttributesBuilder::<WindowSurface>::new().with_srgb(self.srgb.then_some(true));
         let surface_attributes = self
             .window
-            .build_surface_attributes(surface_attributes_builder)?;
-        unsafe { Ok(display.create_window_surface(&self.config, &surface_attributes)?) }
+            .build_surface_attributes(surface_attributes_builder)
+            .context("window has no handle to create a surface for")?;
+        unsafe { display.create_window_surface(&self.config, &surface_attributes) }
+            .context("failed to create the window surface")
     }
-    pub fn create_gl_renderer(&self) -> Renderer {
+    pub fn create_gl_renderer(&mut self) -> Result<Renderer, ModelLoadingError> {
+        self.model_path = None;
         // Renderer can't be instantiated until context is current
         Renderer::new(&self.config.display())
     }
+    /// Creates a renderer for a model file, picking the loader from the file extension, or for
+    /// every OBJ file in a directory. The path is remembered so the model can be reloaded with
+    /// F5.
+    pub fn create_gl_renderer_from_file(
+        &mut self,
+        path: &Path,
+    ) -> Result<Renderer, ModelLoadingError> {
+        self.model_path = Some(path.to_owned());
+        Renderer::from_file(&self.config.display(), path)
+    }
+
+    pub fn window(&self) -> &Window {
+        &self.window
+    }
+
+    /// The renderer drawing the window, once [`GfWindow::run`] has been given one. It's `None`
+    /// before that, and while the GL state is torn down between a suspend and a resume.
+    pub fn renderer(&self) -> Option<&Renderer> {
+        self.renderer.as_ref()
+    }
+
+    /// Mutable access to the renderer between frames, e.g. to move the model or change the clear
+    /// color. `None` as for [`GfWindow::renderer`].
+    pub fn renderer_mut(&mut self) -> Option<&mut Renderer> {
+        self.renderer.as_mut()
+    }
+
+    /// Runs `hook` at a fixed rate of about 60 times a second, however fast frames are drawn, along
+    /// with the window's own camera and model updates. It's given the renderer and the step length
+    /// in seconds, e.g. to animate meshes.
+    pub fn set_update_hook(&mut self, hook: impl FnMut(&mut Renderer, f32) + 'static) {
+        self.update_hook = Some(Box::new(hook));
+    }
+
+    /// Runs `hook` once before each frame is drawn and presented, after the fixed-rate updates of
+    /// [`GfWindow::set_update_hook`], given the renderer and the time since the last frame. The
+    /// renderer is only lent for the call, and `'static` keeps the closure from borrowing anything
+    /// that could be gone by then, so it has to own or share what it captures.
+    pub fn on_redraw(&mut self, hook: impl FnMut(&mut Renderer, Duration) + 'static) {
+        self.redraw_hook = Some(Box::new(hook));
+    }
+
+    /// Stops requesting redraws while the window is unfocused. On by default.
+    pub fn set_pause_on_unfocus(&mut self, pause_on_unfocus: bool) {
+        self.pause_on_unfocus = pause_on_unfocus;
+        if !pause_on_unfocus && !self.rendering_active {
+            self.rendering_active = true;
+            self.window.request_redraw();
+        }
+    }
+
+    /// Sets how fast the fly camera moves, in world units per second, before the Left Ctrl and
+    /// Left Shift modifiers. Scrolling while flying scales it too.
+    pub fn set_camera_speed(&mut self, units_per_sec: f32) {
+        self.camera_speed = units_per_sec;
+    }
+
+    pub fn camera_speed(&self) -> f32 {
+        self.camera_speed
+    }
+
+    /// Waits for vertical blank before presenting each frame when `enabled`, capping the frame
+    /// rate to the display's refresh rate. On by default.
+    pub fn set_vsync(&mut self, enabled: bool) {
+        self.vsync = enabled;
+        self.apply_vsync();
+    }
+
+    fn apply_vsync(&self) {
+        self.set_swap_interval(self.vsync);
+    }
+
+    fn set_swap_interval(&self, vsync: bool) {
+        let (Some(surface), Some(context)) = (&self.surface, &self.context) else {
+            return;
+        };
+        let interval = if vsync {
+            SwapInterval::Wait(NonZero::<u32>::MIN)
+        } else {
+            SwapInterval::DontWait
+        };
+        if let Err(err) = surface.set_swap_interval(context, interval) {
+            log::warn!("failed to set swap interval to {interval:?}: {err}");
+        }
+    }
+
+    fn apply_srgb(&mut self) {
+        if let Some(renderer) = self.renderer.as_mut() {
+            renderer.set_framebuffer_srgb(self.srgb);
+        }
+    }
 
     pub fn run(
         mut self,
@@ -89,48 +653,650 @@ impl GfWindow {
         surface: Surface<WindowSurface>,
         renderer: Renderer,
         context: PossiblyCurrentContext,
-    ) -> anyhow::Result<()> {
+    ) -> Result<(), ModelLoadingError> {
         self.surface = Some(surface);
         self.context = Some(context);
         self.renderer = Some(renderer);
+        self.apply_vsync();
+        self.apply_srgb();
+        self.frame_timer.reset();
 
         event_loop.run_app(&mut self)?;
 
         self.exit_state
     }
+
+    /// Recreates the window surface after a suspend, and the renderer from the snapshot taken
+    /// before it. Without one, the model is loaded again.
+    fn restore_gl_state(&mut self) -> Result<(), ModelLoadingError> {
+        let context = self
+            .context
+            .as_ref()
+            .ok_or_else(|| ModelLoadingError::ContextCreation("no GL context to restore".into()))?;
+        let surface = self.create_window_surface()?;
+        context
+            .make_current(&surface)
+            .context("failed to make the GL context current on the window surface")?;
+        let mut renderer = match (self.suspended_renderer.take(), self.model_path.clone()) {
+            (Some(snapshot), _) => Renderer::from_snapshot(&self.config.display(), &snapshot)?,
+            (None, Some(path)) => self.create_gl_renderer_from_file(&path)?,
+            (None, None) => self.create_gl_renderer()?,
+        };
+        let size = self.window.inner_size();
+        renderer.resize(size.width as i32, size.height as i32);
+        renderer.set_camera(&self.camera);
+
+        self.surface = Some(surface);
+        self.renderer = Some(renderer);
+        self.apply_vsync();
+        self.apply_srgb();
+        self.window.request_redraw();
+        Ok(())
+    }
+
+    /// Frames per second, averaged over the last few frames.
+    pub fn fps(&self) -> f32 {
+        self.frame_timer.fps()
+    }
+
+    fn handle_shortcut(&mut self, key: KeyCode) {
+        match key {
+            // Swap between the fly and orbit cameras, keeping the current view.
+            KeyCode::Tab => self.camera = self.camera.toggled(),
+            KeyCode::KeyP => self.cycle_program(),
+            // Saved after the next frame is drawn, before it's presented.
+            KeyCode::F12 => {
+                self.screenshot_requested = true;
+                self.window.request_redraw();
+            }
+            KeyCode::KeyF => {
+                if let Some(renderer) = self.renderer.as_mut() {
+                    renderer.set_wireframe(!renderer.wireframe());
+                }
+            }
+            // Edges drawn over the shaded faces, which are pushed back so the edges don't z-fight.
+            KeyCode::KeyV => {
+                if let Some(renderer) = self.renderer.as_mut() {
+                    let overlay = renderer.wireframe_overlay().is_none();
+                    renderer.set_wireframe_overlay(overlay.then_some(OVERLAY_COLOR));
+                    renderer.set_polygon_offset(overlay.then_some((1.0, 1.0)));
+                }
+            }
+            KeyCode::KeyR => {
+                if let Some(renderer) = self.renderer.as_mut() {
+                    renderer.model_matrix = Mat4::IDENTITY;
+                }
+            }
+            KeyCode::KeyG => {
+                if let Some(renderer) = self.renderer.as_mut() {
+                    renderer.set_grid_visible(!renderer.grid_visible());
+                }
+            }
+            KeyCode::KeyO => self.toggle_orthographic(),
+            KeyCode::KeyI => {
+                self.instance_transforms = match self.instance_transforms {
+                    Some(_) => None,
+                    None => Some(instance_grid()),
+                };
+            }
+            KeyCode::KeyM => self.toggle_mirror_finish(),
+            KeyCode::KeyL => self.toggle_point_lights(),
+            KeyCode::KeyB => self.show_bounds = !self.show_bounds,
+            KeyCode::KeyT => self.toggle_normal_map_demo(),
+            KeyCode::KeyN => {
+                if let Some(renderer) = self.renderer.as_mut() {
+                    renderer.set_normal_mapping(!renderer.normal_mapping());
+                    log::info!("normal mapping {}", on_off(renderer.normal_mapping()));
+                }
+            }
+            KeyCode::KeyK => {
+                if let Some(renderer) = self.renderer.as_mut() {
+                    let view = match renderer.debug_view() {
+                        DebugView::None => DebugView::Curvature,
+                        DebugView::Curvature => DebugView::None,
+                    };
+                    if let Err(err) = renderer.set_debug_view(view) {
+                        log::error!("failed to switch the debug view: {err:#}");
+                    }
+                }
+            }
+            KeyCode::KeyH => {
+                if let Some(renderer) = self.renderer.as_mut() {
+                    let on = renderer.shadow_resolution().is_none();
+                    let result = if on {
+                        renderer.enable_shadows(SHADOW_MAP_RESOLUTION)
+                    } else {
+                        renderer.disable_shadows();
+                        Ok(())
+                    };
+                    if let Err(err) = result {
+                        log::error!("failed to turn shadows {}: {err:#}", on_off(on));
+                    }
+                }
+            }
+            KeyCode::KeyJ => {
+                if let Some(renderer) = self.renderer.as_mut() {
+                    let on = renderer.ssao().is_none();
+                    if let Err(err) = renderer.set_ssao(on.then(SsaoParams::default)) {
+                        log::error!("failed to turn SSAO {}: {err:#}", on_off(on));
+                    }
+                }
+            }
+            KeyCode::KeyU => {
+                if let Some(renderer) = self.renderer.as_mut() {
+                    let on = renderer.edge_outline().is_none();
+                    if let Err(err) = renderer.set_edge_outline(on.then(EdgeStyle::default)) {
+                        log::error!("failed to turn the edge outline {}: {err:#}", on_off(on));
+                    }
+                }
+            }
+            KeyCode::F5 => self.reload_model(),
+            KeyCode::F10 => self.run_benchmark(),
+            _ => (),
+        }
+    }
+
+    /// Switches the renderer to the registered shader program after the active one.
+    fn cycle_program(&mut self) {
+        let Some(renderer) = self.renderer.as_mut() else {
+            return;
+        };
+        let names: Vec<String> = renderer.programs().names().map(str::to_owned).collect();
+        let active = names
+            .iter()
+            .position(|name| name == renderer.active_program());
+        let next = &names[active.map_or(0, |i| (i + 1) % names.len())];
+        if renderer.use_program(next).is_ok() {
+            log::info!("using shader program {next:?}");
+        }
+    }
+
+    /// Runs [`Renderer::run_benchmark`] with the default settings and vsync off, saving the report
+    /// to [`BENCHMARK_REPORT_PATH`]. The window stops responding until it's done.
+    fn run_benchmark(&mut self) {
+        let Some(renderer) = &self.renderer else {
+            return;
+        };
+        log::info!("running the benchmark");
+        self.set_swap_interval(false);
+        let report = renderer.run_benchmark(&BenchConfig::default());
+        self.apply_vsync();
+        let path = Path::new(BENCHMARK_REPORT_PATH);
+        match report.and_then(|report| report.save(path).map(|()| report)) {
+            Ok(report) => log::info!(
+                "benchmark frames took {:.2} ms on average on the CPU, report saved to {}",
+                report.cpu.avg_ms,
+                path.display()
+            ),
+            Err(err) => log::error!("benchmark failed: {err:#}"),
+        }
+        // The time spent benchmarking isn't a frame.
+        self.frame_timer.reset();
+    }
+
+    /// Loads the model file again, keeping the current one if the new version fails to load.
+    fn reload_model(&mut self) {
+        let (Some(renderer), Some(path)) = (self.renderer.as_mut(), &self.model_path) else {
+            log::info!("showing the built-in mesh, there's no model file to reload");
+            return;
+        };
+        match renderer.replace_scene_from_file(path) {
+            Ok(()) => {
+                // The saved materials belonged to the old meshes.
+                self.original_materials = None;
+                log::info!("reloaded {}", path.display());
+            }
+            Err(err) => log::error!("{err:#}, keeping the current model"),
+        }
+    }
+
+    /// Swaps every mesh's material for a chrome-like one reflecting the skybox, or back.
+    fn toggle_mirror_finish(&mut self) {
+        let Some(renderer) = self.renderer.as_mut() else {
+            return;
+        };
+        match self.original_materials.take() {
+            Some(materials) => {
+                for (mesh, material) in renderer.meshes_mut().iter_mut().zip(materials) {
+                    mesh.set_material(material);
+                }
+            }
+            None => {
+                self.original_materials = Some(
+                    renderer
+                        .meshes()
+                        .iter()
+                        .map(|mesh| mesh.material().clone())
+                        .collect(),
+                );
+                for mesh in renderer.meshes_mut() {
+                    mesh.set_material(Material::reflective(MIRROR_REFLECTIVITY));
+                }
+            }
+        }
+    }
+
+    /// Swaps the lights for a red and a blue point light either side of the model, or back.
+    fn toggle_point_lights(&mut self) {
+        let Some(renderer) = self.renderer.as_mut() else {
+            return;
+        };
+        match self.original_lights.take() {
+            Some(lights) => renderer.set_lights(&lights),
+            None => {
+                self.original_lights = Some(renderer.lights().to_vec());
+                renderer.set_lights(&[
+                    Light::Point {
+                        pos: vec3(-1.5, 1.0, 1.0),
+                        color: vec3(1.0, 0.2, 0.2),
+                        range: DEMO_LIGHT_RANGE,
+                    },
+                    Light::Point {
+                        pos: vec3(1.5, 1.0, 1.0),
+                        color: vec3(0.2, 0.4, 1.0),
+                        range: DEMO_LIGHT_RANGE,
+                    },
+                ]);
+            }
+        }
+    }
+
+    /// Shows a normal mapped quad behind the model with a light circling in front of it in place
+    /// of the renderer's lights, or puts them back.
+    fn toggle_normal_map_demo(&mut self) {
+        let Some(renderer) = self.renderer.as_mut() else {
+            return;
+        };
+        match self.normal_map_demo.take() {
+            Some(demo) => {
+                demo.quad.delete(renderer.gl());
+                renderer.set_lights(&demo.original_lights);
+            }
+            None => match normal_map_quad(renderer) {
+                Ok(quad) => {
+                    self.normal_map_demo = Some(NormalMapDemo {
+                        quad,
+                        original_lights: renderer.lights().to_vec(),
+                        time: 0.0,
+                    });
+                }
+                Err(err) => log::error!("failed to create the normal mapping demo: {err:#}"),
+            },
+        }
+    }
+
+    /// Selects and logs the mesh under the cursor, or clears the selection if there is none.
+    fn pick_mesh(&mut self) {
+        let (Some(renderer), Some(position)) = (&self.renderer, self.cursor_position) else {
+            return;
+        };
+        self.selected_mesh = renderer.pick(position.x as f32, position.y as f32);
+        match self.selected_mesh {
+            Some(id) => {
+                let mesh = &renderer.meshes()[id.0];
+                let name = mesh.name().unwrap_or("unnamed");
+                let offset = mesh.origin_offset();
+                if offset == DVec3::ZERO {
+                    log::info!("picked mesh {} ({name})", id.0);
+                } else {
+                    log::info!("picked mesh {} ({name}), origin at {offset}", id.0);
+                }
+            }
+            None => log::debug!("no mesh under the cursor"),
+        }
+    }
+
+    /// Switches between perspective and orthographic projection, keeping the clip planes and the
+    /// size things appear at around the camera's focus.
+    fn toggle_orthographic(&mut self) {
+        let Some(renderer) = self.renderer.as_mut() else {
+            return;
+        };
+        let (near, far) = renderer.clip_planes();
+        let projection = match renderer.projection() {
+            Projection::Perspective { .. } => Projection::Orthographic {
+                height: orthographic_height(&self.camera),
+                near,
+                far,
+            },
+            Projection::Orthographic { .. } => Projection::Perspective {
+                fov: self.camera.fov(),
+                near,
+                far,
+            },
+        };
+        if let Err(err) = renderer.set_projection(projection) {
+            log::error!("failed to switch projection: {err:#}");
+        }
+    }
+
+    /// Advances everything that moves over time by `dt` seconds.
+    fn update(&mut self, dt: f32) {
+        self.update_camera(dt);
+        self.update_model_rotation(dt);
+        if let (Some(demo), Some(renderer)) = (&mut self.normal_map_demo, &mut self.renderer) {
+            demo.time += dt;
+            let angle = demo.time * NORMAL_MAP_LIGHT_SPEED;
+            let circle = vec2(angle.cos(), angle.sin()) * NORMAL_MAP_LIGHT_RADIUS;
+            renderer.set_lights(&[Light::Point {
+                pos: circle.extend(0.0),
+                color: Vec3::ONE,
+                range: DEMO_LIGHT_RANGE,
+            }]);
+        }
+        if let (Some(hook), Some(renderer)) = (&mut self.update_hook, &mut self.renderer) {
+            hook(renderer, dt);
+        }
+    }
+
+    /// Draws and presents a frame as things stand after the last update, and records how long it
+    /// took in [`GfWindow::frame_stats`]. Returns zeroed stats without recording them while
+    /// suspended, as there is nothing to draw to.
+    pub fn draw_frame(&mut self) -> FrameStats {
+        let start = Instant::now();
+        self.sync_camera();
+        let (Some(surface), Some(context), Some(renderer)) =
+            (&self.surface, &self.context, &self.renderer)
+        else {
+            return FrameStats::default();
+        };
+        renderer.begin_gpu_timing();
+        renderer.draw();
+        if let Some(demo) = &self.normal_map_demo {
+            renderer.draw_mesh(&demo.quad);
+        }
+        if let Err(err) = renderer.draw_grid(GRID_SIZE, GRID_DIVISIONS) {
+            log::error!("failed to draw the grid: {err:#}");
+        }
+        if self.show_bounds {
+            if let Err(err) = renderer.draw_mesh_bounds(BOUNDS_COLOR) {
+                log::error!("failed to draw bounding boxes: {err:#}");
+            }
+        }
+        // The id can outlive its mesh when the model is reloaded.
+        if let Some(mesh) = self
+            .selected_mesh
+            .and_then(|id| renderer.meshes().get(id.0))
+        {
+            renderer.draw_outlined(mesh, SELECTION_COLOR, SELECTION_THICKNESS);
+        }
+        if let (Some(transforms), Some(mesh)) =
+            (&self.instance_transforms, renderer.meshes().first())
+        {
+            renderer.draw_instanced(mesh, transforms);
+        }
+        let stats = FrameStats {
+            gpu_time: renderer.end_gpu_timing(),
+            cpu_time: start.elapsed(),
+        };
+        if std::mem::take(&mut self.screenshot_requested) {
+            let path = screenshot_path();
+            match renderer.capture_screenshot(&path) {
+                Ok(()) => log::info!("saved screenshot to {}", path.display()),
+                Err(err) => log::error!("{err:#}"),
+            }
+        }
+        // With nothing requesting redraws the loop idles in `ControlFlow::Wait`.
+        if self.rendering_active {
+            self.window.request_redraw();
+        }
+        let _ = surface.swap_buffers(context);
+        self.frame_history.record(stats);
+        stats
+    }
+
+    /// Timings of the last few hundred frames drawn.
+    pub fn frame_stats(&self) -> &FrameHistory {
+        &self.frame_history
+    }
+
+    /// Turns the model about the world axes while the arrow keys (yaw and pitch) or Q/E (roll) are
+    /// held, scaled by the time since the last frame.
+    fn update_model_rotation(&mut self, dt: f32) {
+        let Some(renderer) = self.renderer.as_mut() else {
+            return;
+        };
+        let mut rotation = Vec3::ZERO;
+        for key in &self.pressed_keys {
+            rotation += match key {
+                KeyCode::ArrowLeft => -Vec3::Y,
+                KeyCode::ArrowRight => Vec3::Y,
+                KeyCode::ArrowUp => -Vec3::X,
+                KeyCode::ArrowDown => Vec3::X,
+                KeyCode::KeyQ => Vec3::Z,
+                KeyCode::KeyE => -Vec3::Z,
+                _ => Vec3::ZERO,
+            };
+        }
+        if rotation != Vec3::ZERO {
+            let angle = rotation * MODEL_ROTATE_SPEED * dt;
+            renderer.model_matrix = Mat4::from_rotation_z(angle.z)
+                * Mat4::from_rotation_x(angle.x)
+                * Mat4::from_rotation_y(angle.y)
+                * renderer.model_matrix;
+        }
+    }
+
+    /// Moves the fly camera according to the held keys, scaled by the time since the last frame.
+    fn update_camera(&mut self, dt: f32) {
+        if let CameraMode::Fly(camera) = &mut self.camera {
+            let forward = camera.forward();
+            let right = camera.right();
+            let mut direction = Vec3::ZERO;
+            for key in &self.pressed_keys {
+                direction += match key {
+                    KeyCode::KeyW => forward,
+                    KeyCode::KeyS => -forward,
+                    KeyCode::KeyD => right,
+                    KeyCode::KeyA => -right,
+                    KeyCode::Space => Vec3::Y,
+                    KeyCode::KeyC => -Vec3::Y,
+                    _ => Vec3::ZERO,
+                };
+            }
+            let mut speed = self.camera_speed;
+            if self.pressed_keys.contains(&KeyCode::ControlLeft) {
+                speed *= PRECISE_SPEED_FACTOR;
+            }
+            if self.pressed_keys.contains(&KeyCode::ShiftLeft) {
+                speed *= FAST_SPEED_FACTOR;
+            }
+            camera.position += direction.normalize_or_zero() * speed * dt;
+        }
+    }
+
+    /// Hands the camera to the renderer. Done every frame rather than every update, so mouse
+    /// look isn't held to the update rate.
+    fn sync_camera(&mut self) {
+        if let Some(renderer) = self.renderer.as_mut() {
+            renderer.set_camera(&self.camera);
+            // Zooming an orbit camera only changes its distance, which an orthographic view
+            // wouldn't show otherwise.
+            if let Projection::Orthographic { near, far, .. } = renderer.projection() {
+                let height = orthographic_height(&self.camera);
+                let _ = renderer.set_projection(Projection::Orthographic { height, near, far });
+            }
+        }
+    }
+}
+
+/// How a toggle's new state is logged.
+fn on_off(on: bool) -> &'static str {
+    if on {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+/// Height of the orthographic view matching what `camera`'s perspective view shows at its focus.
+fn orthographic_height(camera: &CameraMode) -> f32 {
+    let distance = match camera {
+        CameraMode::Fly(_) => ORTHOGRAPHIC_FLY_DISTANCE,
+        CameraMode::Orbit(orbit) => orbit.distance,
+    };
+    2.0 * distance * (camera.fov() / 2.0).tan()
 }
 
 impl ApplicationHandler for GfWindow {
-    /// Unused, all initialization is done in main.
-    fn resumed(&mut self, _event_loop: &winit::event_loop::ActiveEventLoop) {}
+    /// The first resume finds the GL state created in main. Later ones follow a `suspended`, so
+    /// the surface and renderer are rebuilt on the kept context.
+    fn resumed(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
+        if self.surface.is_some() {
+            return;
+        }
+        if let Err(err) = self.restore_gl_state() {
+            self.exit_state = Err(err.context("failed to restore GL state after resuming"));
+            event_loop.exit();
+        }
+    }
+
+    /// Saves the camera for the next run, as a fly camera with the same view.
+    fn exiting(&mut self, _event_loop: &winit::event_loop::ActiveEventLoop) {
+        match self.camera.fly_camera().save(Path::new(CAMERA_STATE_PATH)) {
+            Ok(()) => log::debug!("saved the camera to {CAMERA_STATE_PATH}"),
+            Err(err) => log::warn!("failed to save the camera: {err:#}"),
+        }
+    }
+
+    /// The surface may be destroyed once the app is suspended, so release it along with the
+    /// renderer's GL objects, keeping a snapshot of the renderer to build it again from on resume.
+    /// The renderer is dropped first, while its context is still current. The normal mapping demo
+    /// ends, putting the renderer's own lights back before the snapshot.
+    fn suspended(&mut self, _event_loop: &winit::event_loop::ActiveEventLoop) {
+        if let Some(renderer) = self.renderer.as_mut() {
+            if let Some(demo) = self.normal_map_demo.take() {
+                demo.quad.delete(renderer.gl());
+                renderer.set_lights(&demo.original_lights);
+            }
+            self.suspended_renderer = Some(renderer.snapshot());
+        }
+        self.renderer = None;
+        self.surface = None;
+    }
+
     fn window_event(
         &mut self,
-        _event_loop: &winit::event_loop::ActiveEventLoop,
+        event_loop: &winit::event_loop::ActiveEventLoop,
         _window_id: winit::window::WindowId,
         event: winit::event::WindowEvent,
     ) {
         match event {
+            WindowEvent::CloseRequested => event_loop.exit(),
             WindowEvent::RedrawRequested => {
-                self.renderer.as_ref().unwrap().draw();
-                self.window.request_redraw();
-                let _ = self
-                    .surface
-                    .as_ref()
-                    .unwrap()
-                    .swap_buffers(self.context.as_ref().unwrap());
+                let dt = self.frame_timer.tick();
+                self.update_backlog = (self.update_backlog + dt).min(MAX_UPDATE_BACKLOG);
+                // The leftover is kept for the next frame, so updates average out to real time.
+                while self.update_backlog >= UPDATE_STEP {
+                    self.update_backlog -= UPDATE_STEP;
+                    self.update(UPDATE_STEP.as_secs_f32());
+                }
+                if self.last_title_update.elapsed() >= TITLE_UPDATE_INTERVAL {
+                    self.last_title_update = Instant::now();
+                    self.window
+                        .set_title(&format!("{} — {:.0} fps", self.title, self.fps()));
+                }
+                if let (Some(hook), Some(renderer)) = (&mut self.redraw_hook, &mut self.renderer) {
+                    hook(renderer, dt);
+                }
+                self.draw_frame();
             }
             WindowEvent::Resized(size) => {
-                self.surface.as_ref().unwrap().resize(
-                    self.context.as_ref().unwrap(),
-                    NonZero::new(size.width).unwrap(),
-                    NonZero::new(size.height).unwrap(),
-                );
-                self.renderer
-                    .as_ref()
-                    .unwrap()
-                    .resize(size.width as i32, size.height as i32);
+                // Resizes can arrive before the GL state is handed over in `run`, and minimizing
+                // reports a zero size that the surface can't take.
+                let (Some(surface), Some(context), Some(renderer)) =
+                    (&self.surface, &self.context, &mut self.renderer)
+                else {
+                    return;
+                };
+                let (Some(width), Some(height)) =
+                    (NonZero::new(size.width), NonZero::new(size.height))
+                else {
+                    return;
+                };
+                surface.resize(context, width, height);
+                renderer.resize(size.width as i32, size.height as i32);
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(key),
+                        state,
+                        ..
+                    },
+                ..
+            } => match state {
+                ElementState::Pressed => {
+                    // Held keys repeat their press events, so only the first one is a shortcut.
+                    if self.pressed_keys.insert(key) {
+                        self.handle_shortcut(key);
+                    }
+                }
+                ElementState::Released => {
+                    self.pressed_keys.remove(&key);
+                }
+            },
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Left,
+                ..
+            } => {
+                self.orbit_dragging = state == ElementState::Pressed;
+                if state == ElementState::Pressed {
+                    self.pick_mesh();
+                }
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                let last_position = self.cursor_position.replace(position);
+                if let (CameraMode::Orbit(orbit), Some(last), true) =
+                    (&mut self.camera, last_position, self.orbit_dragging)
+                {
+                    orbit.rotate(
+                        (position.x - last.x) as f32 * ORBIT_SENSITIVITY,
+                        -(position.y - last.y) as f32 * ORBIT_SENSITIVITY,
+                    );
+                }
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let lines = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => y,
+                    MouseScrollDelta::PixelDelta(position) => (position.y / PIXELS_PER_LINE) as f32,
+                };
+                match &mut self.camera {
+                    CameraMode::Orbit(orbit) => orbit.zoom(ZOOM_STEP.powf(lines)),
+                    // Scrolling up speeds up, like zooming in.
+                    CameraMode::Fly(_) => {
+                        self.camera_speed = (self.camera_speed * CAMERA_SPEED_STEP.powf(lines))
+                            .clamp(MIN_CAMERA_SPEED, MAX_CAMERA_SPEED);
+                    }
+                }
+            }
+            WindowEvent::Focused(focused) if self.pause_on_unfocus => {
+                self.rendering_active = focused;
+                if focused {
+                    // Don't count the time spent paused as one long frame.
+                    self.frame_timer.reset();
+                    self.window.request_redraw();
+                }
             }
             _ => (),
         }
     }
+
+    fn device_event(
+        &mut self,
+        _event_loop: &winit::event_loop::ActiveEventLoop,
+        _device_id: winit::event::DeviceId,
+        event: DeviceEvent,
+    ) {
+        // Raw motion drives the fly camera; the orbit camera follows cursor drags instead.
+        if let (DeviceEvent::MouseMotion { delta: (dx, dy) }, CameraMode::Fly(camera)) =
+            (event, &mut self.camera)
+        {
+            camera.rotate(
+                dx as f32 * MOUSE_SENSITIVITY,
+                -dy as f32 * MOUSE_SENSITIVITY,
+            );
+        }
+    }
 }
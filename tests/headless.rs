@@ -0,0 +1,412 @@
+//! Renders offscreen through an EGL device and checks pixels of the result. They need a GPU or
+//! a software driver such as llvmpipe, so they only run with `cargo test -- --ignored`.
+#![cfg(not(target_vendor = "apple"))]
+
+use glam::{vec3, DVec3, Mat4, Vec3};
+use image::{Rgba, RgbaImage};
+use model_loading::{
+    camera::Camera,
+    headless::HeadlessContext,
+    light::Light,
+    mesh::{Mesh, MeshData, Vertex},
+    renderer::{BlendMode, CullMode, DebugView, EdgeStyle, Renderer, ShadingModel, SsaoParams},
+    texture::Texture,
+    window::gl,
+};
+
+const SIZE: u32 = 128;
+
+/// A renderer drawing `data` unlit in its vertex colors, in place of the default triangle.
+fn renderer_with(context: &HeadlessContext, data: &MeshData) -> Renderer {
+    let mut renderer = context.create_gl_renderer().unwrap();
+    for mesh in renderer.scene_mut().meshes.drain(..).collect::<Vec<_>>() {
+        mesh.delete(renderer.gl());
+    }
+    renderer.add_mesh(Mesh::from_data(renderer.gl(), data).unwrap());
+    renderer.set_shading(ShadingModel::Unlit).unwrap();
+    renderer
+}
+
+fn count(image: &RgbaImage, color: Vec3) -> usize {
+    let color = Rgba(color.extend(1.0).to_array().map(|c| (c * 255.0) as u8));
+    image.pixels().filter(|&&pixel| pixel == color).count()
+}
+
+/// A tetrahedron pointing at the default camera, with its three front faces red, green and blue
+/// and the base behind them, white, last in the index buffer so it's drawn over them unless depth
+/// testing keeps it out.
+fn tetrahedron() -> MeshData {
+    let apex = vec3(0.0, 0.0, 1.0);
+    let base = [
+        vec3(0.0, 0.8, -0.5),
+        vec3(-0.7, -0.4, -0.5),
+        vec3(0.7, -0.4, -0.5),
+    ];
+    let faces = [
+        ([apex, base[0], base[1]], Vec3::X),
+        ([apex, base[1], base[2]], Vec3::Y),
+        ([apex, base[2], base[0]], Vec3::Z),
+        ([base[0], base[2], base[1]], Vec3::ONE),
+    ];
+    let vertices: Vec<Vertex> = faces
+        .iter()
+        .flat_map(|&(corners, color)| {
+            corners.map(|position| Vertex {
+                position,
+                color,
+                ..Default::default()
+            })
+        })
+        .collect();
+    MeshData {
+        indices: (0..vertices.len() as u32).collect(),
+        vertices,
+        ..Default::default()
+    }
+}
+
+#[test]
+#[ignore = "needs an EGL device"]
+fn tetrahedron_base_is_hidden_behind_the_front_faces() {
+    let context = HeadlessContext::new().unwrap();
+    let mut renderer = renderer_with(&context, &tetrahedron());
+
+    let image = renderer.render_to_image(SIZE, SIZE).unwrap();
+    for color in [Vec3::X, Vec3::Y, Vec3::Z] {
+        assert!(count(&image, color) > 100, "front face {color} is missing");
+    }
+    assert_eq!(count(&image, Vec3::ONE), 0, "the base shows through");
+
+    // Without depth testing the base is drawn over the faces, so the check above can fail.
+    renderer.set_depth_test(false);
+    let image = renderer.render_to_image(SIZE, SIZE).unwrap();
+    assert!(count(&image, Vec3::ONE) > 100);
+}
+
+#[test]
+#[ignore = "needs an EGL device"]
+fn render_to_image_reads_back_the_scene_top_row_first() {
+    let context = HeadlessContext::new().unwrap();
+    let mut renderer = context.create_gl_renderer().unwrap();
+    renderer.set_shading(ShadingModel::Unlit).unwrap();
+    renderer.set_clear_color(0.0, 0.25, 0.5, 1.0);
+
+    // Wider than tall, so swapping the two would show.
+    let (width, height) = (160, 120);
+    let image = renderer.render_to_image(width, height).unwrap();
+    assert_eq!(image.dimensions(), (width, height));
+    for (x, y) in [
+        (0, 0),
+        (width - 1, 0),
+        (0, height - 1),
+        (width - 1, height - 1),
+    ] {
+        let Rgba([red, green, blue, alpha]) = *image.get_pixel(x, y);
+        assert!(red == 0 && green.abs_diff(64) <= 1 && blue.abs_diff(128) <= 1 && alpha == 255);
+    }
+
+    // The default triangle has its green corner at the top, its red one at the bottom left and
+    // its blue one at the bottom right.
+    let dominant = |x: f32, y: f32| {
+        let pixel = image.get_pixel((x * width as f32) as u32, (y * height as f32) as u32);
+        let [red, green, blue, _] = pixel.0;
+        if red > green.max(blue) {
+            Vec3::X
+        } else if green > red.max(blue) {
+            Vec3::Y
+        } else {
+            Vec3::Z
+        }
+    };
+    assert_eq!(dominant(0.5, 0.34), Vec3::Y);
+    assert_eq!(dominant(0.4, 0.66), Vec3::X);
+    assert_eq!(dominant(0.6, 0.66), Vec3::Z);
+}
+
+#[test]
+#[ignore = "needs an EGL device"]
+fn flipped_winding_turns_the_tetrahedron_inside_out_for_culling() {
+    let context = HeadlessContext::new().unwrap();
+    let mut renderer = renderer_with(&context, &tetrahedron());
+    renderer.set_depth_test(false);
+    renderer.set_cull_mode(CullMode::Back);
+    let flip = |renderer: &mut Renderer| {
+        let mut mesh = renderer.scene_mut().meshes.pop().unwrap();
+        mesh.flip_winding(renderer.gl());
+        renderer.add_mesh(mesh);
+    };
+
+    // Culling alone keeps the base out, as it faces away from the camera.
+    let image = renderer.render_to_image(SIZE, SIZE).unwrap();
+    assert_eq!(count(&image, Vec3::ONE), 0);
+
+    // Flipped, only the base faces the camera.
+    flip(&mut renderer);
+    let image = renderer.render_to_image(SIZE, SIZE).unwrap();
+    assert!(count(&image, Vec3::ONE) > 100);
+    for color in [Vec3::X, Vec3::Y, Vec3::Z] {
+        assert_eq!(count(&image, color), 0, "front face {color} is drawn");
+    }
+
+    // Flipping starts from the mesh's copy of the indices, so this only restores the winding if
+    // the first flip updated it.
+    flip(&mut renderer);
+    let image = renderer.render_to_image(SIZE, SIZE).unwrap();
+    assert_eq!(count(&image, Vec3::ONE), 0);
+}
+
+/// Binds a framebuffer with a `width`x`height` RGBA8 color renderbuffer in place of the window's,
+/// which a surfaceless context doesn't have.
+fn bind_window_stand_in(renderer: &Renderer, width: i32, height: i32) {
+    unsafe {
+        let (mut framebuffer, mut renderbuffer) = (0, 0);
+        renderer.GenFramebuffers(1, &mut framebuffer);
+        renderer.GenRenderbuffers(1, &mut renderbuffer);
+        renderer.BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+        renderer.BindRenderbuffer(gl::RENDERBUFFER, renderbuffer);
+        renderer.RenderbufferStorage(gl::RENDERBUFFER, gl::RGBA8, width, height);
+        renderer.FramebufferRenderbuffer(
+            gl::FRAMEBUFFER,
+            gl::COLOR_ATTACHMENT0,
+            gl::RENDERBUFFER,
+            renderbuffer,
+        );
+    }
+}
+
+#[test]
+#[ignore = "needs an EGL device"]
+fn clears_leave_the_area_outside_the_scissor_box_alone() {
+    let context = HeadlessContext::new().unwrap();
+    let mut renderer = context.create_gl_renderer().unwrap();
+    let (width, height) = (64, 48);
+    renderer.resize(width, height);
+    bind_window_stand_in(&renderer, width, height);
+
+    renderer.clear_with_color(1.0, 0.0, 0.0, 1.0);
+    let scissor = (16, 8, 32, 24);
+    renderer.set_scissor(Some(scissor)).unwrap();
+    renderer.clear_with_color(0.0, 0.0, 1.0, 1.0);
+
+    let mut pixels = vec![[0u8; 4]; (width * height) as usize];
+    unsafe {
+        renderer.ReadPixels(
+            0,
+            0,
+            width,
+            height,
+            gl::RGBA,
+            gl::UNSIGNED_BYTE,
+            pixels.as_mut_ptr().cast(),
+        );
+    }
+    let (x0, y0, box_width, box_height) = scissor;
+    for (index, pixel) in pixels.iter().enumerate() {
+        let (x, y) = (index as i32 % width, index as i32 / width);
+        let inside = (x0..x0 + box_width).contains(&x) && (y0..y0 + box_height).contains(&y);
+        let expected = if inside {
+            [0, 0, 255, 255]
+        } else {
+            [255, 0, 0, 255]
+        };
+        assert_eq!(*pixel, expected, "pixel ({x}, {y})");
+    }
+
+    // A negative size is refused and keeps the box that was set.
+    assert!(renderer.set_scissor(Some((0, 0, -1, 4))).is_err());
+    assert_eq!(renderer.scissor(), Some(scissor));
+}
+
+#[test]
+#[ignore = "needs an EGL device"]
+fn split_views_cover_the_window_despite_a_scissor_box() {
+    let context = HeadlessContext::new().unwrap();
+    let mut renderer = context.create_gl_renderer().unwrap();
+    let (width, height) = (64, 48);
+    renderer.resize(width, height);
+    bind_window_stand_in(&renderer, width, height);
+    renderer.clear_with_color(1.0, 0.0, 0.0, 1.0);
+    renderer.set_scissor(Some((30, 20, 4, 4))).unwrap();
+    renderer.set_clear_color(0.0, 0.0, 1.0, 1.0);
+
+    let camera = Camera::default();
+    renderer.draw_split(&camera, &camera);
+    let mut pixel = [0u8; 4];
+    for (x, y) in [(0, 0), (width - 1, height - 1)] {
+        unsafe {
+            renderer.ReadPixels(
+                x,
+                y,
+                1,
+                1,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixel.as_mut_ptr().cast(),
+            );
+        }
+        assert_eq!(pixel, [0, 0, 255, 255], "pixel ({x}, {y})");
+    }
+    assert_eq!(unsafe { renderer.IsEnabled(gl::SCISSOR_TEST) }, gl::TRUE);
+}
+
+#[test]
+#[ignore = "needs an EGL device"]
+fn merged_meshes_keep_their_uv_flip_origin_and_names() {
+    let context = HeadlessContext::new().unwrap();
+    let mut renderer = context.create_gl_renderer().unwrap();
+    for mesh in renderer.scene_mut().meshes.drain(..).collect::<Vec<_>>() {
+        mesh.delete(renderer.gl());
+    }
+    for (name, flip_uv_v, origin_offset) in [
+        ("a.obj", false, DVec3::ZERO),
+        ("a.obj", false, DVec3::ZERO),
+        ("b.obj", true, DVec3::ZERO),
+        ("c.obj", false, DVec3::ZERO),
+        ("d.ply", false, DVec3::X),
+    ] {
+        let mut mesh = Mesh::from_data(renderer.gl(), &tetrahedron()).unwrap();
+        mesh.set_name(name);
+        mesh.set_flip_uv_v(flip_uv_v);
+        mesh.set_origin_offset(origin_offset);
+        renderer.add_mesh(mesh);
+    }
+
+    // Only the three sharing the flip and the origin can be merged.
+    assert_eq!(renderer.merge_static_meshes(|_, _| false).unwrap(), 2);
+    let meshes = renderer.meshes();
+    let summary: Vec<_> = meshes
+        .iter()
+        .map(|mesh| (mesh.name().unwrap(), mesh.flip_uv_v(), mesh.origin_offset()))
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            ("b.obj", true, DVec3::ZERO),
+            ("d.ply", false, DVec3::X),
+            ("a.obj, c.obj", false, DVec3::ZERO),
+        ]
+    );
+    assert_eq!(meshes[2].triangle_count(), 3 * meshes[0].triangle_count());
+}
+
+#[test]
+#[ignore = "needs an EGL device"]
+fn traces_record_the_draw_calls_of_a_frame() {
+    let context = HeadlessContext::new().unwrap();
+    let renderer = context.create_gl_renderer().unwrap();
+    let path = std::env::temp_dir().join(format!("headless-trace-{}.txt", std::process::id()));
+
+    renderer.begin_trace();
+    renderer.render_to_image(SIZE, SIZE).unwrap();
+    renderer.end_trace(&path).unwrap();
+    let trace = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let lines: Vec<&str> = trace.lines().collect();
+    for (call_no, line) in lines.iter().enumerate() {
+        assert!(
+            line.starts_with(&format!("{call_no} gl")),
+            "line {call_no}: {line}"
+        );
+    }
+    // The default triangle, drawn with GL_TRIANGLES from a 16 bit index buffer.
+    let draw = lines
+        .iter()
+        .find(|line| line.contains(" glDrawElements("))
+        .expect("no draw call was traced");
+    assert!(
+        draw.contains("(mode = 4, count = 3, type_ = 5123, "),
+        "{draw}"
+    );
+    assert!(lines.iter().any(|line| line.contains(" glReadPixels(")));
+
+    // Nothing is recorded once the trace has ended.
+    assert!(!renderer.is_tracing());
+    assert!(renderer.end_trace(&path).is_err());
+}
+
+#[test]
+#[ignore = "needs an EGL device"]
+fn snapshots_rebuild_the_renderer_on_a_new_context() {
+    let context = HeadlessContext::new().unwrap();
+    let mut renderer = renderer_with(&context, &tetrahedron());
+    let mut textured = Mesh::from_data(renderer.gl(), &tetrahedron()).unwrap();
+    let texture_image =
+        RgbaImage::from_fn(4, 2, |x, y| Rgba([x as u8 * 60, y as u8 * 120, 7, 255]));
+    textured.set_texture(
+        renderer.gl(),
+        Texture::from_image(renderer.gl(), &texture_image).unwrap(),
+    );
+    textured.set_name("textured");
+    textured.model_matrix = Mat4::from_translation(Vec3::X);
+    renderer.add_mesh(textured);
+
+    let lights = [
+        Light::Directional {
+            dir: vec3(0.0, -1.0, -1.0),
+            color: Vec3::ONE,
+        },
+        Light::Point {
+            pos: Vec3::Z,
+            color: vec3(1.0, 0.5, 0.0),
+            range: 4.0,
+        },
+    ];
+    renderer
+        .set_shading(ShadingModel::Toon { bands: 3 })
+        .unwrap();
+    renderer.set_lights(&lights);
+    renderer.set_cull_mode(CullMode::Back);
+    renderer.set_blend_mode(BlendMode::Additive);
+    renderer.set_array(2, 1, 1, vec3(2.0, 0.0, 0.0));
+    renderer.set_clear_color(0.1, 0.2, 0.3, 1.0);
+    renderer.enable_shadows(256).unwrap();
+    renderer.set_ssao(Some(SsaoParams::default())).unwrap();
+    renderer
+        .set_edge_outline(Some(EdgeStyle::default()))
+        .unwrap();
+    renderer.set_debug_view(DebugView::Curvature).unwrap();
+    renderer.set_msaa_samples(4);
+    let msaa_samples = renderer.msaa_samples();
+    let faces = std::array::from_fn(|face| {
+        let path =
+            std::env::temp_dir().join(format!("headless-skybox-{}-{face}.png", std::process::id()));
+        RgbaImage::from_pixel(2, 2, Rgba([face as u8 * 40, 255, 0, 255]))
+            .save(&path)
+            .unwrap();
+        path
+    });
+    renderer.set_skybox(faces.clone()).unwrap();
+    for path in faces {
+        std::fs::remove_file(path).unwrap();
+    }
+    let image = renderer.render_to_image(SIZE, SIZE).unwrap();
+
+    // Nothing of the old context is left once it's gone, as after a suspend, and the skybox
+    // files are gone too.
+    let snapshot = renderer.snapshot();
+    drop(renderer);
+    drop(context);
+    let context = HeadlessContext::new().unwrap();
+    let renderer = context.create_gl_renderer_from_snapshot(&snapshot).unwrap();
+
+    let meshes = renderer.meshes();
+    assert_eq!(meshes.len(), 2);
+    assert_eq!(meshes[1].name(), Some("textured"));
+    assert_eq!(meshes[1].model_matrix, Mat4::from_translation(Vec3::X));
+    assert!(meshes.iter().all(|mesh| mesh.curvature_range().is_some()));
+    let texture = meshes[1].texture().expect("the texture was lost");
+    assert_eq!(texture.read_back(renderer.gl()), texture_image);
+    assert_eq!(renderer.shading(), Some(ShadingModel::Toon { bands: 3 }));
+    assert_eq!(renderer.lights(), lights);
+    assert_eq!(renderer.cull_mode(), CullMode::Back);
+    assert_eq!(renderer.blend_mode(), BlendMode::Additive);
+    assert_eq!(renderer.array(), Some(([2, 1, 1], vec3(2.0, 0.0, 0.0))));
+    assert_eq!(renderer.clear_color(), [0.1, 0.2, 0.3, 1.0]);
+    assert_eq!(renderer.shadow_resolution(), Some(256));
+    assert_eq!(renderer.ssao(), Some(SsaoParams::default()));
+    assert_eq!(renderer.edge_outline(), Some(EdgeStyle::default()));
+    assert_eq!(renderer.debug_view(), DebugView::Curvature);
+    assert_eq!(renderer.msaa_samples(), msaa_samples);
+    assert_eq!(renderer.render_to_image(SIZE, SIZE).unwrap(), image);
+}